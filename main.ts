@@ -1,11 +1,36 @@
 import { parse } from "https://deno.land/std@0.191.0/flags/mod.ts";
 import * as path from "https://deno.land/std@0.191.0/path/mod.ts";
 import { exists } from "https://deno.land/std@0.191.0/fs/exists.ts";
-import { pack } from "./mod.ts";
+import {
+  type DiagnosticSeverity,
+  type DiagnosticsFormat,
+  pack,
+} from "./mod.ts";
 
 const args = parse(Deno.args, {
-  boolean: ["no-deno-json", "no-check", "no-tests"],
-  string: ["output-folder", "build-branch", "release-tag-prefix"],
+  boolean: [
+    "no-deno-json",
+    "no-check",
+    "no-tests",
+    "source-map",
+    "declaration-map",
+    "include-remote",
+    "minimal-import-map",
+  ],
+  string: [
+    "output-folder",
+    "build-branch",
+    "release-tag-prefix",
+    "include-remote-allow",
+    "include-remote-deny",
+    "diagnostic-severity",
+    "diagnostics-format",
+  ],
+  collect: [
+    "include-remote-allow",
+    "include-remote-deny",
+    "diagnostic-severity",
+  ],
 });
 
 const firstArg = args._[0];
@@ -27,6 +52,10 @@ async function buildCommand() {
 
   const outputFolder = path.resolve(getArg("output-folder"));
   const entryPoint = path.resolve(rawEntryPoint);
+  // any additional arguments are secondary entry points (ex. `testing.ts`)
+  const additionalEntryPoints = args._.slice(2).map((arg) =>
+    path.resolve(String(arg))
+  );
   const entryPointExt = path.extname(entryPoint);
   const entryPointBaseName = path.basename(entryPoint);
   const entryPointNoExt = entryPointBaseName.slice(
@@ -45,23 +74,87 @@ async function buildCommand() {
   }
   const importMap = args["no-deno-json"]
     ? undefined
-    : path.join(path.dirname(entryPoint), "deno.json");
-  if (importMap != null && !await exists(importMap)) {
-    console.error(
-      `Expected a deno.json file at ${importMap}. Run with --no-deno-json to skip.`,
-    );
-    Deno.exit(1);
-  }
+    : await findDenoJson(path.dirname(entryPoint));
 
   await pack({
-    entryPoint,
+    entryPoint: [entryPoint, ...additionalEntryPoints],
     outputFolder,
     typeCheck: !args["no-check"],
     testFile,
     importMap,
+    sourceMap: args["source-map"],
+    declarationMap: args["declaration-map"],
+    includeRemote: getIncludeRemoteOptions(),
+    minimalImportMap: args["minimal-import-map"],
+    diagnosticSeverities: getDiagnosticSeverities(),
+    diagnosticsFormat: getDiagnosticsFormat(),
   });
 }
 
+function getDiagnosticsFormat(): DiagnosticsFormat | undefined {
+  const value = args["diagnostics-format"];
+  switch (value) {
+    case undefined:
+    case "text":
+      return undefined;
+    case "json-lines":
+      return "jsonLines";
+    case "sarif":
+      return "sarif";
+    default:
+      throw new Error(
+        `Expected --diagnostics-format to be text, json-lines, or sarif, but got: ${value}`,
+      );
+  }
+}
+
+function getDiagnosticSeverities() {
+  const values = args["diagnostic-severity"] as string[];
+  if (values.length === 0) {
+    return undefined;
+  }
+  const severities: Record<string, DiagnosticSeverity | "off"> = {};
+  for (const value of values) {
+    const index = value.lastIndexOf("=");
+    const severity = value.slice(index + 1);
+    if (
+      index === -1 ||
+      !["error", "warning", "info", "off"].includes(severity)
+    ) {
+      throw new Error(
+        `Expected --diagnostic-severity=<code>=<severity> where the severity is error, warning, info, or off, but got: ${value}`,
+      );
+    }
+    severities[value.slice(0, index)] = severity as DiagnosticSeverity | "off";
+  }
+  return severities;
+}
+
+function getIncludeRemoteOptions() {
+  const allow = args["include-remote-allow"] as string[];
+  const deny = args["include-remote-deny"] as string[];
+  if (allow.length > 0 || deny.length > 0) {
+    return { allow, deny };
+  } else {
+    return args["include-remote"];
+  }
+}
+
+async function findDenoJson(dir: string) {
+  for (const fileName of ["deno.json", "deno.jsonc"]) {
+    const filePath = path.join(dir, fileName);
+    if (await exists(filePath)) {
+      return filePath;
+    }
+  }
+  console.error(
+    `Expected a deno.json file at ${
+      path.join(dir, "deno.json")
+    }. Run with --no-deno-json to skip.`,
+  );
+  Deno.exit(1);
+}
+
 async function publishCommand() {
   const publishFile = "./publish.ts";
   const module = await import(publishFile);
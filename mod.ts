@@ -2,7 +2,10 @@ import { instantiate } from "./lib/rs_lib.generated.js";
 import * as path from "https://deno.land/std@0.191.0/path/mod.ts";
 
 export interface PackOptions {
-  entryPoint: string;
+  /** Entry point or entry points to pack. Each entry point gets its
+   * own `.js`, `.d.ts`, and `.ts` file in the output folder.
+   */
+  entryPoint: string | string[];
   outputFolder: string;
   testFile?: string;
   /** Whether to type check the outputted declaration file.
@@ -10,26 +13,111 @@ export interface PackOptions {
    */
   typeCheck: boolean;
   importMap?: string;
+  /** Whether to output a source map next to each JavaScript file.
+   * Defaults to `false`.
+   */
+  sourceMap?: boolean;
+  /** Whether to output a declaration map next to each declaration file.
+   * Defaults to `false`.
+   */
+  declarationMap?: boolean;
+  /** Remote modules to pack into the output instead of importing them.
+   * Use `true` to pack all remote modules or provide URL patterns where
+   * `*` matches any sequence of characters. Defaults to `false`.
+   */
+  includeRemote?: boolean | IncludeRemoteOptions;
+  /** Whether to output an _import_map.json_ file with only the remote
+   * mappings used by the output and warn about the unused entries.
+   * Defaults to `false`.
+   */
+  minimalImportMap?: boolean;
+  /** Severity of diagnostics by code (ex. `LP1001`) or name
+   * (ex. `missing-return-type`).
+   */
+  diagnosticSeverities?: Record<string, DiagnosticSeverity | "off">;
+  /** Format to output the diagnostics in. The `jsonLines` and `sarif`
+   * formats are written to stdout once packing finishes instead of as
+   * text to stderr. Defaults to `"text"`.
+   */
+  diagnosticsFormat?: DiagnosticsFormat;
+  /** How JSX in `.jsx` and `.tsx` files is transformed. Defaults to the
+   * `compilerOptions` of the `importMap` when it's a deno.json file.
+   */
+  jsx?: JsxOptions;
+  /** Whether to log how long packing the JavaScript of each entry point
+   * and its slowest modules took. Defaults to `false`.
+   */
+  timings?: boolean;
+  /** Whether to output the declarations of the packed modules in a single
+   * scope and refer to them directly instead of through a namespace object
+   * per module. Defaults to `false`.
+   */
+  scopeHoist?: boolean;
   onDiagnostic?: (diagnostic: Diagnostic) => void;
 }
 
+/** Same as the JSX settings in a deno.json's `compilerOptions`. */
+export interface JsxOptions {
+  /** Defaults to `"react"`. */
+  jsx?: "react" | "react-jsx" | "react-jsxdev" | "precompile";
+  /** Defaults to `"React.createElement"`. */
+  jsxFactory?: string;
+  /** Defaults to `"React.Fragment"`. */
+  jsxFragmentFactory?: string;
+  /** Module the JSX runtime is imported from by the automatic
+   * transforms (ex. `"npm:preact"`). Defaults to `"react"`.
+   */
+  jsxImportSource?: string;
+}
+
+export interface IncludeRemoteOptions {
+  /** Patterns of the remote modules to pack. Packs all when empty. */
+  allow?: string[];
+  /** Patterns of the remote modules to never pack. */
+  deny?: string[];
+}
+
 export interface LineAndColumnDisplay {
   lineNumber: string;
   columnNumber: string;
 }
 
+export type DiagnosticSeverity = "error" | "warning" | "info";
+
+export type DiagnosticsFormat = "text" | "jsonLines" | "sarif";
+
 export interface Diagnostic {
+  /** Stable code of the diagnostic (ex. `LP1001`). */
+  code: string;
+  severity: DiagnosticSeverity;
   specifier: string;
   message: string;
   lineAndColumn: LineAndColumnDisplay | undefined;
+  endLineAndColumn?: LineAndColumnDisplay;
+  related?: RelatedDiagnostic[];
+  hint?: string;
+}
+
+export interface RelatedDiagnostic {
+  specifier: string;
+  message: string;
+  lineAndColumn: LineAndColumnDisplay | undefined;
+  endLineAndColumn?: LineAndColumnDisplay;
 }
 
 export function outputDiagnostic(diagnostic: Diagnostic) {
-  console.warn(
-    `ERROR: ${diagnostic.message} -- ${diagnostic.specifier}${
-      formatLineAndColumn(diagnostic.lineAndColumn)
-    }`,
-  );
+  let text = `${diagnostic.severity.toUpperCase()} ${diagnostic.code}: ${
+    diagnostic.message
+  } -- ${diagnostic.specifier}${formatLineAndColumn(diagnostic.lineAndColumn)}`;
+  if (diagnostic.hint != null) {
+    text += `\n  hint: ${diagnostic.hint}`;
+  }
+  for (const related of diagnostic.related ?? []) {
+    text += `\n  related: ${related.message} -- ${related.specifier}${
+      formatLineAndColumn(related.lineAndColumn)
+    }`;
+  }
+  console.warn(text);
 }
 
 function formatLineAndColumn(lineAndColumn: LineAndColumnDisplay | undefined) {
@@ -39,94 +127,164 @@ function formatLineAndColumn(lineAndColumn: LineAndColumnDisplay | undefined) {
   return `:${lineAndColumn.lineNumber}:${lineAndColumn.columnNumber}`;
 }
 
+interface UnusedImportMapEntry {
+  specifier: string;
+  scope: string | undefined;
+  key: string;
+  usedByTests: boolean;
+}
+
+interface EntryPointOutput {
+  specifier: string;
+  js: string;
+  jsSourceMap: string | undefined;
+  dts: string;
+  declarationMap: string | undefined;
+  hasDefaultExport: boolean;
+  timings: PackTimings | undefined;
+  files: OutputFile[];
+}
+
+interface OutputFile {
+  /** URL of the file relative to the output folder. */
+  relativeUrl: string;
+  text: string;
+}
+
+interface PackTimings {
+  modules: ModuleTiming[];
+  transpileMs: number;
+  printMs: number;
+}
+
+interface ModuleTiming {
+  specifier: string;
+  analyzeMs: number;
+  emitMs: number;
+}
+
 export async function pack(options: PackOptions) {
   const rs = await instantiate();
   const importMapUrl = options.importMap == null
     ? undefined
     : path.toFileUrl(path.resolve(options.importMap));
-  let diagnosticCount = 0;
+  const entryPoints = typeof options.entryPoint === "string"
+    ? [options.entryPoint]
+    : options.entryPoint;
+  const jsOutputFolder = path.resolve(options.outputFolder);
+  const jsOutputFolderUrl = path.toFileUrl(jsOutputFolder).toString() + "/";
+  const diagnosticsFormat = options.diagnosticsFormat ?? "text";
+  let errorCount = 0;
   const output: {
-    js: string;
-    dts: string;
+    entryPoints: EntryPointOutput[];
     importMap: string | undefined;
-    hasDefaultExport: boolean;
+    unusedImportMapEntries: UnusedImportMapEntry[];
+    testImportMap: string | undefined;
+    diagnosticsReport: string | undefined;
   } = await rs.pack({
-    entryPoints: [
-      path.toFileUrl(path.resolve(options.entryPoint)).toString(),
-    ],
+    entryPoints: entryPoints.map((entryPoint) =>
+      path.toFileUrl(path.resolve(entryPoint)).toString()
+    ),
     importMap: importMapUrl?.toString(),
+    sourceMap: options.sourceMap
+      ? { sourcesContent: true, urlComment: true }
+      : undefined,
+    declarationMap: options.declarationMap
+      ? { sourcesContent: false, urlComment: true }
+      : undefined,
+    includeRemote: options.includeRemote === true
+      ? {}
+      : options.includeRemote || undefined,
+    testImportMap: options.testFile == null ? undefined : {
+      outputFolder: path.toFileUrl(jsOutputFolder).toString(),
+    },
+    minimalImportMap: options.minimalImportMap
+      ? {
+        testFiles: options.testFile == null
+          ? []
+          : [path.toFileUrl(path.resolve(options.testFile)).toString()],
+      }
+      : undefined,
+    diagnostics: options.diagnosticSeverities == null
+      ? undefined
+      : { severities: options.diagnosticSeverities },
+    diagnosticsReport: diagnosticsFormat === "text" ? undefined : {
+      format: diagnosticsFormat,
+      baseUrl: path.toFileUrl(Deno.cwd()).toString() + "/",
+    },
+    jsx: options.jsx,
+    timings: options.timings ?? false,
+    scopeHoist: options.scopeHoist ?? false,
+    outputFolder: jsOutputFolderUrl,
   }, (diagnostic: Diagnostic) => {
     if (options.onDiagnostic) {
       options.onDiagnostic(diagnostic);
     } else {
-      diagnosticCount++;
-      outputDiagnostic(diagnostic);
+      if (diagnostic.severity === "error") {
+        errorCount++;
+      }
+      if (diagnosticsFormat === "text") {
+        outputDiagnostic(diagnostic);
+      }
     }
   });
-  const baseNameNoExt = path.basename(options.entryPoint).slice(
-    0,
-    path.extname(options.entryPoint).length * -1,
-  );
-  const jsOutputFolder = path.resolve(options.outputFolder);
-  const jsOutputPath = path.join(options.outputFolder, `${baseNameNoExt}.js`);
-  const tsOutputPath = path.join(options.outputFolder, `${baseNameNoExt}.ts`);
-  const dtsOutputPath = path.join(jsOutputFolder, `${baseNameNoExt}.d.ts`);
+  for (const entryPointOutput of output.entryPoints) {
+    if (entryPointOutput.timings != null) {
+      outputTimings(entryPointOutput.specifier, entryPointOutput.timings);
+    }
+  }
+  if (output.diagnosticsReport != null) {
+    await Deno.stdout.write(
+      new TextEncoder().encode(
+        output.diagnosticsReport.endsWith("\n") ||
+          output.diagnosticsReport.length === 0
+          ? output.diagnosticsReport
+          : output.diagnosticsReport + "\n",
+      ),
+    );
+  }
   await Deno.mkdir(jsOutputFolder, { recursive: true });
-  await Deno.writeTextFileSync(
-    jsOutputPath,
-    `/// <reference types="./${baseNameNoExt}.d.ts" />\n${output.js}`,
-  );
-  await Deno.writeTextFileSync(
-    tsOutputPath,
-    (() => {
-      let text =
-        `// @deno-types="./${baseNameNoExt}.d.ts"\nexport * from "./${baseNameNoExt}.js";\n`;
-      if (output.hasDefaultExport) {
-        text += `// @deno-types="./${baseNameNoExt}.d.ts"\n`;
-        text += `import defaultExport from "./${baseNameNoExt}.js";\n`;
-        text += `export default defaultExport;`;
+  const tsOutputPaths: string[] = [];
+  for (const entryPointOutput of output.entryPoints) {
+    for (const file of entryPointOutput.files) {
+      const filePath = path.fromFileUrl(
+        new URL(file.relativeUrl, jsOutputFolderUrl),
+      );
+      await Deno.writeTextFile(filePath, file.text);
+      if (filePath.endsWith(".ts") && !filePath.endsWith(".d.ts")) {
+        tsOutputPaths.push(filePath);
       }
-      return text;
-    })(),
-  );
-  // todo: https://github.com/swc-project/swc/issues/7492
-  await Deno.writeTextFileSync(
-    dtsOutputPath,
-    output.dts.replaceAll("*/ ", "*/\n"),
-  );
-  if (diagnosticCount > 0) {
+    }
+  }
+  if (options.minimalImportMap && output.importMap != null) {
+    await Deno.writeTextFile(
+      path.join(jsOutputFolder, "import_map.json"),
+      output.importMap,
+    );
+  }
+  for (const entry of output.unusedImportMapEntries) {
+    const scopeText = entry.scope == null ? "" : ` in scope "${entry.scope}"`;
+    console.warn(
+      `WARNING: Import map entry "${entry.key}"${scopeText} is ${
+        entry.usedByTests ? "only used by tests" : "unused"
+      }. -- ${entry.specifier}`,
+    );
+  }
+  if (errorCount > 0) {
     throw new Error(
-      `Failed. Had ${diagnosticCount} diagnostic${
-        diagnosticCount != 1 ? "s" : ""
-      }.`,
+      `Failed. Had ${errorCount} error${errorCount != 1 ? "s" : ""}.`,
     );
   }
   if ((options.typeCheck ?? true) && options.testFile == null) {
     const checkOutput = await new Deno.Command(Deno.execPath(), {
-      args: ["check", "--no-config", tsOutputPath],
+      args: ["check", "--no-config", ...tsOutputPaths],
     }).spawn();
     if (!await checkOutput.status) {
       Deno.exit(1);
     }
   }
   if (options.testFile != null) {
-    const importMapObj = output.importMap == null
-      ? {}
-      : JSON.parse(output.importMap);
-    importMapObj.imports ??= {};
-    importMapObj
-      .imports[path.toFileUrl(path.resolve(options.entryPoint)).toString()] =
-        path.toFileUrl(tsOutputPath).toString();
-    // todo: needs to handle scopes
-    if (importMapUrl != null) {
-      for (const [key, value] of Object.entries(importMapObj.imports)) {
-        if ((value as string).startsWith("./")) {
-          importMapObj.imports[key] = new URL(value as string, importMapUrl)
-            .toString();
-        }
-      }
-    }
-    const uri = `data:,${JSON.stringify(importMapObj)}`;
+    const uri = `data:,${encodeURIComponent(output.testImportMap!)}`;
     // todo: configurable permissions
     const args = ["test", "-A", "--import-map", uri];
     if (options.typeCheck === false) {
@@ -141,3 +299,21 @@ export async function pack(options: PackOptions) {
     }
   }
 }
+
+function outputTimings(specifier: string, timings: PackTimings) {
+  const moduleMs = (timing: ModuleTiming) =>
+    timing.analyzeMs + timing.emitMs;
+  const formatMs = (ms: number) => `${ms.toFixed(2)}ms`.padStart(11);
+  const modules = [...timings.modules].sort((a, b) =>
+    moduleMs(b) - moduleMs(a)
+  );
+  const totalModuleMs = modules.reduce((sum, t) => sum + moduleMs(t), 0);
+  console.error(`Timings for ${specifier}`);
+  console.error(`  ${formatMs(totalModuleMs)} modules (${modules.length})`);
+  console.error(`  ${formatMs(timings.transpileMs)} transpile`);
+  console.error(`  ${formatMs(timings.printMs)} print`);
+  console.error("Slowest modules:");
+  for (const timing of modules.slice(0, 10)) {
+    console.error(`  ${formatMs(moduleMs(timing))} ${timing.specifier}`);
+  }
+}
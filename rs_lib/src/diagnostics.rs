@@ -0,0 +1,467 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::fmt;
+
+use anyhow::bail;
+use deno_ast::ModuleSpecifier;
+use deno_graph::ModuleGraph;
+use serde::Deserialize;
+use serde::Serialize;
+
+/// Stable identifier of a kind of diagnostic.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DiagnosticCode {
+  /// LP1001 - A function in the public API has a return statement
+  /// without an explicit return type.
+  MissingReturnType,
+  /// LP1002 - A default export expression isn't an identifier.
+  UnsupportedDefaultExpr,
+  /// LP1003 - A class property or variable in the public API has no
+  /// explicit type and its type couldn't be inferred.
+  MissingPropertyType,
+  /// LP1004 - A namespace in the public API has an export whose name
+  /// isn't a valid identifier, which declaration namespaces can't have.
+  StringNamespaceMember,
+  /// LP1005 - An export that a packed module re-exports from a package
+  /// with `export * from "..."` can't be referenced in the declaration
+  /// file because the package's export names aren't known.
+  PackageStarReExport,
+  /// LP2001 - A module specifier couldn't be resolved.
+  UnresolvedSpecifier,
+  /// LP2002 - An export assignment (`export = x`) can't be represented
+  /// as a default export in the JS output.
+  UnsupportedExportAssignment,
+  /// LP2003 - A JSON module isn't valid JSON.
+  InvalidJsonModule,
+  /// LP2004 - A JSX pragma of a module isn't supported with the
+  /// configured JSX transform (ex. `@jsxRuntime automatic` when using
+  /// the classic transform).
+  UnsupportedJsxPragma,
+}
+
+impl DiagnosticCode {
+  pub const ALL: [DiagnosticCode; 9] = [
+    DiagnosticCode::MissingReturnType,
+    DiagnosticCode::UnsupportedDefaultExpr,
+    DiagnosticCode::MissingPropertyType,
+    DiagnosticCode::StringNamespaceMember,
+    DiagnosticCode::PackageStarReExport,
+    DiagnosticCode::UnresolvedSpecifier,
+    DiagnosticCode::UnsupportedExportAssignment,
+    DiagnosticCode::InvalidJsonModule,
+    DiagnosticCode::UnsupportedJsxPragma,
+  ];
+
+  /// Gets the code (ex. `LP1001`).
+  pub fn as_str(&self) -> &'static str {
+    match self {
+      DiagnosticCode::MissingReturnType => "LP1001",
+      DiagnosticCode::UnsupportedDefaultExpr => "LP1002",
+      DiagnosticCode::MissingPropertyType => "LP1003",
+      DiagnosticCode::StringNamespaceMember => "LP1004",
+      DiagnosticCode::PackageStarReExport => "LP1005",
+      DiagnosticCode::UnresolvedSpecifier => "LP2001",
+      DiagnosticCode::UnsupportedExportAssignment => "LP2002",
+      DiagnosticCode::InvalidJsonModule => "LP2003",
+      DiagnosticCode::UnsupportedJsxPragma => "LP2004",
+    }
+  }
+
+  /// Gets the human readable name (ex. `missing-return-type`).
+  pub fn name(&self) -> &'static str {
+    match self {
+      DiagnosticCode::MissingReturnType => "missing-return-type",
+      DiagnosticCode::UnsupportedDefaultExpr => "unsupported-default-expr",
+      DiagnosticCode::MissingPropertyType => "missing-property-type",
+      DiagnosticCode::StringNamespaceMember => "string-namespace-member",
+      DiagnosticCode::PackageStarReExport => "package-star-re-export",
+      DiagnosticCode::UnresolvedSpecifier => "unresolved-specifier",
+      DiagnosticCode::UnsupportedExportAssignment => {
+        "unsupported-export-assignment"
+      }
+      DiagnosticCode::InvalidJsonModule => "invalid-json-module",
+      DiagnosticCode::UnsupportedJsxPragma => "unsupported-jsx-pragma",
+    }
+  }
+
+  pub fn description(&self) -> &'static str {
+    match self {
+      DiagnosticCode::MissingReturnType => {
+        "Function with a return statement has no return type."
+      }
+      DiagnosticCode::UnsupportedDefaultExpr => {
+        "Default export expression is not an identifier."
+      }
+      DiagnosticCode::MissingPropertyType => {
+        "Class property or variable type could not be inferred."
+      }
+      DiagnosticCode::StringNamespaceMember => {
+        "Namespace has an export that is not a valid identifier."
+      }
+      DiagnosticCode::PackageStarReExport => {
+        "Export re-exported from a package cannot be referenced."
+      }
+      DiagnosticCode::UnresolvedSpecifier => {
+        "Module specifier could not be resolved."
+      }
+      DiagnosticCode::UnsupportedExportAssignment => {
+        "Export assignment is not supported alongside other exports."
+      }
+      DiagnosticCode::InvalidJsonModule => "JSON module is not valid JSON.",
+      DiagnosticCode::UnsupportedJsxPragma => {
+        "JSX pragma is not supported with the JSX transform."
+      }
+    }
+  }
+
+  /// Parses a code (ex. `LP1001`) or a name (ex. `missing-return-type`).
+  pub fn parse(text: &str) -> Option<Self> {
+    Self::ALL.into_iter().find(|code| {
+      code.as_str().eq_ignore_ascii_case(text) || code.name() == text
+    })
+  }
+
+  pub fn default_severity(&self) -> DiagnosticSeverity {
+    match self {
+      // these previously silently became `unknown`
+      DiagnosticCode::MissingPropertyType => DiagnosticSeverity::Warning,
+      DiagnosticCode::MissingReturnType
+      | DiagnosticCode::UnsupportedDefaultExpr
+      | DiagnosticCode::StringNamespaceMember
+      | DiagnosticCode::PackageStarReExport
+      | DiagnosticCode::UnresolvedSpecifier
+      | DiagnosticCode::UnsupportedExportAssignment
+      | DiagnosticCode::InvalidJsonModule
+      | DiagnosticCode::UnsupportedJsxPragma => DiagnosticSeverity::Error,
+    }
+  }
+}
+
+impl fmt::Display for DiagnosticCode {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.write_str(self.as_str())
+  }
+}
+
+impl Serialize for DiagnosticCode {
+  fn serialize<S: serde::Serializer>(
+    &self,
+    serializer: S,
+  ) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(self.as_str())
+  }
+}
+
+impl<'de> Deserialize<'de> for DiagnosticCode {
+  fn deserialize<D: serde::Deserializer<'de>>(
+    deserializer: D,
+  ) -> Result<Self, D::Error> {
+    let text = String::deserialize(deserializer)?;
+    DiagnosticCode::parse(&text).ok_or_else(|| {
+      serde::de::Error::custom(format!("Unknown diagnostic code '{}'.", text))
+    })
+  }
+}
+
+#[derive(
+  Debug,
+  Clone,
+  Copy,
+  Serialize,
+  Deserialize,
+  PartialEq,
+  Eq,
+  Hash,
+  PartialOrd,
+  Ord,
+)]
+#[serde(rename_all = "camelCase")]
+pub enum DiagnosticSeverity {
+  Info,
+  Warning,
+  Error,
+}
+
+impl DiagnosticSeverity {
+  pub fn as_str(&self) -> &'static str {
+    match self {
+      DiagnosticSeverity::Info => "info",
+      DiagnosticSeverity::Warning => "warning",
+      DiagnosticSeverity::Error => "error",
+    }
+  }
+}
+
+/// Severity of a diagnostic code configured by the user.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum DiagnosticSeverityConfig {
+  Error,
+  Warning,
+  Info,
+  /// Don't report the diagnostic.
+  Off,
+}
+
+impl DiagnosticSeverityConfig {
+  /// Parses `error`, `warning`, `info`, or `off`.
+  pub fn parse(text: &str) -> Option<Self> {
+    match text {
+      "error" => Some(DiagnosticSeverityConfig::Error),
+      "warning" => Some(DiagnosticSeverityConfig::Warning),
+      "info" => Some(DiagnosticSeverityConfig::Info),
+      "off" => Some(DiagnosticSeverityConfig::Off),
+      _ => None,
+    }
+  }
+
+  fn severity(&self) -> Option<DiagnosticSeverity> {
+    match self {
+      DiagnosticSeverityConfig::Error => Some(DiagnosticSeverity::Error),
+      DiagnosticSeverityConfig::Warning => Some(DiagnosticSeverity::Warning),
+      DiagnosticSeverityConfig::Info => Some(DiagnosticSeverity::Info),
+      DiagnosticSeverityConfig::Off => None,
+    }
+  }
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DiagnosticsOptions {
+  /// Severity of the diagnostics by code (ex. `LP1001`) or name
+  /// (ex. `missing-return-type`).
+  #[serde(default)]
+  pub severities: HashMap<String, DiagnosticSeverityConfig>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash)]
+#[serde(rename_all = "camelCase")]
+pub struct LineAndColumnDisplay {
+  pub line_number: usize,
+  pub column_number: usize,
+}
+
+impl From<deno_ast::LineAndColumnDisplay> for LineAndColumnDisplay {
+  fn from(value: deno_ast::LineAndColumnDisplay) -> Self {
+    Self {
+      line_number: value.line_number,
+      column_number: value.column_number,
+    }
+  }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash)]
+#[serde(rename_all = "camelCase")]
+pub struct Diagnostic {
+  pub code: DiagnosticCode,
+  pub severity: DiagnosticSeverity,
+  pub message: String,
+  pub specifier: ModuleSpecifier,
+  pub line_and_column: Option<LineAndColumnDisplay>,
+  /// Exclusive end of the range the diagnostic applies to.
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub end_line_and_column: Option<LineAndColumnDisplay>,
+  /// Other locations that help explain the diagnostic.
+  #[serde(default, skip_serializing_if = "Vec::is_empty")]
+  pub related: Vec<RelatedDiagnostic>,
+  /// Suggestion on how to fix the diagnostic.
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub hint: Option<String>,
+}
+
+impl fmt::Display for Diagnostic {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(
+      f,
+      "{} {}: {} -- {}{}",
+      self.severity.as_str().to_uppercase(),
+      self.code,
+      self.message,
+      self.specifier,
+      format_line_and_column(self.line_and_column.as_ref()),
+    )?;
+    if let Some(hint) = &self.hint {
+      write!(f, "\n  hint: {}", hint)?;
+    }
+    for related in &self.related {
+      write!(
+        f,
+        "\n  related: {} -- {}{}",
+        related.message,
+        related.specifier,
+        format_line_and_column(related.line_and_column.as_ref()),
+      )?;
+    }
+    Ok(())
+  }
+}
+
+fn format_line_and_column(
+  line_and_column: Option<&LineAndColumnDisplay>,
+) -> String {
+  match line_and_column {
+    Some(lc) => format!(":{}:{}", lc.line_number, lc.column_number),
+    None => String::new(),
+  }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash)]
+#[serde(rename_all = "camelCase")]
+pub struct RelatedDiagnostic {
+  pub message: String,
+  pub specifier: ModuleSpecifier,
+  pub line_and_column: Option<LineAndColumnDisplay>,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub end_line_and_column: Option<LineAndColumnDisplay>,
+}
+
+pub trait Reporter {
+  fn diagnostic(&self, diagnostic: Diagnostic);
+}
+
+/// Forwards diagnostics to the inner reporter only once each.
+///
+/// Modules shared by multiple entry points are analyzed once per
+/// entry point, so this prevents reporting the same diagnostic twice.
+pub(crate) struct DedupReporter<'a, TReporter: Reporter> {
+  inner: &'a TReporter,
+  seen: RefCell<HashSet<Diagnostic>>,
+}
+
+impl<'a, TReporter: Reporter> DedupReporter<'a, TReporter> {
+  pub fn new(inner: &'a TReporter) -> Self {
+    Self {
+      inner,
+      seen: Default::default(),
+    }
+  }
+}
+
+impl<'a, TReporter: Reporter> Reporter for DedupReporter<'a, TReporter> {
+  fn diagnostic(&self, diagnostic: Diagnostic) {
+    if self.seen.borrow_mut().insert(diagnostic.clone()) {
+      self.inner.diagnostic(diagnostic);
+    }
+  }
+}
+
+/// Holds diagnostics until they're forwarded to the inner reporter
+/// with `flush`.
+///
+/// The declarations are packed before the JavaScript, which takes the
+/// modules of the last entry point, but their diagnostics are reported
+/// after the JavaScript's.
+pub(crate) struct DeferredReporter<'a, TReporter: Reporter> {
+  inner: &'a TReporter,
+  diagnostics: RefCell<Vec<Diagnostic>>,
+}
+
+impl<'a, TReporter: Reporter> DeferredReporter<'a, TReporter> {
+  pub fn new(inner: &'a TReporter) -> Self {
+    Self {
+      inner,
+      diagnostics: Default::default(),
+    }
+  }
+
+  pub fn flush(&self) {
+    for diagnostic in self.diagnostics.take() {
+      self.inner.diagnostic(diagnostic);
+    }
+  }
+}
+
+impl<'a, TReporter: Reporter> Reporter for DeferredReporter<'a, TReporter> {
+  fn diagnostic(&self, diagnostic: Diagnostic) {
+    self.diagnostics.borrow_mut().push(diagnostic);
+  }
+}
+
+/// Applies the configured severities and drops the diagnostics
+/// suppressed with a `// libpack-ignore <code>` comment on the line
+/// before.
+pub(crate) struct ConfiguredReporter<'a, TReporter: Reporter> {
+  inner: &'a TReporter,
+  graph: &'a ModuleGraph,
+  severities: HashMap<DiagnosticCode, DiagnosticSeverityConfig>,
+}
+
+impl<'a, TReporter: Reporter> ConfiguredReporter<'a, TReporter> {
+  pub fn new(
+    inner: &'a TReporter,
+    graph: &'a ModuleGraph,
+    options: Option<&DiagnosticsOptions>,
+  ) -> Result<Self, anyhow::Error> {
+    let mut severities = HashMap::new();
+    if let Some(options) = options {
+      for (key, severity) in &options.severities {
+        let Some(code) = DiagnosticCode::parse(key) else {
+          bail!("Unknown diagnostic code '{}'.", key);
+        };
+        severities.insert(code, *severity);
+      }
+    }
+    Ok(Self {
+      inner,
+      graph,
+      severities,
+    })
+  }
+
+  fn is_ignored(&self, diagnostic: &Diagnostic) -> bool {
+    let Some(line_and_column) = &diagnostic.line_and_column else {
+      return false;
+    };
+    let Some(deno_graph::Module::Esm(module)) =
+      self.graph.get(&diagnostic.specifier)
+    else {
+      return false;
+    };
+    let Some(previous_line_index) = line_and_column.line_number.checked_sub(2)
+    else {
+      return false;
+    };
+    module
+      .source
+      .lines()
+      .nth(previous_line_index)
+      .map(|line| is_ignore_comment_for(line, diagnostic.code))
+      .unwrap_or(false)
+  }
+}
+
+impl<'a, TReporter: Reporter> Reporter for ConfiguredReporter<'a, TReporter> {
+  fn diagnostic(&self, mut diagnostic: Diagnostic) {
+    if self.is_ignored(&diagnostic) {
+      return;
+    }
+    if let Some(config) = self.severities.get(&diagnostic.code) {
+      match config.severity() {
+        Some(severity) => diagnostic.severity = severity,
+        None => return,
+      }
+    }
+    self.inner.diagnostic(diagnostic);
+  }
+}
+
+/// Gets if the line is a `// libpack-ignore` comment for the code. The
+/// comment ignores all diagnostics when no codes are provided.
+fn is_ignore_comment_for(line: &str, code: DiagnosticCode) -> bool {
+  let Some(comment) = line.trim().strip_prefix("//") else {
+    return false;
+  };
+  let Some(codes) = comment.trim_start().strip_prefix("libpack-ignore") else {
+    return false;
+  };
+  if !codes.is_empty() && !codes.starts_with(char::is_whitespace) {
+    return false;
+  }
+  let mut codes = codes
+    .split(|c: char| c.is_whitespace() || c == ',')
+    .filter(|c| !c.is_empty())
+    .peekable();
+  codes.peek().is_none()
+    || codes.any(|text| DiagnosticCode::parse(text) == Some(code))
+}
@@ -1,11 +1,14 @@
+use std::cell::RefCell;
 use std::collections::HashSet;
 use std::rc::Rc;
 
 use deno_ast::swc::ast::*;
 use deno_ast::swc::common::comments::CommentKind;
 use deno_ast::swc::common::comments::SingleThreadedComments;
+use deno_ast::swc::common::EqIgnoreSpan;
 use deno_ast::swc::common::FileName;
 use deno_ast::swc::common::SourceMap;
+use deno_ast::swc::common::Span;
 use deno_ast::swc::common::Spanned;
 use deno_ast::swc::common::DUMMY_SP;
 use deno_ast::swc::visit::*;
@@ -14,20 +17,69 @@ use deno_ast::ParsedSource;
 use deno_ast::SourcePos;
 use deno_ast::SourceRange;
 use deno_ast::SourceRangedForSpanned;
+use deno_graph::type_tracer::ExportDeclRef;
 use deno_graph::type_tracer::ImportedExports;
 use deno_graph::type_tracer::ModuleId;
 use deno_graph::type_tracer::RootSymbol;
+use deno_graph::type_tracer::SymbolNodeRef;
+use deno_graph::type_tracer::TypeTraceDiagnosticKind;
 use deno_graph::CapturingModuleParser;
 use deno_graph::ModuleGraph;
 
+use crate::externals::external_specifier_text;
+use crate::externals::is_opaque_external;
+use crate::fix::print_type;
+use crate::fix::FixCollector;
 use crate::helpers::adjust_spans;
 use crate::helpers::fill_leading_comments;
 use crate::helpers::ident;
+use crate::helpers::is_packed_specifier;
 use crate::helpers::is_remote_specifier;
+use crate::helpers::is_valid_ident;
+use crate::helpers::module_export_name;
+use crate::helpers::module_export_name_text;
+use crate::helpers::parse_json_module_expr;
 use crate::helpers::print_program;
 use crate::helpers::ts_keyword_type;
+use crate::helpers::PrintedProgram;
 use crate::Diagnostic;
+use crate::DiagnosticCode;
+use crate::IncludeRemoteOptions;
+use crate::RelatedDiagnostic;
 use crate::Reporter;
+use crate::SourceMapOptions;
+
+/// Global classes that aren't generic, so the type of an instance
+/// is complete without type arguments.
+const NON_GENERIC_GLOBAL_CLASSES: &[&str] = &[
+  "AbortController",
+  "Blob",
+  "Date",
+  "Error",
+  "EvalError",
+  "FormData",
+  "Headers",
+  "Intl.Collator",
+  "Intl.DateTimeFormat",
+  "Intl.ListFormat",
+  "Intl.Locale",
+  "Intl.NumberFormat",
+  "Intl.PluralRules",
+  "Intl.RelativeTimeFormat",
+  "Intl.Segmenter",
+  "RangeError",
+  "ReferenceError",
+  "RegExp",
+  "Request",
+  "Response",
+  "SyntaxError",
+  "TextDecoder",
+  "TextEncoder",
+  "TypeError",
+  "URIError",
+  "URL",
+  "URLSearchParams",
+];
 
 struct LibPackTypeTraceHandler<'a, TReporter: Reporter>(&'a TReporter);
 
@@ -38,29 +90,63 @@ impl<'a, TReporter: Reporter> deno_graph::type_tracer::TypeTraceHandler
     &self,
     diagnostic: deno_graph::type_tracer::TypeTraceDiagnostic,
   ) {
-    self.0.diagnostic(Diagnostic {
-        message: match diagnostic.kind {
-            deno_graph::type_tracer::TypeTraceDiagnosticKind::UnsupportedDefaultExpr => concat!(
-              "Default expressions that are not identifiers are not supported. ",
-              "To work around this, extract out the expression to a variable, ",
-              "type the variable, and then default export the variable declaration.",
-            ).to_string(),
-        },
-        specifier: diagnostic.specifier,
-        line_and_column: diagnostic.line_and_column.map(|line_and_column| line_and_column.into()),
-      });
+    match diagnostic.kind {
+      TypeTraceDiagnosticKind::UnsupportedDefaultExpr => {
+        self.0.diagnostic(Diagnostic {
+          code: DiagnosticCode::UnsupportedDefaultExpr,
+          severity: DiagnosticCode::UnsupportedDefaultExpr.default_severity(),
+          message:
+            "Default expressions that are not identifiers are not supported."
+              .to_string(),
+          specifier: diagnostic.specifier,
+          line_and_column: diagnostic
+            .line_and_column
+            .map(|line_and_column| line_and_column.into()),
+          end_line_and_column: None,
+          related: Vec::new(),
+          hint: Some(
+            concat!(
+              "Extract out the expression to a variable, type the variable, ",
+              "and then default export the variable declaration."
+            )
+            .to_string(),
+          ),
+        });
+      }
+    }
   }
 }
 
+pub struct PackDtsOptions<'a> {
+  /// Remote modules to pack into the output. Remote modules are left
+  /// as external imports when not provided.
+  pub include_remote: Option<IncludeRemoteOptions>,
+  /// Builds a declaration map for the output when provided.
+  pub declaration_map: Option<SourceMapOptions>,
+  /// Collects text edits for the missing types that can be inferred
+  /// instead of reporting them when provided.
+  pub fixes: Option<&'a FixCollector>,
+}
+
+pub struct PackDtsOutput {
+  pub text: String,
+  pub declaration_map: Option<String>,
+}
+
+/// Packs the public types of the provided root into a single
+/// declaration file.
 pub fn pack_dts(
   graph: &ModuleGraph,
   parser: &CapturingModuleParser,
+  root: &ModuleSpecifier,
+  options: PackDtsOptions<'_>,
   reporter: &impl Reporter,
-) -> Result<String, anyhow::Error> {
+) -> Result<PackDtsOutput, anyhow::Error> {
+  let root = graph.resolve(root);
   // run the tracer
   let root_symbol = deno_graph::type_tracer::trace_public_types(
-    &graph,
-    &graph.roots,
+    graph,
+    std::slice::from_ref(&root),
     parser,
     &LibPackTypeTraceHandler(reporter),
   )?;
@@ -74,9 +160,12 @@ pub fn pack_dts(
   };
   let mut remote_module_items = Vec::new();
   let mut default_remote_module_items = Vec::new();
+  let external_packages = ExternalPackages::default();
+  let external_string_imports = ExternalStringImports::default();
 
+  let include_remote = options.include_remote.as_ref();
   for graph_module in graph.modules() {
-    if is_remote_specifier(graph_module.specifier()) {
+    if !is_packed_specifier(graph_module.specifier(), include_remote) {
       if let Some(module_symbol) = root_symbol
         .get_module_from_specifier(graph_module.specifier())
         .and_then(|m| m.esm())
@@ -88,73 +177,23 @@ pub fn pack_dts(
             let Some(module) = root_symbol.get_module_from_id(*module_id) else {
               return false;
             };
-            module.specifier().scheme() == "file"
+            is_packed_specifier(module.specifier(), include_remote)
               && match imported_exports {
                 ImportedExports::AllWithDefault => true,
                 ImportedExports::Star => false,
                 ImportedExports::Named(named) => named.contains("default"),
               }
           });
+        let specifier_text = external_specifier_text(graph_module.specifier());
         if has_locally_imported_remote_default {
-          let temp_name = format!(
-            "{}DefaultImport",
-            module_symbol.module_id().to_code_string()
+          let module_id = module_symbol.module_id();
+          let (import_item, namespace_item) = default_import_items(
+            module_id.to_code_string(),
+            module_id.to_default_code_string(),
+            &specifier_text,
           );
-          remote_module_items.push(ModuleItem::ModuleDecl(ModuleDecl::Import(
-            ImportDecl {
-              span: DUMMY_SP,
-              specifiers: vec![ImportSpecifier::Named(ImportNamedSpecifier {
-                span: DUMMY_SP,
-                imported: Some(ModuleExportName::Ident(ident(
-                  "default".to_string(),
-                ))),
-                local: ident(temp_name.clone()),
-                is_type_only: false,
-              })],
-              src: Box::new(Str {
-                span: DUMMY_SP,
-                value: graph_module.specifier().to_string().into(),
-                raw: None,
-              }),
-              type_only: false,
-              with: None,
-            },
-          )));
-          // This is done because `import something = defaultImport` is not valid
-          // because `defaultImport` is not a namespace, so instead we do:
-          //   import { default as pack1DefaultImport } from "...";
-          //   declare module pack1Default {
-          //     export { pack1DefaultImport as __default };
-          //   }
-          // Then downstream code will do `import something = pack1Default.__default`
-          default_remote_module_items.push(ModuleItem::Stmt(Stmt::Decl(
-            Decl::TsModule(Box::new(TsModuleDecl {
-              span: DUMMY_SP,
-              declare: true,
-              global: false,
-              id: ident(module_symbol.module_id().to_default_code_string())
-                .into(),
-              body: Some(TsNamespaceBody::TsModuleBlock(TsModuleBlock {
-                span: DUMMY_SP,
-                body: Vec::from([ModuleItem::ModuleDecl(
-                  ModuleDecl::ExportNamed(NamedExport {
-                    span: DUMMY_SP,
-                    specifiers: Vec::from([ExportSpecifier::Named(
-                      ExportNamedSpecifier {
-                        span: DUMMY_SP,
-                        orig: ident(temp_name).into(),
-                        exported: Some(ident("__default".to_string()).into()),
-                        is_type_only: false,
-                      },
-                    )]),
-                    src: None,
-                    type_only: false,
-                    with: None,
-                  }),
-                )]),
-              })),
-            })),
-          )))
+          remote_module_items.push(import_item);
+          default_remote_module_items.push(namespace_item);
         }
         let is_locally_imported_remote = module_symbol
           .traced_referrers()
@@ -163,7 +202,7 @@ pub fn pack_dts(
             let Some(module) = root_symbol.get_module_from_id(*module_id) else {
               return false;
             };
-            module.specifier().scheme() == "file"
+            is_packed_specifier(module.specifier(), include_remote)
               && match imported_exports {
                 ImportedExports::AllWithDefault => true,
                 ImportedExports::Star => true,
@@ -175,110 +214,829 @@ pub fn pack_dts(
               }
           });
         if is_locally_imported_remote {
-          remote_module_items.push(ModuleItem::ModuleDecl(ModuleDecl::Import(
-            ImportDecl {
+          remote_module_items.push(namespace_import_item(
+            module_symbol.module_id().to_code_string(),
+            &specifier_text,
+          ));
+        }
+      }
+    } else if let Some(module_symbol) = root_symbol
+      .get_module_from_specifier(graph_module.specifier())
+      .and_then(|m| m.esm())
+    {
+      let ranges = module_symbol.public_source_ranges();
+      if !ranges.is_empty() || !module_symbol.traced_re_exports().is_empty() {
+        let graph_module = graph_module.esm().unwrap();
+        let parsed_source = module_symbol.source();
+
+        let file_name = FileName::Url(graph_module.specifier.clone());
+        let source_file = source_map.new_source_file(
+          file_name,
+          parsed_source.text_info().text().to_string(),
+        );
+
+        let mut module = (*parsed_source.module()).clone();
+        let is_root = graph_module.specifier == root;
+        let module_name = if is_root {
+          None
+        } else {
+          Some(module_symbol.module_id().to_code_string())
+        };
+        // strip all the non-declaration types
+        let mut dts_transformer = DtsTransformer {
+          reporter,
+          module_name,
+          module_specifier: &graph_module.specifier,
+          module_symbol,
+          parsed_source,
+          ranges,
+          graph,
+          include_remote,
+          root_symbol: &root_symbol,
+          external_packages: &external_packages,
+          external_string_imports: &external_string_imports,
+          // only fix the local modules
+          fixes: options
+            .fixes
+            .filter(|_| !is_remote_specifier(&graph_module.specifier)),
+          append_module_items: Default::default(),
+          re_export_index: 0,
+          has_default_export: false,
+        };
+        module.visit_mut_with(&mut dts_transformer);
+
+        // adjust the spans to be within the sourcemap
+        adjust_spans(source_file.start_pos, &mut module);
+
+        // Add the file's leading comments to the global comment map.
+        // We don't have to deal with the trailing comments because
+        // we're only interested in jsdocs
+        fill_leading_comments(
+          source_file.start_pos,
+          parsed_source,
+          &global_comments,
+          // only include js docs
+          |comment| {
+            comment.kind == CommentKind::Block && comment.text.starts_with('*')
+          },
+        );
+        final_module.body.extend(module.body);
+      }
+    } else if let Some(module_symbol) = root_symbol
+      .get_module_from_specifier(graph_module.specifier())
+      .and_then(|m| m.json())
+    {
+      if module_symbol.symbols().any(|symbol| symbol.is_public()) {
+        let graph_module = graph_module.json().unwrap();
+        let source_file = source_map.new_source_file(
+          FileName::Url(graph_module.specifier.clone()),
+          graph_module.source.to_string(),
+        );
+        let json_type =
+          match parse_json_module_expr(&source_file, &graph_module.specifier) {
+            Ok(json_expr) => json_expr_to_ts_type(&json_expr),
+            Err(diagnostic) => {
+              reporter.diagnostic(*diagnostic);
+              ts_keyword_type(TsKeywordTypeKind::TsUnknownKeyword)
+            }
+          };
+        final_module.body.push(json_module_decl(
+          module_symbol.as_ref().module_id(),
+          Span::new(
+            source_file.start_pos,
+            source_file.end_pos,
+            Default::default(),
+          ),
+          json_type,
+        ));
+      }
+    }
+  }
+
+  let package_star_re_exports =
+    get_package_star_re_exports(graph, &root_symbol, &root, include_remote)
+      .into_iter()
+      .map(|specifier| {
+        ModuleItem::ModuleDecl(ModuleDecl::ExportAll(ExportAll {
+          span: DUMMY_SP,
+          src: Box::new(Str {
+            span: DUMMY_SP,
+            value: external_specifier_text(&specifier).into(),
+            raw: None,
+          }),
+          type_only: false,
+          with: None,
+        }))
+      });
+  let (package_import_items, package_default_items) =
+    external_packages.into_module_items();
+  let (string_import_items, string_namespace_item) =
+    external_string_imports.into_module_items();
+  final_module.body.splice(
+    0..0,
+    remote_module_items
+      .into_iter()
+      .chain(package_import_items)
+      .chain(string_import_items)
+      .chain(default_remote_module_items)
+      .chain(package_default_items)
+      .chain(string_namespace_item)
+      .chain(package_star_re_exports),
+  );
+
+  let mut printed =
+    print_program(&final_module, &source_map, &global_comments)?;
+  break_lines_after_jsdocs(&mut printed);
+  let maybe_declaration_map = match &options.declaration_map {
+    Some(declaration_map_options) => {
+      Some(printed.build_source_map(&source_map, declaration_map_options)?)
+    }
+    None => None,
+  };
+
+  Ok(PackDtsOutput {
+    text: printed.text,
+    declaration_map: maybe_declaration_map,
+  })
+}
+
+/// Moves anything printed on the same line after a jsdoc to the next line.
+///
+/// This works around https://github.com/swc-project/swc/issues/7492 and
+/// keeps the mappings of the printed program in sync with the text.
+fn break_lines_after_jsdocs(printed: &mut PrintedProgram) {
+  const NEEDLE: &str = "*/ ";
+
+  struct LineBreaks {
+    /// Number of lines inserted before this line.
+    lines_before: u32,
+    indent_width: u32,
+    /// Columns of the spaces that were replaced with a line break.
+    cols: Vec<u32>,
+  }
+
+  if !printed.text.contains(NEEDLE) {
+    return;
+  }
+
+  let mut line_breaks = Vec::new();
+  let mut inserted_lines = 0;
+  let mut text = String::with_capacity(printed.text.len());
+  for (line_index, line) in printed.text.split('\n').enumerate() {
+    if line_index > 0 {
+      text.push('\n');
+    }
+    let trimmed_line = line.trim_start();
+    let mut indent = &line[..line.len() - trimmed_line.len()];
+    if trimmed_line.starts_with('*') && !indent.is_empty() {
+      // jsdoc continuation lines are indented one more than the jsdoc
+      indent = &indent[..indent.len() - 1];
+    }
+    let mut cols = Vec::new();
+    let mut last_end = 0;
+    for (index, _) in line.match_indices(NEEDLE) {
+      let space_index = index + NEEDLE.len() - 1;
+      text.push_str(&line[last_end..space_index]);
+      text.push('\n');
+      text.push_str(indent);
+      last_end = space_index + 1;
+      cols.push(space_index as u32);
+    }
+    text.push_str(&line[last_end..]);
+    line_breaks.push(LineBreaks {
+      lines_before: inserted_lines,
+      indent_width: indent.len() as u32,
+      cols,
+    });
+    inserted_lines += line_breaks.last().unwrap().cols.len() as u32;
+  }
+
+  for (_, line_col) in printed.src_map_buf.iter_mut() {
+    let Some(breaks) = line_breaks.get(line_col.line as usize) else {
+      continue;
+    };
+    let prior_breaks = breaks
+      .cols
+      .iter()
+      .filter(|col| **col < line_col.col)
+      .count();
+    line_col.line += breaks.lines_before + prior_breaks as u32;
+    if prior_breaks > 0 {
+      line_col.col = line_col.col - (breaks.cols[prior_breaks - 1] + 1)
+        + breaks.indent_width;
+    }
+  }
+  printed.text = text;
+}
+
+/// Namespaces for the npm, jsr, and node packages referenced by the
+/// public types. The type tracer doesn't analyze these modules, so
+/// they're imported as a whole.
+#[derive(Default)]
+struct ExternalPackages {
+  packages: RefCell<Vec<ExternalPackage>>,
+}
+
+struct ExternalPackage {
+  specifier: ModuleSpecifier,
+  has_namespace_import: bool,
+  has_default_import: bool,
+}
+
+impl ExternalPackages {
+  pub fn namespace_name(&self, specifier: &ModuleSpecifier) -> String {
+    let index = self.with_package(specifier, |package| {
+      package.has_namespace_import = true;
+    });
+    format!("packExternal{}", index)
+  }
+
+  pub fn default_namespace_name(&self, specifier: &ModuleSpecifier) -> String {
+    let index = self.with_package(specifier, |package| {
+      package.has_default_import = true;
+    });
+    format!("packExternal{}Default", index)
+  }
+
+  fn with_package(
+    &self,
+    specifier: &ModuleSpecifier,
+    action: impl FnOnce(&mut ExternalPackage),
+  ) -> usize {
+    let mut packages = self.packages.borrow_mut();
+    let index = match packages.iter().position(|p| p.specifier == *specifier) {
+      Some(index) => index,
+      None => {
+        packages.push(ExternalPackage {
+          specifier: specifier.clone(),
+          has_namespace_import: false,
+          has_default_import: false,
+        });
+        packages.len() - 1
+      }
+    };
+    action(&mut packages[index]);
+    index
+  }
+
+  /// Gets the imports of the packages and the namespaces for their
+  /// default exports.
+  pub fn into_module_items(self) -> (Vec<ModuleItem>, Vec<ModuleItem>) {
+    let mut import_items = Vec::new();
+    let mut default_namespace_items = Vec::new();
+    for (index, package) in self.packages.into_inner().into_iter().enumerate() {
+      let specifier_text = external_specifier_text(&package.specifier);
+      let code_string = format!("packExternal{}", index);
+      if package.has_default_import {
+        let (import_item, namespace_item) = default_import_items(
+          code_string.clone(),
+          format!("{}Default", code_string),
+          &specifier_text,
+        );
+        import_items.push(import_item);
+        default_namespace_items.push(namespace_item);
+      }
+      if package.has_namespace_import {
+        import_items.push(namespace_import_item(code_string, &specifier_text));
+      }
+    }
+    (import_items, default_namespace_items)
+  }
+}
+
+/// Imports of the exports of external modules that aren't valid
+/// identifiers, which can't be accessed as a member of the module's
+/// namespace. Similar to default imports, these are exposed through a
+/// namespace so they may be referenced by an import equals declaration:
+///
+///   import { "a-b" as packExternalName0Import } from "...";
+///   declare module packExternalNames {
+///     export { packExternalName0Import as packExternalName0 };
+///   }
+#[derive(Default)]
+struct ExternalStringImports {
+  imports: RefCell<Vec<(String, String)>>,
+}
+
+impl ExternalStringImports {
+  pub fn entity_name(
+    &self,
+    specifier_text: String,
+    name: &str,
+  ) -> TsEntityName {
+    let mut imports = self.imports.borrow_mut();
+    let index = match imports
+      .iter()
+      .position(|import| import.0 == specifier_text && import.1 == name)
+    {
+      Some(index) => index,
+      None => {
+        imports.push((specifier_text, name.to_string()));
+        imports.len() - 1
+      }
+    };
+    TsEntityName::TsQualifiedName(Box::new(TsQualifiedName {
+      left: TsEntityName::Ident(ident("packExternalNames".to_string())),
+      right: ident(format!("packExternalName{}", index)),
+    }))
+  }
+
+  /// Gets the imports and the namespace that exposes them.
+  pub fn into_module_items(self) -> (Vec<ModuleItem>, Option<ModuleItem>) {
+    let imports = self.imports.into_inner();
+    if imports.is_empty() {
+      return (Vec::new(), None);
+    }
+    let mut import_items = Vec::with_capacity(imports.len());
+    let mut specifiers = Vec::with_capacity(imports.len());
+    for (index, (specifier_text, name)) in imports.into_iter().enumerate() {
+      let local_name = format!("packExternalName{}", index);
+      let import_name = format!("{}Import", local_name);
+      import_items.push(ModuleItem::ModuleDecl(ModuleDecl::Import(
+        ImportDecl {
+          span: DUMMY_SP,
+          specifiers: vec![ImportSpecifier::Named(ImportNamedSpecifier {
+            span: DUMMY_SP,
+            imported: Some(module_export_name(name)),
+            local: ident(import_name.clone()),
+            is_type_only: false,
+          })],
+          src: Box::new(Str {
+            span: DUMMY_SP,
+            value: specifier_text.into(),
+            raw: None,
+          }),
+          type_only: false,
+          with: None,
+        },
+      )));
+      specifiers.push(ExportSpecifier::Named(ExportNamedSpecifier {
+        span: DUMMY_SP,
+        orig: ident(import_name).into(),
+        exported: Some(ident(local_name).into()),
+        is_type_only: false,
+      }));
+    }
+    let namespace_item =
+      ModuleItem::Stmt(Stmt::Decl(Decl::TsModule(Box::new(TsModuleDecl {
+        span: DUMMY_SP,
+        declare: true,
+        global: false,
+        id: ident("packExternalNames".to_string()).into(),
+        body: Some(TsNamespaceBody::TsModuleBlock(TsModuleBlock {
+          span: DUMMY_SP,
+          body: Vec::from([ModuleItem::ModuleDecl(ModuleDecl::ExportNamed(
+            NamedExport {
               span: DUMMY_SP,
-              specifiers: vec![ImportSpecifier::Namespace(
-                ImportStarAsSpecifier {
-                  span: DUMMY_SP,
-                  local: ident(module_symbol.module_id().to_code_string()),
-                },
-              )],
-              src: Box::new(Str {
-                span: DUMMY_SP,
-                value: graph_module.specifier().to_string().into(),
-                raw: None,
-              }),
+              specifiers,
+              src: None,
               type_only: false,
               with: None,
             },
-          )));
-        }
+          ))]),
+        })),
+      }))));
+    (import_items, Some(namespace_item))
+  }
+}
+
+/// A module imported by a packed module along with how to
+/// reference its exports in the output.
+#[derive(Clone)]
+enum ImportedModule {
+  Traced {
+    specifier: ModuleSpecifier,
+    module_id: ModuleId,
+    is_external: bool,
+  },
+  Package(ModuleSpecifier),
+}
+
+impl ImportedModule {
+  pub fn specifier(&self) -> &ModuleSpecifier {
+    match self {
+      ImportedModule::Traced { specifier, .. } => specifier,
+      ImportedModule::Package(specifier) => specifier,
+    }
+  }
+
+  pub fn is_external(&self) -> bool {
+    match self {
+      ImportedModule::Traced { is_external, .. } => *is_external,
+      ImportedModule::Package(_) => true,
+    }
+  }
+
+  pub fn namespace_name(&self, external_packages: &ExternalPackages) -> String {
+    match self {
+      ImportedModule::Traced { module_id, .. } => module_id.to_code_string(),
+      ImportedModule::Package(specifier) => {
+        external_packages.namespace_name(specifier)
+      }
+    }
+  }
+
+  /// Gets the name of the namespace with the `__default` member, which
+  /// is separate for external modules.
+  pub fn default_namespace_name(
+    &self,
+    external_packages: &ExternalPackages,
+  ) -> String {
+    match self {
+      ImportedModule::Traced {
+        module_id,
+        is_external: true,
+        ..
+      } => module_id.to_default_code_string(),
+      ImportedModule::Traced { module_id, .. } => module_id.to_code_string(),
+      ImportedModule::Package(specifier) => {
+        external_packages.default_namespace_name(specifier)
       }
+    }
+  }
+
+  /// Gets the entity name that refers to an export of the module.
+  pub fn export_entity_name(
+    &self,
+    name: &str,
+    external_packages: &ExternalPackages,
+    external_string_imports: &ExternalStringImports,
+  ) -> TsEntityName {
+    if self.is_external() && name != "default" && !is_valid_ident(name) {
+      // the export can't be accessed as a member of the external
+      // module's namespace, so it's imported by its name instead
+      return external_string_imports
+        .entity_name(external_specifier_text(self.specifier()), name);
+    }
+    let namespace_name = if self.is_external() && name == "default" {
+      self.default_namespace_name(external_packages)
     } else {
-      if let Some(module_symbol) = root_symbol
-        .get_module_from_specifier(graph_module.specifier())
-        .and_then(|m| m.esm())
-      {
-        let ranges = module_symbol.public_source_ranges();
-        if !ranges.is_empty() || !module_symbol.traced_re_exports().is_empty() {
-          let graph_module = graph_module.esm().unwrap();
-          let parsed_source = module_symbol.source();
-
-          let file_name = FileName::Url(graph_module.specifier.clone());
-          let source_file = source_map.new_source_file(
-            file_name,
-            parsed_source.text_info().text().to_string(),
-          );
+      self.namespace_name(external_packages)
+    };
+    TsEntityName::TsQualifiedName(Box::new(TsQualifiedName {
+      left: TsEntityName::Ident(ident(namespace_name)),
+      right: ident(namespace_member_name(name)),
+    }))
+  }
+}
 
-          let mut module = (*parsed_source.module()).clone();
-          let is_root = graph.roots.contains(&graph_module.specifier);
-          let module_name = if is_root {
-            None
-          } else {
-            Some(module_symbol.module_id().to_code_string())
-          };
-          // strip all the non-declaration types
-          let mut dts_transformer = DtsTransformer {
-            reporter,
-            module_name,
-            module_specifier: &graph_module.specifier,
-            module_symbol,
-            parsed_source: &parsed_source,
-            ranges,
-            graph,
-            root_symbol: &root_symbol,
-            append_module_items: Default::default(),
-            re_export_index: 0,
-          };
-          module.visit_mut_with(&mut dts_transformer);
+/// A default or namespace import of a module that's output as an
+/// import equals declaration (ex. `import React = packExternal0.__default`).
+struct PackageImportDecl {
+  local: Ident,
+  module: ImportedModule,
+  is_default: bool,
+}
 
-          // adjust the spans to be within the sourcemap
-          adjust_spans(source_file.start_pos, &mut module);
+impl PackageImportDecl {
+  pub fn into_module_item(
+    self,
+    external_packages: &ExternalPackages,
+  ) -> ModuleItem {
+    let entity_name = if self.is_default {
+      TsEntityName::TsQualifiedName(Box::new(TsQualifiedName {
+        left: TsEntityName::Ident(ident(
+          self.module.default_namespace_name(external_packages),
+        )),
+        // can't use `.default` because it's a reserved word,
+        // so use our custom `__default` instead
+        right: ident("__default".to_string()),
+      }))
+    } else {
+      TsEntityName::Ident(ident(self.module.namespace_name(external_packages)))
+    };
+    ModuleItem::ModuleDecl(ModuleDecl::TsImportEquals(Box::new(
+      TsImportEqualsDecl {
+        span: DUMMY_SP,
+        is_export: false,
+        is_type_only: false,
+        id: self.local,
+        module_ref: TsModuleRef::TsEntityName(entity_name),
+      },
+    )))
+  }
+}
 
-          // Add the file's leading comments to the global comment map.
-          // We don't have to deal with the trailing comments because
-          // we're only interested in jsdocs
-          fill_leading_comments(
-            source_file.start_pos,
-            &parsed_source,
-            &global_comments,
-            // only include js docs
-            |comment| {
-              comment.kind == CommentKind::Block
-                && comment.text.starts_with('*')
-            },
-          );
-          final_module.body.extend(module.body);
+/// Collects the identifiers referenced by module items.
+#[derive(Default)]
+struct ReferencedIdCollector {
+  ids: HashSet<Id>,
+}
+
+impl ReferencedIdCollector {
+  pub fn collect(items: &[ModuleItem]) -> HashSet<Id> {
+    let mut collector = Self::default();
+    items.visit_with(&mut collector);
+    collector.ids
+  }
+}
+
+impl Visit for ReferencedIdCollector {
+  fn visit_ident(&mut self, ident: &Ident) {
+    self.ids.insert(ident.to_id());
+  }
+}
+
+/// Gets the packages re-exported with `export * from "..."` by the root
+/// or by the packed modules it re-exports everything from. These are
+/// re-exported at the top level because the tracer doesn't know the
+/// names exported by packages.
+fn get_package_star_re_exports(
+  graph: &ModuleGraph,
+  root_symbol: &RootSymbol,
+  root: &ModuleSpecifier,
+  include_remote: Option<&IncludeRemoteOptions>,
+) -> Vec<ModuleSpecifier> {
+  let mut result = Vec::new();
+  let mut visited = HashSet::new();
+  let mut pending = vec![root.clone()];
+  while let Some(specifier) = pending.pop() {
+    if !visited.insert(specifier.clone()) {
+      continue;
+    }
+    let Some(module_symbol) = root_symbol
+      .get_module_from_specifier(&specifier)
+      .and_then(|m| m.esm())
+    else {
+      continue;
+    };
+    for item in &module_symbol.source().module().body {
+      let ModuleItem::ModuleDecl(ModuleDecl::ExportAll(export_all)) = item
+      else {
+        continue;
+      };
+      let Some(dep_specifier) =
+        graph.resolve_dependency(&export_all.src.value, &specifier, true)
+      else {
+        continue;
+      };
+      if is_opaque_external(graph, &dep_specifier) {
+        if !result.contains(&dep_specifier) {
+          result.push(dep_specifier);
         }
+      } else if is_packed_specifier(&dep_specifier, include_remote) {
+        pending.push(dep_specifier);
       }
     }
   }
+  result
+}
 
-  final_module.body.splice(
-    0..0,
-    remote_module_items
-      .into_iter()
-      .chain(default_remote_module_items.into_iter()),
-  );
+fn namespace_import_item(local: String, specifier_text: &str) -> ModuleItem {
+  ModuleItem::ModuleDecl(ModuleDecl::Import(ImportDecl {
+    span: DUMMY_SP,
+    specifiers: vec![ImportSpecifier::Namespace(ImportStarAsSpecifier {
+      span: DUMMY_SP,
+      local: ident(local),
+    })],
+    src: Box::new(Str {
+      span: DUMMY_SP,
+      value: specifier_text.into(),
+      raw: None,
+    }),
+    type_only: false,
+    with: None,
+  }))
+}
+
+/// Creates the import of an external module's default export and the
+/// namespace that exposes it.
+///
+/// This is done because `import something = defaultImport` is not valid
+/// because `defaultImport` is not a namespace, so instead we do:
+///   import { default as pack1DefaultImport } from "...";
+///   declare module pack1Default {
+///     export { pack1DefaultImport as __default };
+///   }
+/// Then downstream code will do `import something = pack1Default.__default`
+fn default_import_items(
+  code_string: String,
+  default_code_string: String,
+  specifier_text: &str,
+) -> (ModuleItem, ModuleItem) {
+  let temp_name = format!("{}DefaultImport", code_string);
+  let import_item = ModuleItem::ModuleDecl(ModuleDecl::Import(ImportDecl {
+    span: DUMMY_SP,
+    specifiers: vec![ImportSpecifier::Named(ImportNamedSpecifier {
+      span: DUMMY_SP,
+      imported: Some(ModuleExportName::Ident(ident("default".to_string()))),
+      local: ident(temp_name.clone()),
+      is_type_only: false,
+    })],
+    src: Box::new(Str {
+      span: DUMMY_SP,
+      value: specifier_text.into(),
+      raw: None,
+    }),
+    type_only: false,
+    with: None,
+  }));
+  let namespace_item =
+    ModuleItem::Stmt(Stmt::Decl(Decl::TsModule(Box::new(TsModuleDecl {
+      span: DUMMY_SP,
+      declare: true,
+      global: false,
+      id: ident(default_code_string).into(),
+      body: Some(TsNamespaceBody::TsModuleBlock(TsModuleBlock {
+        span: DUMMY_SP,
+        body: Vec::from([ModuleItem::ModuleDecl(ModuleDecl::ExportNamed(
+          NamedExport {
+            span: DUMMY_SP,
+            specifiers: Vec::from([ExportSpecifier::Named(
+              ExportNamedSpecifier {
+                span: DUMMY_SP,
+                orig: ident(temp_name).into(),
+                exported: Some(ident("__default".to_string()).into()),
+                is_type_only: false,
+              },
+            )]),
+            src: None,
+            type_only: false,
+            with: None,
+          },
+        ))]),
+      })),
+    }))));
+  (import_item, namespace_item)
+}
 
-  print_program(&final_module, &source_map, &global_comments)
+/// Creates the namespace for a JSON module with the type of its value:
+///
+///   declare module pack1 {
+///     export const __default: { value: number; };
+///   }
+fn json_module_decl(
+  module_id: ModuleId,
+  span: Span,
+  json_type: TsType,
+) -> ModuleItem {
+  ModuleItem::Stmt(Stmt::Decl(Decl::TsModule(Box::new(TsModuleDecl {
+    span: DUMMY_SP,
+    declare: true,
+    global: false,
+    id: ident(module_id.to_code_string()).into(),
+    body: Some(TsNamespaceBody::TsModuleBlock(TsModuleBlock {
+      span: DUMMY_SP,
+      body: Vec::from([ModuleItem::ModuleDecl(ModuleDecl::ExportDecl(
+        ExportDecl {
+          span: DUMMY_SP,
+          decl: Decl::Var(Box::new(VarDecl {
+            span,
+            kind: VarDeclKind::Const,
+            declare: false,
+            decls: vec![VarDeclarator {
+              span: DUMMY_SP,
+              name: Pat::Ident(BindingIdent {
+                id: ident("__default".to_string()),
+                type_ann: Some(Box::new(TsTypeAnn {
+                  span: DUMMY_SP,
+                  type_ann: Box::new(json_type),
+                })),
+              }),
+              init: None,
+              definite: false,
+            }],
+          })),
+        },
+      ))]),
+    })),
+  }))))
+}
+
+/// Gets the type TypeScript gives a JSON value when importing it.
+fn json_expr_to_ts_type(expr: &Expr) -> TsType {
+  match expr {
+    Expr::Object(obj) => TsType::TsTypeLit(TsTypeLit {
+      span: DUMMY_SP,
+      members: obj
+        .props
+        .iter()
+        .filter_map(|prop| {
+          let PropOrSpread::Prop(prop) = prop else {
+            return None;
+          };
+          let Prop::KeyValue(kv) = &**prop else {
+            return None;
+          };
+          let key = match &kv.key {
+            PropName::Str(str) => str,
+            // `__proto__` keys are computed so they define a property
+            PropName::Computed(ComputedPropName { expr, .. }) => {
+              match &**expr {
+                Expr::Lit(Lit::Str(str)) => str,
+                _ => return None,
+              }
+            }
+            _ => return None,
+          };
+          let key = if is_valid_ident(&key.value) {
+            Expr::Ident(ident(key.value.to_string()))
+          } else {
+            Expr::Lit(Lit::Str(Str {
+              span: DUMMY_SP,
+              value: key.value.clone(),
+              raw: None,
+            }))
+          };
+          Some(TsTypeElement::TsPropertySignature(TsPropertySignature {
+            span: DUMMY_SP,
+            readonly: false,
+            key: Box::new(key),
+            computed: false,
+            optional: false,
+            init: None,
+            params: Vec::new(),
+            type_ann: Some(Box::new(TsTypeAnn {
+              span: DUMMY_SP,
+              type_ann: Box::new(json_expr_to_ts_type(&kv.value)),
+            })),
+            type_params: None,
+          }))
+        })
+        .collect(),
+    }),
+    Expr::Array(array) => {
+      let mut elem_types: Vec<TsType> = Vec::new();
+      for elem in array.elems.iter().flatten() {
+        let elem_type = json_expr_to_ts_type(&elem.expr);
+        if !elem_types.contains(&elem_type) {
+          elem_types.push(elem_type);
+        }
+      }
+      // order the union like tsc (ex. `(string | number)[]`)
+      elem_types.sort_by_key(union_member_order);
+      let elem_type = match elem_types.len() {
+        0 => ts_keyword_type(TsKeywordTypeKind::TsNeverKeyword),
+        1 => elem_types.remove(0),
+        _ => TsType::TsParenthesizedType(TsParenthesizedType {
+          span: DUMMY_SP,
+          type_ann: Box::new(TsType::TsUnionOrIntersectionType(
+            TsUnionOrIntersectionType::TsUnionType(TsUnionType {
+              span: DUMMY_SP,
+              types: elem_types.into_iter().map(Box::new).collect(),
+            }),
+          )),
+        }),
+      };
+      TsType::TsArrayType(TsArrayType {
+        span: DUMMY_SP,
+        elem_type: Box::new(elem_type),
+      })
+    }
+    Expr::Lit(Lit::Null(_)) => {
+      ts_keyword_type(TsKeywordTypeKind::TsNullKeyword)
+    }
+    Expr::Lit(Lit::Bool(_)) => {
+      ts_keyword_type(TsKeywordTypeKind::TsBooleanKeyword)
+    }
+    Expr::Lit(Lit::Str(_)) => {
+      ts_keyword_type(TsKeywordTypeKind::TsStringKeyword)
+    }
+    Expr::Lit(Lit::Num(_)) | Expr::Unary(_) => {
+      ts_keyword_type(TsKeywordTypeKind::TsNumberKeyword)
+    }
+    _ => ts_keyword_type(TsKeywordTypeKind::TsUnknownKeyword),
+  }
+}
+
+/// Gets the name of an export within a packed module's namespace.
+///
+/// Names that are not valid identifiers (ex. `export { a as "a-b" }`)
+/// are escaped so they may be referenced as `pack0.__packStr_a$2d$b`.
+/// This name is only used within the declaration file, so exporting a
+/// namespace with such a member is reported.
+fn namespace_member_name(name: &str) -> String {
+  if name == "default" {
+    "__default".to_string()
+  } else if is_valid_ident(name) {
+    name.to_string()
+  } else {
+    let mut text = "__packStr_".to_string();
+    for c in name.chars() {
+      if c.is_alphanumeric() || c == '_' {
+        text.push(c);
+      } else {
+        text.push_str(&format!("${:x}$", c as u32));
+      }
+    }
+    text
+  }
 }
 
 struct ReExportName(String);
 
 impl ReExportName {
-  pub fn to_string(&self) -> String {
-    self.0.clone()
+  pub fn as_str(&self) -> &str {
+    &self.0
   }
 
-  fn into_module_item(self, export_name: String) -> ModuleItem {
+  fn into_module_item(self, exported: ModuleExportName) -> ModuleItem {
     ModuleItem::ModuleDecl(ModuleDecl::ExportNamed(NamedExport {
       span: DUMMY_SP,
       specifiers: vec![ExportSpecifier::Named(ExportNamedSpecifier {
         span: DUMMY_SP,
         orig: ModuleExportName::Ident(ident(self.0)),
-        exported: Some(ModuleExportName::Ident(ident(export_name))),
+        exported: Some(exported),
         is_type_only: false,
       })],
       src: None,
@@ -296,9 +1054,15 @@ struct DtsTransformer<'a, TReporter: Reporter> {
   parsed_source: &'a ParsedSource,
   ranges: HashSet<SourceRange>,
   graph: &'a ModuleGraph,
+  include_remote: Option<&'a IncludeRemoteOptions>,
   root_symbol: &'a RootSymbol,
+  external_packages: &'a ExternalPackages,
+  external_string_imports: &'a ExternalStringImports,
+  fixes: Option<&'a FixCollector>,
   append_module_items: Vec<ModuleItem>,
   re_export_index: u32,
+  /// If the `__default` member of the packed module was exported.
+  has_default_export: bool,
 }
 
 impl<'a, TReporter: Reporter> DtsTransformer<'a, TReporter> {
@@ -310,9 +1074,718 @@ impl<'a, TReporter: Reporter> DtsTransformer<'a, TReporter> {
     ReExportName(format!("__export{}", self.re_export_index))
   }
 
+  fn re_export_module_item(
+    &self,
+    private_name: ReExportName,
+    export_name: String,
+  ) -> ModuleItem {
+    private_name.into_module_item(self.export_name(export_name))
+  }
+
+  /// Gets the name to export with from this module. String export
+  /// names are only supported at the top level, so the names within
+  /// a packed module's namespace are converted to identifiers.
+  fn export_name(&self, name: String) -> ModuleExportName {
+    if self.module_name.is_some() {
+      ModuleExportName::Ident(ident(namespace_member_name(&name)))
+    } else {
+      module_export_name(name)
+    }
+  }
+
+  fn imported_module(
+    &self,
+    specifier: &ModuleSpecifier,
+  ) -> Option<ImportedModule> {
+    if is_opaque_external(self.graph, specifier) {
+      return Some(ImportedModule::Package(specifier.clone()));
+    }
+    let module_symbol =
+      self.root_symbol.get_module_from_specifier(specifier)?;
+    Some(ImportedModule::Traced {
+      specifier: specifier.clone(),
+      module_id: module_symbol.module_id(),
+      is_external: !is_packed_specifier(specifier, self.include_remote),
+    })
+  }
+
+  /// Gets if the local is exported from this module.
+  fn is_exported_local(&self, local: &Ident) -> bool {
+    let Some(symbol_id) = self.module_symbol.symbol_id_from_swc(&local.to_id())
+    else {
+      return false;
+    };
+    self
+      .module_symbol
+      .exports(self.graph, self.root_symbol)
+      .values()
+      .any(|(module, id)| {
+        module.module_id() == self.module_symbol.module_id() && *id == symbol_id
+      })
+  }
+
+  /// Reports the exports of a packed module that aren't valid
+  /// identifiers when its namespace is exported, because they can't
+  /// be members of the module's namespace in the declaration file.
+  fn report_string_namespace_members(
+    &self,
+    module: &ImportedModule,
+    range: SourceRange,
+  ) {
+    if module.is_external() {
+      // the namespace is the external module itself
+      return;
+    }
+    let Some(module_symbol) = self
+      .root_symbol
+      .get_module_from_specifier(module.specifier())
+    else {
+      return;
+    };
+    let text_info = self.parsed_source.text_info();
+    for name in module_symbol.exports(self.graph, self.root_symbol).keys() {
+      if name == "default" || is_valid_ident(name) {
+        continue;
+      }
+      self.reporter.diagnostic(Diagnostic {
+        code: DiagnosticCode::StringNamespaceMember,
+        severity: DiagnosticCode::StringNamespaceMember.default_severity(),
+        message: format!(
+          concat!(
+            "Export '{}' of {} cannot be accessed through the exported ",
+            "namespace in the declaration file."
+          ),
+          name,
+          module.specifier(),
+        ),
+        specifier: self.module_specifier.clone(),
+        line_and_column: Some(
+          text_info.line_and_column_display(range.start).into(),
+        ),
+        end_line_and_column: Some(
+          text_info.line_and_column_display(range.end).into(),
+        ),
+        related: Vec::new(),
+        hint: Some(
+          concat!(
+            "Rename the export to a valid identifier or export it by name ",
+            "instead of through the namespace."
+          )
+          .to_string(),
+        ),
+      });
+    }
+  }
+
+  /// Gets the module to refer to an export of the imported module
+  /// through, which is the package the imported module re-exports
+  /// everything from when the export isn't otherwise known.
+  fn export_module(
+    &self,
+    module: &ImportedModule,
+    name: &str,
+    range: SourceRange,
+  ) -> ImportedModule {
+    if module.is_external() || name == "default" {
+      return module.clone();
+    }
+    let Some(module_symbol) = self
+      .root_symbol
+      .get_module_from_specifier(module.specifier())
+    else {
+      return module.clone();
+    };
+    if module_symbol
+      .exports(self.graph, self.root_symbol)
+      .contains_key(name)
+    {
+      return module.clone();
+    }
+    let mut packages = get_package_star_re_exports(
+      self.graph,
+      self.root_symbol,
+      module.specifier(),
+      self.include_remote,
+    );
+    match packages.len() {
+      0 => module.clone(),
+      1 => ImportedModule::Package(packages.remove(0)),
+      _ => {
+        self.report_package_star_re_export(
+          format!(
+            concat!(
+              "Export '{}' of {} could come from any of the packages it ",
+              "re-exports everything from ({})."
+            ),
+            name,
+            module.specifier(),
+            packages
+              .iter()
+              .map(|s| s.as_str())
+              .collect::<Vec<_>>()
+              .join(", "),
+          ),
+          "Import it from the package that exports it instead.",
+          range,
+        );
+        module.clone()
+      }
+    }
+  }
+
+  /// Reports that the exports of the packages a packed module
+  /// re-exports everything from are missing from its namespace, which
+  /// is a declaration namespace that can't re-export everything.
+  fn report_package_star_re_exports(
+    &self,
+    module: &ImportedModule,
+    range: SourceRange,
+  ) {
+    if module.is_external() {
+      // the namespace is the external module itself
+      return;
+    }
+    let packages = get_package_star_re_exports(
+      self.graph,
+      self.root_symbol,
+      module.specifier(),
+      self.include_remote,
+    );
+    for package in packages {
+      self.report_package_star_re_export(
+        format!(
+          concat!(
+            "Exports of {} that {} re-exports everything from are missing ",
+            "from the exported namespace in the declaration file."
+          ),
+          package,
+          module.specifier(),
+        ),
+        concat!(
+          "Export the namespace of a module without `export * from` a ",
+          "package or re-export the package's exports by name."
+        ),
+        range,
+      );
+    }
+  }
+
+  fn report_package_star_re_export(
+    &self,
+    message: String,
+    hint: &str,
+    range: SourceRange,
+  ) {
+    let text_info = self.parsed_source.text_info();
+    self.reporter.diagnostic(Diagnostic {
+      code: DiagnosticCode::PackageStarReExport,
+      severity: DiagnosticCode::PackageStarReExport.default_severity(),
+      message,
+      specifier: self.module_specifier.clone(),
+      line_and_column: Some(
+        text_info.line_and_column_display(range.start).into(),
+      ),
+      end_line_and_column: Some(
+        text_info.line_and_column_display(range.end).into(),
+      ),
+      related: Vec::new(),
+      hint: Some(hint.to_string()),
+    });
+  }
+
+  /// Exports the local as the `__default` member of the packed module.
+  fn push_default_export(&mut self, local_name: String) {
+    if self.has_default_export {
+      // ex. overloads of a default exported function
+      return;
+    }
+    self.has_default_export = true;
+    let exported = (local_name != "__default")
+      .then(|| ModuleExportName::Ident(ident("__default".to_string())));
+    self.append_module_items.push(ModuleItem::ModuleDecl(
+      ModuleDecl::ExportNamed(NamedExport {
+        span: DUMMY_SP,
+        specifiers: Vec::from([ExportSpecifier::Named(ExportNamedSpecifier {
+          span: DUMMY_SP,
+          orig: ModuleExportName::Ident(ident(local_name)),
+          exported,
+          is_type_only: false,
+        })]),
+        src: None,
+        type_only: false,
+        with: None,
+      }),
+    ));
+  }
+
   fn has_internal_jsdoc(&self, pos: SourcePos) -> bool {
     has_internal_jsdoc(self.parsed_source, pos)
   }
+
+  fn byte_index(&self, pos: SourcePos) -> usize {
+    pos.as_byte_index(self.parsed_source.text_info().range().start)
+  }
+
+  /// Inserts a type annotation at the position when fixing and the
+  /// type can be printed.
+  fn maybe_insert_type_ann(&self, pos: SourcePos, ts_type: &TsType) -> bool {
+    let Some(fixes) = self.fixes else {
+      return false;
+    };
+    let Ok(type_text) = print_type(ts_type) else {
+      return false;
+    };
+    fixes.insert(
+      self.module_specifier,
+      self.byte_index(pos),
+      format!(": {}", type_text),
+    );
+    true
+  }
+
+  /// Infers the type of an initializer the way `tsc --declaration` does
+  /// for a mutable location (ex. a `let` declaration or class property).
+  fn maybe_infer_expr_type(&self, expr: &Expr) -> Option<TsType> {
+    self.infer_expr_type(expr, InferContext::default())
+  }
+
+  /// Infers the type of an initializer to insert into the source when
+  /// fixing, which also includes the types of global classes.
+  fn maybe_infer_fix_expr_type(&self, expr: &Expr) -> Option<TsType> {
+    self.infer_expr_type(
+      expr,
+      InferContext {
+        is_const: false,
+        is_fix: true,
+      },
+    )
+  }
+
+  fn infer_expr_type(&self, expr: &Expr, ctx: InferContext) -> Option<TsType> {
+    let is_const = ctx.is_const;
+    match expr {
+      Expr::TsTypeAssertion(n) => Some(*n.type_ann.clone()),
+      Expr::TsAs(n) => Some(*n.type_ann.clone()),
+      Expr::TsConstAssertion(n) => self.infer_expr_type(
+        &n.expr,
+        InferContext {
+          is_const: true,
+          ..ctx
+        },
+      ),
+      Expr::TsSatisfies(n) => self.infer_expr_type(&n.expr, ctx),
+      Expr::Paren(n) => self.infer_expr_type(&n.expr, ctx),
+      Expr::Lit(lit) => {
+        if is_const {
+          if let Some(lit) = maybe_lit_type(expr) {
+            return Some(lit);
+          }
+        }
+        let keyword = match lit {
+          Lit::Str(_) => Some(TsKeywordTypeKind::TsStringKeyword),
+          Lit::Bool(_) => Some(TsKeywordTypeKind::TsBooleanKeyword),
+          Lit::Null(_) => Some(TsKeywordTypeKind::TsNullKeyword),
+          Lit::Num(_) => Some(TsKeywordTypeKind::TsNumberKeyword),
+          Lit::BigInt(_) => Some(TsKeywordTypeKind::TsBigIntKeyword),
+          Lit::Regex(_) => None,
+          Lit::JSXText(_) => None,
+        };
+        keyword.map(ts_keyword_type)
+      }
+      Expr::Unary(UnaryExpr {
+        op: UnaryOp::Minus,
+        arg,
+        ..
+      }) => match &**arg {
+        Expr::Lit(Lit::Num(_)) if is_const => maybe_lit_type(expr),
+        Expr::Lit(Lit::Num(_)) => {
+          Some(ts_keyword_type(TsKeywordTypeKind::TsNumberKeyword))
+        }
+        Expr::Lit(Lit::BigInt(_)) if is_const => maybe_lit_type(expr),
+        Expr::Lit(Lit::BigInt(_)) => {
+          Some(ts_keyword_type(TsKeywordTypeKind::TsBigIntKeyword))
+        }
+        _ => None,
+      },
+      Expr::Tpl(tpl) => {
+        if is_const && tpl.exprs.is_empty() {
+          maybe_lit_type(expr)
+        } else {
+          // `${string}` style template literal types aren't supported
+          Some(ts_keyword_type(TsKeywordTypeKind::TsStringKeyword))
+        }
+      }
+      Expr::Array(array) => {
+        let mut elem_types: Vec<TsType> = Vec::new();
+        for elem in &array.elems {
+          let elem = elem.as_ref()?;
+          if elem.spread.is_some() {
+            return None;
+          }
+          let elem_type = self.infer_expr_type(&elem.expr, ctx)?;
+          if is_const
+            || !elem_types.iter().any(|t| t.eq_ignore_span(&elem_type))
+          {
+            elem_types.push(elem_type);
+          }
+        }
+        if is_const {
+          return Some(readonly_type(TsType::TsTupleType(TsTupleType {
+            span: DUMMY_SP,
+            elem_types: elem_types
+              .into_iter()
+              .map(|ty| TsTupleElement {
+                span: DUMMY_SP,
+                label: None,
+                ty: Box::new(ty),
+              })
+              .collect(),
+          })));
+        }
+        // order the union like tsc (ex. `(string | number)[]`)
+        elem_types.sort_by_key(union_member_order);
+        let elem_type = match elem_types.len() {
+          // tsc infers `any[]`, which is better written out explicitly
+          0 => return None,
+          1 => elem_types.remove(0),
+          _ => TsType::TsParenthesizedType(TsParenthesizedType {
+            span: DUMMY_SP,
+            type_ann: Box::new(TsType::TsUnionOrIntersectionType(
+              TsUnionOrIntersectionType::TsUnionType(TsUnionType {
+                span: DUMMY_SP,
+                types: elem_types.into_iter().map(Box::new).collect(),
+              }),
+            )),
+          }),
+        };
+        Some(TsType::TsArrayType(TsArrayType {
+          span: DUMMY_SP,
+          elem_type: Box::new(elem_type),
+        }))
+      }
+      Expr::Object(obj) => {
+        let mut members = Vec::with_capacity(obj.props.len());
+        for prop in &obj.props {
+          let PropOrSpread::Prop(prop) = prop else {
+            return None;
+          };
+          members.push(match &**prop {
+            Prop::KeyValue(kv) => {
+              TsTypeElement::TsPropertySignature(TsPropertySignature {
+                span: DUMMY_SP,
+                readonly: is_const,
+                key: Box::new(prop_name_to_type_key(&kv.key)?),
+                computed: false,
+                optional: false,
+                init: None,
+                params: Vec::new(),
+                type_ann: Some(Box::new(TsTypeAnn {
+                  span: DUMMY_SP,
+                  type_ann: Box::new(self.infer_expr_type(&kv.value, ctx)?),
+                })),
+                type_params: None,
+              })
+            }
+            Prop::Method(method) => {
+              let function = &method.function;
+              TsTypeElement::TsMethodSignature(TsMethodSignature {
+                span: DUMMY_SP,
+                readonly: false,
+                key: Box::new(prop_name_to_type_key(&method.key)?),
+                computed: false,
+                optional: false,
+                params: function
+                  .params
+                  .iter()
+                  .map(|param| pat_to_ts_fn_param(&param.pat))
+                  .collect::<Option<Vec<_>>>()?,
+                type_ann: Some(function.return_type.clone()?),
+                type_params: function.type_params.clone(),
+              })
+            }
+            // the type of shorthand properties and accessors can't be
+            // known without type checking
+            Prop::Shorthand(_)
+            | Prop::Assign(_)
+            | Prop::Getter(_)
+            | Prop::Setter(_) => return None,
+          });
+        }
+        Some(TsType::TsTypeLit(TsTypeLit {
+          span: DUMMY_SP,
+          members,
+        }))
+      }
+      Expr::New(new_expr) => self.maybe_infer_new_expr_type(new_expr, ctx),
+      Expr::This(_)
+      | Expr::Fn(_)
+      | Expr::Unary(_)
+      | Expr::Update(_)
+      | Expr::Bin(_)
+      | Expr::Assign(_)
+      | Expr::Member(_)
+      | Expr::SuperProp(_)
+      | Expr::Cond(_)
+      | Expr::Call(_)
+      | Expr::Seq(_)
+      | Expr::Ident(_)
+      | Expr::TaggedTpl(_)
+      | Expr::Arrow(_)
+      | Expr::Class(_)
+      | Expr::Yield(_)
+      | Expr::MetaProp(_)
+      | Expr::Await(_)
+      | Expr::JSXMember(_)
+      | Expr::JSXNamespacedName(_)
+      | Expr::JSXEmpty(_)
+      | Expr::JSXElement(_)
+      | Expr::JSXFragment(_)
+      | Expr::TsNonNull(_)
+      | Expr::TsInstantiation(_)
+      | Expr::PrivateName(_)
+      | Expr::OptChain(_)
+      | Expr::Invalid(_) => None,
+    }
+  }
+
+  /// Infers the instance type of a `new` expression when the type is
+  /// complete, which is when the class is known to not be generic or
+  /// is given type arguments (ex. `new Map()` is `Map<any, any>`).
+  fn maybe_infer_new_expr_type(
+    &self,
+    n: &NewExpr,
+    ctx: InferContext,
+  ) -> Option<TsType> {
+    let type_name = expr_to_ts_entity_name(&n.callee)?;
+    let root_ident = ts_entity_name_root_ident(&type_name);
+    let maybe_symbol = self.module_symbol.symbol_from_swc(&root_ident.to_id());
+    let is_complete = match (&type_name, maybe_symbol) {
+      // the class needs to be in the declaration output
+      (TsEntityName::Ident(ident), Some(symbol)) if symbol.is_public() => {
+        let class = self.maybe_local_class(ident)?;
+        class.type_params.is_none() || n.type_args.is_some()
+      }
+      // ex. a class within a namespace or imported namespace
+      (_, Some(_)) => false,
+      // global classes are only assumed in the types inserted into
+      // the source, where they can be reviewed
+      (_, None) if !ctx.is_fix => false,
+      (_, None) => {
+        n.type_args.is_some()
+          || NON_GENERIC_GLOBAL_CLASSES.contains(
+            &print_type(&ts_entity_name_type(&type_name)).ok()?.as_str(),
+          )
+      }
+    };
+    is_complete.then(|| {
+      TsType::TsTypeRef(TsTypeRef {
+        span: DUMMY_SP,
+        type_name,
+        type_params: n.type_args.clone(),
+      })
+    })
+  }
+
+  /// Gets the class declared in this module that the identifier
+  /// refers to.
+  fn maybe_local_class(&self, ident: &Ident) -> Option<&'a Class> {
+    let symbol = self.module_symbol.symbol_from_swc(&ident.to_id())?;
+    symbol.decls().find_map(|decl| match decl.maybe_node()? {
+      SymbolNodeRef::ClassDecl(n) => Some(&*n.class),
+      SymbolNodeRef::ExportDecl(_, ExportDeclRef::Class(n)) => Some(&*n.class),
+      _ => None,
+    })
+  }
+
+  /// Infers the type returned by the return statements when they're
+  /// all inferred to the same type.
+  fn maybe_infer_returned_type(
+    &self,
+    return_stmts: &[&ReturnStmt],
+    ctx: InferContext,
+  ) -> Option<TsType> {
+    if return_stmts.iter().all(|stmt| stmt.arg.is_none()) {
+      return Some(ts_keyword_type(TsKeywordTypeKind::TsVoidKeyword));
+    }
+    maybe_uniform_type(
+      return_stmts.iter().map(|stmt| {
+        self.maybe_infer_returned_expr_type(stmt.arg.as_ref()?, ctx)
+      }),
+    )
+    .filter(|ts_type| self.has_emitted_type_names(ts_type))
+  }
+
+  /// Infers the type yielded by a generator function body when all the
+  /// yields are inferred to the same type.
+  fn maybe_infer_yielded_type(
+    &self,
+    yields: &[YieldedExpr],
+    ctx: InferContext,
+  ) -> Option<TsType> {
+    if yields.is_empty() {
+      return Some(ts_keyword_type(TsKeywordTypeKind::TsNeverKeyword));
+    }
+    maybe_uniform_type(yields.iter().map(|yielded| {
+      self.maybe_infer_returned_expr_type(yielded.arg.as_ref()?, ctx)
+    }))
+    .filter(|ts_type| self.has_emitted_type_names(ts_type))
+  }
+
+  /// Gets if the names referenced by an inferred type are globals or
+  /// are in the declaration output. A type copied from elsewhere in
+  /// the module (ex. a called function's return type) may reference
+  /// locals that aren't.
+  fn has_emitted_type_names(&self, ts_type: &TsType) -> bool {
+    let mut collector = TypeNameCollector::default();
+    ts_type.visit_with(&mut collector);
+    !collector.has_import_type
+      && collector.idents.iter().all(|ident| {
+        self
+          .module_symbol
+          .symbol_from_swc(&ident.to_id())
+          .map(|symbol| symbol.is_public())
+          .unwrap_or(true)
+      })
+  }
+
+  fn maybe_infer_returned_expr_type(
+    &self,
+    expr: &Expr,
+    ctx: InferContext,
+  ) -> Option<TsType> {
+    match expr {
+      Expr::Paren(paren) => {
+        self.maybe_infer_returned_expr_type(&paren.expr, ctx)
+      }
+      Expr::Call(CallExpr {
+        callee: Callee::Expr(callee),
+        type_args: None,
+        ..
+      }) => match &**callee {
+        Expr::Ident(ident) => self.maybe_fn_decl_return_type(ident),
+        _ => None,
+      },
+      Expr::Await(await_expr) => {
+        let awaited_type =
+          self.maybe_infer_returned_expr_type(&await_expr.arg, ctx)?;
+        Some(match maybe_promise_type_arg(&awaited_type) {
+          Some(inner) => inner.clone(),
+          None => awaited_type,
+        })
+      }
+      _ => self.infer_expr_type(expr, ctx),
+    }
+  }
+
+  /// Gets the explicit return type of a function declared at the top
+  /// level of this module that isn't generic or overloaded, which isn't
+  /// what's called when the name is shadowed (ex. by a parameter).
+  fn maybe_fn_decl_return_type(&self, name: &Ident) -> Option<TsType> {
+    let id = name.to_id();
+    let mut fn_decls =
+      self
+        .parsed_source
+        .module()
+        .body
+        .iter()
+        .filter_map(|item| match item {
+          ModuleItem::Stmt(Stmt::Decl(Decl::Fn(fn_decl)))
+          | ModuleItem::ModuleDecl(ModuleDecl::ExportDecl(ExportDecl {
+            decl: Decl::Fn(fn_decl),
+            ..
+          }))
+            if fn_decl.ident.to_id() == id =>
+          {
+            Some(fn_decl)
+          }
+          _ => None,
+        });
+    let fn_decl = fn_decls.next()?;
+    if fn_decls.next().is_some() || fn_decl.function.type_params.is_some() {
+      return None;
+    }
+    Some(*fn_decl.function.return_type.as_ref()?.type_ann.clone())
+  }
+
+  fn report_missing_return_type(&self, n: &Function, return_stmt: &ReturnStmt) {
+    self.report_missing_return_type_with_related(
+      n,
+      "Missing return type for function with return statement.",
+      "Value returned here.",
+      return_stmt.range(),
+    );
+  }
+
+  fn report_missing_yield_type(&self, n: &Function, yielded: &YieldedExpr) {
+    self.report_missing_return_type_with_related(
+      n,
+      "Missing return type for generator function with yielded values.",
+      "Value yielded here.",
+      yielded.range,
+    );
+  }
+
+  fn report_missing_return_type_with_related(
+    &self,
+    n: &Function,
+    message: &str,
+    related_message: &str,
+    related_range: SourceRange,
+  ) {
+    let text_info = self.parsed_source.text_info();
+    // only highlight the signature
+    let signature_end =
+      n.body.as_ref().map(|body| body.start()).unwrap_or(n.end());
+    self.reporter.diagnostic(Diagnostic {
+      code: DiagnosticCode::MissingReturnType,
+      severity: DiagnosticCode::MissingReturnType.default_severity(),
+      message: message.to_string(),
+      specifier: self.module_specifier.clone(),
+      line_and_column: Some(
+        text_info.line_and_column_display(n.start()).into(),
+      ),
+      end_line_and_column: Some(
+        text_info.line_and_column_display(signature_end).into(),
+      ),
+      related: vec![RelatedDiagnostic {
+        message: related_message.to_string(),
+        specifier: self.module_specifier.clone(),
+        line_and_column: Some(
+          text_info
+            .line_and_column_display(related_range.start)
+            .into(),
+        ),
+        end_line_and_column: Some(
+          text_info.line_and_column_display(related_range.end).into(),
+        ),
+      }],
+      hint: Some("Add an explicit return type to the function.".to_string()),
+    });
+  }
+
+  /// Gets the position after the key of a class property and its
+  /// optional or definite marker.
+  fn class_prop_key_end(&self, n: &ClassProp) -> SourcePos {
+    let key_end = n.key.end();
+    let text =
+      &self.parsed_source.text_info().text_str()[self.byte_index(key_end)..];
+    let trimmed = text.trim_start();
+    if trimmed.starts_with('?') || trimmed.starts_with('!') {
+      key_end + (text.len() - trimmed.len()) + 1
+    } else {
+      key_end
+    }
+  }
+
+  /// Gets the position after the parameters of a function.
+  fn function_params_end(&self, n: &Function) -> Option<SourcePos> {
+    let body_start = n.body.as_ref()?.start();
+    let text = self.parsed_source.text_info().text_str();
+    let start_index = self.byte_index(n.start());
+    let body_index = self.byte_index(body_start);
+    let paren_index = text[start_index..body_index].rfind(')')?;
+    Some(n.start() + paren_index + 1)
+  }
 }
 
 impl<'a, TReporter: Reporter> VisitMut for DtsTransformer<'a, TReporter> {
@@ -438,7 +1911,7 @@ impl<'a, TReporter: Reporter> VisitMut for DtsTransformer<'a, TReporter> {
                     _ => None,
                   };
                   explicit_type_ann.or_else(|| {
-                    maybe_infer_type_from_expr(&*assign.right).map(|type_ann| {
+                    self.maybe_infer_expr_type(&assign.right).map(|type_ann| {
                       Box::new(TsTypeAnn {
                         span: DUMMY_SP,
                         type_ann: Box::new(type_ann),
@@ -491,8 +1964,36 @@ impl<'a, TReporter: Reporter> VisitMut for DtsTransformer<'a, TReporter> {
       let type_ann = n
         .value
         .as_ref()
-        .and_then(|value| maybe_infer_type_from_expr(value))
+        .and_then(|value| self.maybe_infer_expr_type(value))
+        .or_else(|| {
+          let fix_type = n
+            .value
+            .as_ref()
+            .and_then(|value| self.maybe_infer_fix_expr_type(value))?;
+          self
+            .maybe_insert_type_ann(self.class_prop_key_end(n), &fix_type)
+            .then_some(fix_type)
+        })
         .unwrap_or_else(|| {
+          if n.span != DUMMY_SP {
+            let text_info = self.parsed_source.text_info();
+            self.reporter.diagnostic(Diagnostic {
+              code: DiagnosticCode::MissingPropertyType,
+              severity: DiagnosticCode::MissingPropertyType.default_severity(),
+              message:
+                "Missing type for class property that could not be inferred."
+                  .to_string(),
+              specifier: self.module_specifier.clone(),
+              line_and_column: Some(
+                text_info.line_and_column_display(n.start()).into(),
+              ),
+              end_line_and_column: Some(
+                text_info.line_and_column_display(n.end()).into(),
+              ),
+              related: Vec::new(),
+              hint: Some("Add an explicit type to the property.".to_string()),
+            });
+          }
           ts_keyword_type(TsKeywordTypeKind::TsUnknownKeyword)
         });
       n.type_ann = Some(Box::new(TsTypeAnn {
@@ -557,30 +2058,12 @@ impl<'a, TReporter: Reporter> VisitMut for DtsTransformer<'a, TReporter> {
 
   fn visit_mut_export_default_expr(&mut self, n: &mut ExportDefaultExpr) {
     if self.module_name.is_some() {
-      match &*n.expr {
-        // convert:
-        //   export default a;
-        // to:
-        //   export { a as __default };
-        Expr::Ident(orig) => self.append_module_items.push(
-          ModuleItem::ModuleDecl(ModuleDecl::ExportNamed(NamedExport {
-            span: DUMMY_SP,
-            specifiers: Vec::from([ExportSpecifier::Named(
-              ExportNamedSpecifier {
-                span: DUMMY_SP,
-                orig: ModuleExportName::Ident(ident(orig.sym.to_string())),
-                exported: Some(ModuleExportName::Ident(ident(
-                  "__default".to_string(),
-                ))),
-                is_type_only: false,
-              },
-            )]),
-            src: None,
-            type_only: false,
-            with: None,
-          })),
-        ),
-        _ => {}
+      // convert:
+      //   export default a;
+      // to:
+      //   export { a as __default };
+      if let Expr::Ident(orig) = &*n.expr {
+        self.push_default_export(orig.sym.to_string());
       }
     }
   }
@@ -616,63 +2099,54 @@ impl<'a, TReporter: Reporter> VisitMut for DtsTransformer<'a, TReporter> {
   }
 
   fn visit_mut_function(&mut self, n: &mut Function) {
-    // insert a void type when there's no return type
     if n.return_type.is_none() {
-      // todo: this should go into if statements and other things as well
-      let has_return_stmt = get_return_stmt_from_function(n).is_some();
-
-      if has_return_stmt {
-        let line_and_column = self
-          .parsed_source
-          .text_info()
-          .line_and_column_display(n.start());
-        self.reporter.diagnostic(Diagnostic {
-          message: "Missing return type for function with return statement."
-            .to_string(),
-          specifier: self.module_specifier.clone(),
-          line_and_column: Some(line_and_column.into()),
-        });
-      }
-
-      let return_type =
-        Box::new(if has_return_stmt || n.is_generator || n.body.is_none() {
-          ts_keyword_type(TsKeywordTypeKind::TsUnknownKeyword)
-        } else {
-          ts_keyword_type(TsKeywordTypeKind::TsVoidKeyword)
-        });
+      let return_type = match &n.body {
+        Some(body) => {
+          let return_stmts = get_return_stmts_from_function(n);
+          // the inferred return types are inserted when fixing
+          let ctx = InferContext {
+            is_const: false,
+            is_fix: self.fixes.is_some(),
+          };
+          let maybe_return_type =
+            self.maybe_infer_returned_type(&return_stmts, ctx);
+          let yields = if n.is_generator {
+            get_yielded_exprs_from_function_body(body)
+          } else {
+            Vec::new()
+          };
+          let maybe_yield_type = if n.is_generator {
+            self.maybe_infer_yielded_type(&yields, ctx)
+          } else {
+            None
+          };
+          if maybe_return_type.is_none() {
+            self.report_missing_return_type(n, return_stmts[0]);
+          } else if n.is_generator && maybe_yield_type.is_none() {
+            self.report_missing_yield_type(n, &yields[0]);
+          }
+          let is_inferred = maybe_return_type.is_some()
+            && (!n.is_generator || maybe_yield_type.is_some());
+          let return_type = wrap_function_return_type(
+            n,
+            maybe_return_type.unwrap_or_else(|| {
+              ts_keyword_type(TsKeywordTypeKind::TsUnknownKeyword)
+            }),
+            maybe_yield_type,
+          );
+          // make the return types explicit that were previously reported
+          if is_inferred && !return_stmts.is_empty() {
+            if let Some(params_end) = self.function_params_end(n) {
+              self.maybe_insert_type_ann(params_end, &return_type);
+            }
+          }
+          return_type
+        }
+        None => ts_keyword_type(TsKeywordTypeKind::TsUnknownKeyword),
+      };
       n.return_type = Some(Box::new(TsTypeAnn {
         span: DUMMY_SP,
-        type_ann: if n.is_async {
-          Box::new(TsType::TsTypeRef(TsTypeRef {
-            span: DUMMY_SP,
-            type_name: TsEntityName::Ident(Ident::new(
-              "Promise".into(),
-              DUMMY_SP,
-            )),
-            type_params: Some(Box::new(TsTypeParamInstantiation {
-              span: DUMMY_SP,
-              params: vec![return_type],
-            })),
-          }))
-        } else if n.is_generator {
-          Box::new(TsType::TsTypeRef(TsTypeRef {
-            span: DUMMY_SP,
-            type_name: TsEntityName::Ident(Ident::new(
-              "Generator".into(),
-              DUMMY_SP,
-            )),
-            type_params: Some(Box::new(TsTypeParamInstantiation {
-              span: DUMMY_SP,
-              params: vec![
-                Box::new(ts_keyword_type(TsKeywordTypeKind::TsUnknownKeyword)),
-                Box::new(ts_keyword_type(TsKeywordTypeKind::TsVoidKeyword)),
-                Box::new(ts_keyword_type(TsKeywordTypeKind::TsUnknownKeyword)),
-              ],
-            })),
-          }))
-        } else {
-          return_type
-        },
+        type_ann: Box::new(return_type),
       }));
     }
     n.body = None;
@@ -760,6 +2234,9 @@ impl<'a, TReporter: Reporter> VisitMut for DtsTransformer<'a, TReporter> {
     }
 
     let mut insert_decls = Vec::new();
+    // the tracer doesn't mark the default and namespace imports of
+    // packages as public, so these are only output when referenced
+    let mut package_import_decls = Vec::new();
     for item in &n.body {
       if let ModuleItem::ModuleDecl(ModuleDecl::Import(import_decl)) = item {
         if !import_decl.specifiers.is_empty() {
@@ -769,11 +2246,7 @@ impl<'a, TReporter: Reporter> VisitMut for DtsTransformer<'a, TReporter> {
             true,
           );
           if let Some(specifier) = maybe_specifier {
-            if let Some(module_symbol) =
-              self.root_symbol.get_module_from_specifier(&specifier)
-            {
-              let module_id = module_symbol.module_id();
-              let is_remote = is_remote_specifier(&specifier);
+            if let Some(imported_module) = self.imported_module(&specifier) {
               for specifier in &import_decl.specifiers {
                 match specifier {
                   ImportSpecifier::Named(named) => {
@@ -796,10 +2269,7 @@ impl<'a, TReporter: Reporter> VisitMut for DtsTransformer<'a, TReporter> {
                     let imported_name = named
                       .imported
                       .as_ref()
-                      .map(|i| match i {
-                        ModuleExportName::Ident(ident) => ident.sym.to_string(),
-                        ModuleExportName::Str(_) => todo!(),
-                      })
+                      .map(module_export_name_text)
                       .unwrap_or_else(|| named.local.sym.to_string());
 
                     insert_decls.push(ModuleItem::ModuleDecl(
@@ -810,22 +2280,17 @@ impl<'a, TReporter: Reporter> VisitMut for DtsTransformer<'a, TReporter> {
                           is_type_only: false,
                           id: named.local.clone(),
                           module_ref: TsModuleRef::TsEntityName(
-                            TsEntityName::TsQualifiedName(Box::new(
-                              TsQualifiedName {
-                                left: TsEntityName::Ident(ident(
-                                  if is_remote && imported_name == "default" {
-                                    module_id.to_default_code_string()
-                                  } else {
-                                    module_id.to_code_string()
-                                  },
-                                )),
-                                right: ident(if imported_name == "default" {
-                                  "__default".to_string()
-                                } else {
-                                  imported_name
-                                }),
-                              },
-                            )),
+                            self
+                              .export_module(
+                                &imported_module,
+                                &imported_name,
+                                named.range(),
+                              )
+                              .export_entity_name(
+                                &imported_name,
+                                self.external_packages,
+                                self.external_string_imports,
+                              ),
                           ),
                         },
                       )),
@@ -838,42 +2303,30 @@ impl<'a, TReporter: Reporter> VisitMut for DtsTransformer<'a, TReporter> {
                       .and_then(|symbol_id| {
                         self.module_symbol.symbol(symbol_id)
                       });
+                    let decl = PackageImportDecl {
+                      local: specifier.local.clone(),
+                      module: imported_module.clone(),
+                      is_default: true,
+                    };
                     match maybe_symbol {
-                      Some(symbol) if !symbol.is_public() => {
-                        continue;
+                      // same as namespace imports below (ex. the
+                      // `React.ReactElement` of `import React from "npm:react"`)
+                      Some(symbol)
+                        if !symbol.is_public()
+                          && matches!(
+                            imported_module,
+                            ImportedModule::Package(_)
+                          ) =>
+                      {
+                        package_import_decls.push((insert_decls.len(), decl));
                       }
-                      None => {
-                        continue;
+                      Some(symbol) if !symbol.is_public() => {}
+                      None => {}
+                      Some(_) => {
+                        insert_decls
+                          .push(decl.into_module_item(self.external_packages));
                       }
-                      _ => {}
                     }
-
-                    insert_decls.push(ModuleItem::ModuleDecl(
-                      ModuleDecl::TsImportEquals(Box::new(
-                        TsImportEqualsDecl {
-                          span: DUMMY_SP,
-                          is_export: false,
-                          is_type_only: false,
-                          id: specifier.local.clone(),
-                          module_ref: TsModuleRef::TsEntityName(
-                            TsEntityName::TsQualifiedName(Box::new(
-                              TsQualifiedName {
-                                left: TsEntityName::Ident(ident(
-                                  if is_remote {
-                                    module_id.to_default_code_string()
-                                  } else {
-                                    module_id.to_code_string()
-                                  },
-                                )),
-                                // can't use `.default` because it's a reserved word,
-                                // so use our custom `__default` instead
-                                right: ident("__default".to_string()),
-                              },
-                            )),
-                          ),
-                        },
-                      )),
-                    ));
                   }
                   ImportSpecifier::Namespace(specifier) => {
                     let maybe_symbol = self
@@ -882,30 +2335,41 @@ impl<'a, TReporter: Reporter> VisitMut for DtsTransformer<'a, TReporter> {
                       .and_then(|symbol_id| {
                         self.module_symbol.symbol(symbol_id)
                       });
+                    let decl = PackageImportDecl {
+                      local: specifier.local.clone(),
+                      module: imported_module.clone(),
+                      is_default: false,
+                    };
                     match maybe_symbol {
-                      Some(symbol) if !symbol.is_public() => {
-                        continue;
+                      // the tracer can't resolve qualified names (ex.
+                      // `path.ParsedPath`) in packages, so their namespace
+                      // imports are never marked as public
+                      Some(symbol)
+                        if !symbol.is_public()
+                          && matches!(
+                            imported_module,
+                            ImportedModule::Package(_)
+                          ) =>
+                      {
+                        package_import_decls.push((insert_decls.len(), decl));
                       }
-                      None => {
-                        continue;
+                      Some(symbol) if !symbol.is_public() => {}
+                      None => {}
+                      Some(_) => {
+                        if self.is_exported_local(&specifier.local) {
+                          self.report_string_namespace_members(
+                            &imported_module,
+                            specifier.range(),
+                          );
+                          self.report_package_star_re_exports(
+                            &imported_module,
+                            specifier.range(),
+                          );
+                        }
+                        insert_decls
+                          .push(decl.into_module_item(self.external_packages));
                       }
-                      _ => {}
                     }
-                    insert_decls.push(ModuleItem::ModuleDecl(
-                      ModuleDecl::TsImportEquals(Box::new(
-                        TsImportEqualsDecl {
-                          span: DUMMY_SP,
-                          is_export: false,
-                          is_type_only: false,
-                          id: specifier.local.clone(),
-                          module_ref: TsModuleRef::TsEntityName(
-                            TsEntityName::Ident(ident(
-                              module_id.to_code_string(),
-                            )),
-                          ),
-                        },
-                      )),
-                    ));
                   }
                 }
               }
@@ -918,6 +2382,13 @@ impl<'a, TReporter: Reporter> VisitMut for DtsTransformer<'a, TReporter> {
     n.body.splice(0..0, insert_decls);
 
     for (name, global_symbol) in self.module_symbol.traced_re_exports() {
+      let Some(re_exported_module) = self
+        .root_symbol
+        .get_module_from_id(global_symbol.module_id)
+        .and_then(|module| self.imported_module(module.specifier()))
+      else {
+        continue;
+      };
       let private_name = self.next_re_export_name();
       n.body
         .push(ModuleItem::ModuleDecl(ModuleDecl::TsImportEquals(
@@ -925,22 +2396,37 @@ impl<'a, TReporter: Reporter> VisitMut for DtsTransformer<'a, TReporter> {
             span: DUMMY_SP,
             is_export: false,
             is_type_only: false,
-            id: ident(private_name.to_string()),
+            id: ident(private_name.as_str().to_string()),
             module_ref: TsModuleRef::TsEntityName(
-              TsEntityName::TsQualifiedName(Box::new(TsQualifiedName {
-                left: TsEntityName::Ident(ident(
-                  global_symbol.module_id.to_code_string(),
-                )),
-                right: ident(name.clone()),
-              })),
+              re_exported_module.export_entity_name(
+                name,
+                self.external_packages,
+                self.external_string_imports,
+              ),
             ),
           }),
         )));
-      n.body.push(private_name.into_module_item(name.clone()));
+      n.body
+        .push(self.re_export_module_item(private_name, name.clone()));
     }
 
     visit_mut_module(self, n);
 
+    if !package_import_decls.is_empty() {
+      // insert them where they would've been among the inserted decls
+      let referenced_ids = ReferencedIdCollector::collect(&n.body);
+      let mut inserted_count = 0;
+      for (index, decl) in package_import_decls {
+        if referenced_ids.contains(&decl.local.to_id()) {
+          n.body.insert(
+            index + inserted_count,
+            decl.into_module_item(self.external_packages),
+          );
+          inserted_count += 1;
+        }
+      }
+    }
+
     if let Some(module_name) = self.module_name.clone() {
       let module_items = n.body.drain(..).collect::<Vec<_>>();
       n.body
@@ -949,7 +2435,7 @@ impl<'a, TReporter: Reporter> VisitMut for DtsTransformer<'a, TReporter> {
             span: DUMMY_SP,
             declare: true,
             global: false,
-            id: TsModuleName::Ident(ident(module_name.into())),
+            id: TsModuleName::Ident(ident(module_name)),
             body: Some(TsNamespaceBody::TsModuleBlock(TsModuleBlock {
               span: DUMMY_SP,
               body: module_items,
@@ -968,6 +2454,53 @@ impl<'a, TReporter: Reporter> VisitMut for DtsTransformer<'a, TReporter> {
   }
 
   fn visit_mut_module_item(&mut self, n: &mut ModuleItem) {
+    if let ModuleItem::ModuleDecl(ModuleDecl::TsExportAssignment(assignment)) =
+      n
+    {
+      // export = a; -> export default a;
+      *n = ModuleItem::ModuleDecl(ModuleDecl::ExportDefaultExpr(
+        ExportDefaultExpr {
+          span: assignment.span,
+          expr: assignment.expr.clone(),
+        },
+      ));
+    }
+    if self.module_name.is_some() {
+      if let ModuleItem::ModuleDecl(ModuleDecl::ExportDefaultDecl(decl)) = n {
+        // default exports aren't allowed in namespaces, so convert:
+        //   export default class A {}
+        // to:
+        //   class A {}
+        //   export { A as __default };
+        let (local_name, decl) = match decl.decl.clone() {
+          DefaultDecl::Class(expr) => {
+            let ident =
+              expr.ident.unwrap_or_else(|| ident("__default".to_string()));
+            let decl = Decl::Class(ClassDecl {
+              ident: ident.clone(),
+              declare: false,
+              class: expr.class,
+            });
+            (ident.sym.to_string(), decl)
+          }
+          DefaultDecl::Fn(expr) => {
+            let ident =
+              expr.ident.unwrap_or_else(|| ident("__default".to_string()));
+            let decl = Decl::Fn(FnDecl {
+              ident: ident.clone(),
+              declare: false,
+              function: expr.function,
+            });
+            (ident.sym.to_string(), decl)
+          }
+          DefaultDecl::TsInterfaceDecl(decl) => {
+            (decl.id.sym.to_string(), Decl::TsInterface(decl))
+          }
+        };
+        *n = ModuleItem::Stmt(Stmt::Decl(decl));
+        self.push_default_export(local_name);
+      }
+    }
     visit_mut_module_item(self, n)
   }
 
@@ -1018,7 +2551,7 @@ impl<'a, TReporter: Reporter> VisitMut for DtsTransformer<'a, TReporter> {
       }
       true
     });
-    n.extend(self.append_module_items.drain(..));
+    n.append(&mut self.append_module_items);
 
     // todo: temporary workaround until https://github.com/microsoft/TypeScript/issues/54446 is fixed
     let should_insert_ts_under_5_2_workaround = self.module_name.is_some()
@@ -1055,93 +2588,103 @@ impl<'a, TReporter: Reporter> VisitMut for DtsTransformer<'a, TReporter> {
   }
 
   fn visit_mut_named_export(&mut self, n: &mut NamedExport) {
-    n.specifiers
-      .retain(|s| s.span() == DUMMY_SP || self.ranges.contains(&s.range()));
+    let is_local = n.src.is_none();
+    n.specifiers.retain(|s| match s {
+      _ if s.span() == DUMMY_SP => true,
+      // the tracer marks the original symbol of an aliased local
+      // export (ex. `export { a as b }`) and not the specifier
+      ExportSpecifier::Named(ExportNamedSpecifier {
+        orig: ModuleExportName::Ident(orig),
+        exported: Some(_),
+        ..
+      }) if is_local => self
+        .module_symbol
+        .symbol_from_swc(&orig.to_id())
+        .map(|symbol| symbol.is_public())
+        .unwrap_or(false),
+      _ => self.ranges.contains(&s.range()),
+    });
     if let Some(src) = n.src.as_ref().map(|s| s.value.to_string()) {
       let maybe_src_specifier =
         self
           .graph
           .resolve_dependency(&src, self.module_specifier, true);
-      if let Some(src_specifier) = maybe_src_specifier {
-        let src_module_id = self
-          .root_symbol
-          .get_module_from_specifier(&src_specifier)
-          .unwrap()
-          .module_id();
+      let maybe_src_module = maybe_src_specifier
+        .and_then(|specifier| self.imported_module(&specifier));
+      if let Some(src_module) = maybe_src_module {
         for specifier in &mut n.specifiers {
-          match specifier {
-            ExportSpecifier::Named(named) => {
-              let export_name = named
-                .exported
-                .as_ref()
-                .map(|e| match e {
-                  ModuleExportName::Ident(ident) => ident.sym.to_string(),
-                  ModuleExportName::Str(_) => todo!(),
-                })
-                .clone()
-                .unwrap_or_else(|| match &named.orig {
-                  ModuleExportName::Ident(ident) => ident.sym.to_string(),
-                  ModuleExportName::Str(_) => todo!(),
-                });
-              let private_name = self.next_re_export_name();
-              self.append_module_items.push(ModuleItem::ModuleDecl(
-                ModuleDecl::TsImportEquals(Box::new(TsImportEqualsDecl {
-                  span: DUMMY_SP,
-                  is_export: false,
-                  is_type_only: false,
-                  id: ident(private_name.to_string()),
-                  module_ref: TsModuleRef::TsEntityName(
-                    TsEntityName::TsQualifiedName(Box::new(TsQualifiedName {
-                      left: TsEntityName::Ident(ident(
-                        src_module_id.to_code_string(),
-                      )),
-                      right: ident(match &named.orig {
-                        ModuleExportName::Ident(ident) => {
-                          let name = ident.sym.to_string();
-                          if name == "default" {
-                            "__default".to_string()
-                          } else {
-                            name
-                          }
-                        }
-                        ModuleExportName::Str(_) => todo!(),
-                      }),
-                    })),
-                  ),
-                })),
-              ));
-              self
-                .append_module_items
-                .push(private_name.into_module_item(export_name));
+          let range = specifier.range();
+          let (orig_name, export_name) = match specifier {
+            ExportSpecifier::Named(named) => (
+              module_export_name_text(&named.orig),
+              module_export_name_text(
+                named.exported.as_ref().unwrap_or(&named.orig),
+              ),
+            ),
+            // export a from "./mod.ts";
+            ExportSpecifier::Default(default) => {
+              ("default".to_string(), default.exported.sym.to_string())
             }
             ExportSpecifier::Namespace(specifier) => {
-              let export_name = match &specifier.name {
-                ModuleExportName::Ident(ident) => ident.sym.to_string(),
-                ModuleExportName::Str(_) => todo!(),
-              };
+              self.report_string_namespace_members(
+                &src_module,
+                specifier.range(),
+              );
+              self
+                .report_package_star_re_exports(&src_module, specifier.range());
+              let export_name = module_export_name_text(&specifier.name);
               let private_name = self.next_re_export_name();
               self.append_module_items.push(ModuleItem::ModuleDecl(
                 ModuleDecl::TsImportEquals(Box::new(TsImportEqualsDecl {
                   span: DUMMY_SP,
                   is_export: false,
                   is_type_only: false,
-                  id: ident(private_name.to_string()),
+                  id: ident(private_name.as_str().to_string()),
                   module_ref: TsModuleRef::TsEntityName(TsEntityName::Ident(
-                    ident(src_module_id.to_code_string()),
+                    ident(src_module.namespace_name(self.external_packages)),
                   )),
                 })),
               ));
               self
                 .append_module_items
-                .push(private_name.into_module_item(export_name));
+                .push(self.re_export_module_item(private_name, export_name));
+              continue;
             }
-            ExportSpecifier::Default(_) => todo!(),
-          }
+          };
+          let private_name = self.next_re_export_name();
+          self.append_module_items.push(ModuleItem::ModuleDecl(
+            ModuleDecl::TsImportEquals(Box::new(TsImportEqualsDecl {
+              span: DUMMY_SP,
+              is_export: false,
+              is_type_only: false,
+              id: ident(private_name.as_str().to_string()),
+              module_ref: TsModuleRef::TsEntityName(
+                self
+                  .export_module(&src_module, &orig_name, range)
+                  .export_entity_name(
+                    &orig_name,
+                    self.external_packages,
+                    self.external_string_imports,
+                  ),
+              ),
+            })),
+          ));
+          self
+            .append_module_items
+            .push(self.re_export_module_item(private_name, export_name));
         }
         n.specifiers.clear();
       }
     }
     n.src = None;
+    for specifier in &mut n.specifiers {
+      if let ExportSpecifier::Named(named) = specifier {
+        if let Some(exported) = &named.exported {
+          named.exported =
+            Some(self.export_name(module_export_name_text(exported)));
+        }
+      }
+    }
     visit_mut_named_export(self, n)
   }
 
@@ -1243,6 +2786,49 @@ impl<'a, TReporter: Reporter> VisitMut for DtsTransformer<'a, TReporter> {
     n.decls.retain(|decl| {
       decl.span() == DUMMY_SP || self.ranges.contains(&decl.range())
     });
+    for decl in &mut n.decls {
+      decl.definite = false;
+      let is_source_decl = decl.span != DUMMY_SP;
+      let decl_range = decl.range();
+      let init = decl.init.take();
+      let (Pat::Ident(name), Some(init)) = (&mut decl.name, init) else {
+        continue;
+      };
+      if name.type_ann.is_some() {
+        continue;
+      }
+      if n.kind == VarDeclKind::Const {
+        if let Some(lit) = maybe_const_literal_init(&init) {
+          decl.init = Some(Box::new(lit));
+          continue;
+        }
+      }
+      let type_ann = self.maybe_infer_expr_type(&init).unwrap_or_else(|| {
+        if is_source_decl {
+          let text_info = self.parsed_source.text_info();
+          self.reporter.diagnostic(Diagnostic {
+            code: DiagnosticCode::MissingPropertyType,
+            severity: DiagnosticCode::MissingPropertyType.default_severity(),
+            message: "Missing type for variable that could not be inferred."
+              .to_string(),
+            specifier: self.module_specifier.clone(),
+            line_and_column: Some(
+              text_info.line_and_column_display(decl_range.start).into(),
+            ),
+            end_line_and_column: Some(
+              text_info.line_and_column_display(decl_range.end).into(),
+            ),
+            related: Vec::new(),
+            hint: Some("Add an explicit type to the variable.".to_string()),
+          });
+        }
+        ts_keyword_type(TsKeywordTypeKind::TsUnknownKeyword)
+      });
+      name.type_ann = Some(Box::new(TsTypeAnn {
+        span: DUMMY_SP,
+        type_ann: Box::new(type_ann),
+      }));
+    }
     visit_mut_var_decl(self, n)
   }
 
@@ -1259,8 +2845,6 @@ impl<'a, TReporter: Reporter> VisitMut for DtsTransformer<'a, TReporter> {
   }
 
   fn visit_mut_var_declarator(&mut self, n: &mut VarDeclarator) {
-    n.definite = false;
-    n.init = None;
     visit_mut_var_declarator(self, n)
   }
 
@@ -1280,39 +2864,36 @@ impl<'a, TReporter: Reporter> VisitMut for DtsTransformer<'a, TReporter> {
       }
     }
 
-    match &n {
-      Pat::Assign(assign) => {
-        let type_ann = pat_type_ann(&assign.left).clone().or_else(|| {
-          maybe_infer_type_from_expr(&*assign.right).map(|type_ann| {
-            Box::new(TsTypeAnn {
-              span: DUMMY_SP,
-              type_ann: Box::new(type_ann),
-            })
+    if let Pat::Assign(assign) = &n {
+      let type_ann = pat_type_ann(&assign.left).or_else(|| {
+        self.maybe_infer_expr_type(&assign.right).map(|type_ann| {
+          Box::new(TsTypeAnn {
+            span: DUMMY_SP,
+            type_ann: Box::new(type_ann),
           })
-        });
-        match &*assign.left {
-          Pat::Ident(name) => {
-            *n = Pat::Ident(BindingIdent {
-              id: Ident {
-                span: DUMMY_SP,
-                sym: name.sym.to_string().into(),
-                optional: true,
-              },
-              type_ann,
-            });
-          }
-          Pat::Object(obj) => {
-            *n = Pat::Object(ObjectPat {
+        })
+      });
+      match &*assign.left {
+        Pat::Ident(name) => {
+          *n = Pat::Ident(BindingIdent {
+            id: Ident {
               span: DUMMY_SP,
+              sym: name.sym.to_string().into(),
               optional: true,
-              type_ann,
-              props: obj.props.clone(),
-            });
-          }
-          _ => {}
+            },
+            type_ann,
+          });
+        }
+        Pat::Object(obj) => {
+          *n = Pat::Object(ObjectPat {
+            span: DUMMY_SP,
+            optional: true,
+            type_ann,
+            props: obj.props.clone(),
+          });
         }
+        _ => {}
       }
-      _ => {}
     }
 
     visit_mut_pat(self, n)
@@ -1346,116 +2927,350 @@ impl<'a, TReporter: Reporter> VisitMut for DtsTransformer<'a, TReporter> {
   }
 }
 
-fn maybe_infer_type_from_expr(expr: &Expr) -> Option<TsType> {
-  match expr {
-    Expr::TsTypeAssertion(n) => Some(*n.type_ann.clone()),
-    Expr::TsAs(n) => Some(*n.type_ann.clone()),
-    Expr::Lit(lit) => {
-      let keyword = match lit {
-        Lit::Str(_) => Some(TsKeywordTypeKind::TsStringKeyword),
-        Lit::Bool(_) => Some(TsKeywordTypeKind::TsBooleanKeyword),
-        Lit::Null(_) => Some(TsKeywordTypeKind::TsNullKeyword),
-        Lit::Num(_) => Some(TsKeywordTypeKind::TsNumberKeyword),
-        Lit::BigInt(_) => Some(TsKeywordTypeKind::TsBigIntKeyword),
-        Lit::Regex(_) => None,
-        Lit::JSXText(_) => None,
-      };
-      keyword.map(|kind| {
-        TsType::TsKeywordType(TsKeywordType {
-          span: DUMMY_SP,
-          kind,
-        })
-      })
+/// Gets the literal type of a primitive literal (ex. `"a"`, `-1`,
+/// or `true`).
+fn maybe_lit_type(expr: &Expr) -> Option<TsType> {
+  let lit = match expr {
+    Expr::Lit(Lit::Str(str)) => TsLit::Str(Str {
+      span: DUMMY_SP,
+      value: str.value.clone(),
+      raw: None,
+    }),
+    Expr::Lit(Lit::Num(num)) => TsLit::Number(Number {
+      span: DUMMY_SP,
+      value: num.value,
+      raw: None,
+    }),
+    Expr::Lit(Lit::Bool(bool)) => TsLit::Bool(*bool),
+    Expr::Lit(Lit::BigInt(big_int)) => TsLit::BigInt(BigInt {
+      span: DUMMY_SP,
+      value: big_int.value.clone(),
+      raw: None,
+    }),
+    Expr::Lit(Lit::Null(_)) => {
+      return Some(ts_keyword_type(TsKeywordTypeKind::TsNullKeyword))
     }
-    Expr::This(_)
-    | Expr::Array(_)
-    | Expr::Object(_)
-    | Expr::Fn(_)
-    | Expr::Unary(_)
-    | Expr::Update(_)
-    | Expr::Bin(_)
-    | Expr::Assign(_)
-    | Expr::Member(_)
-    | Expr::SuperProp(_)
-    | Expr::Cond(_)
-    | Expr::Call(_)
-    | Expr::New(_)
-    | Expr::Seq(_)
-    | Expr::Ident(_)
-    | Expr::Tpl(_)
-    | Expr::TaggedTpl(_)
-    | Expr::Arrow(_)
-    | Expr::Class(_)
-    | Expr::Yield(_)
-    | Expr::MetaProp(_)
-    | Expr::Await(_)
-    | Expr::Paren(_)
-    | Expr::JSXMember(_)
-    | Expr::JSXNamespacedName(_)
-    | Expr::JSXEmpty(_)
-    | Expr::JSXElement(_)
-    | Expr::JSXFragment(_)
-    | Expr::TsConstAssertion(_)
-    | Expr::TsNonNull(_)
-    | Expr::TsInstantiation(_)
-    | Expr::TsSatisfies(_)
-    | Expr::PrivateName(_)
-    | Expr::OptChain(_)
-    | Expr::Invalid(_) => None,
+    Expr::Tpl(tpl) if tpl.exprs.is_empty() => TsLit::Str(Str {
+      span: DUMMY_SP,
+      value: tpl.quasis[0].cooked.clone()?,
+      raw: None,
+    }),
+    Expr::Unary(UnaryExpr {
+      op: UnaryOp::Minus,
+      arg,
+      ..
+    }) => match &**arg {
+      Expr::Lit(Lit::Num(num)) => TsLit::Number(Number {
+        span: DUMMY_SP,
+        value: -num.value,
+        raw: None,
+      }),
+      Expr::Lit(Lit::BigInt(big_int)) => TsLit::BigInt(BigInt {
+        span: DUMMY_SP,
+        value: Box::new(-(*big_int.value.clone())),
+        raw: None,
+      }),
+      _ => return None,
+    },
+    _ => return None,
+  };
+  Some(TsType::TsLitType(TsLitType {
+    span: DUMMY_SP,
+    lit,
+  }))
+}
+
+/// Gets the literal to keep as the initializer of a `const`
+/// declaration, which is what `tsc` does for primitive literals
+/// (ex. `export declare const a = 1;`).
+fn maybe_const_literal_init(expr: &Expr) -> Option<Expr> {
+  match maybe_lit_type(expr)? {
+    TsType::TsLitType(TsLitType {
+      lit: TsLit::Str(str),
+      ..
+    }) => Some(Expr::Lit(Lit::Str(str))),
+    TsType::TsLitType(_) => Some(expr.clone()),
+    _ => None,
   }
 }
 
-fn get_return_stmt_from_function<'a>(
-  func: &'a Function,
-) -> Option<&'a ReturnStmt> {
-  let body = func.body.as_ref()?;
-  get_return_stmt_from_stmts(&body.stmts)
+fn union_member_order(ts_type: &TsType) -> u8 {
+  match ts_type {
+    TsType::TsKeywordType(keyword) => match keyword.kind {
+      TsKeywordTypeKind::TsStringKeyword => 0,
+      TsKeywordTypeKind::TsNumberKeyword => 1,
+      TsKeywordTypeKind::TsBigIntKeyword => 2,
+      TsKeywordTypeKind::TsBooleanKeyword => 3,
+      TsKeywordTypeKind::TsNullKeyword => 5,
+      _ => 4,
+    },
+    _ => 4,
+  }
 }
 
-fn get_return_stmt_from_stmts<'a>(stmts: &'a [Stmt]) -> Option<&'a ReturnStmt> {
-  for stmt in stmts {
-    if let Some(return_stmt) = get_return_stmt_from_stmt(stmt) {
-      return Some(return_stmt);
+fn readonly_type(ts_type: TsType) -> TsType {
+  TsType::TsTypeOperator(TsTypeOperator {
+    span: DUMMY_SP,
+    op: TsTypeOperatorOp::ReadOnly,
+    type_ann: Box::new(ts_type),
+  })
+}
+
+fn prop_name_to_type_key(prop_name: &PropName) -> Option<Expr> {
+  match prop_name {
+    PropName::Ident(ident) => Some(Expr::Ident(ident.clone())),
+    PropName::Str(str) => Some(Expr::Lit(Lit::Str(Str {
+      span: DUMMY_SP,
+      value: str.value.clone(),
+      raw: None,
+    }))),
+    PropName::Num(num) => Some(Expr::Lit(Lit::Num(Number {
+      span: DUMMY_SP,
+      value: num.value,
+      raw: None,
+    }))),
+    PropName::Computed(_) | PropName::BigInt(_) => None,
+  }
+}
+
+/// Converts a parameter to a method signature parameter when it has
+/// an explicit type.
+fn pat_to_ts_fn_param(pat: &Pat) -> Option<TsFnParam> {
+  match pat {
+    Pat::Ident(ident) if ident.type_ann.is_some() => {
+      Some(TsFnParam::Ident(ident.clone()))
+    }
+    Pat::Rest(rest) if rest.type_ann.is_some() => {
+      Some(TsFnParam::Rest(rest.clone()))
+    }
+    _ => None,
+  }
+}
+
+/// Gets the type when all the types are inferred and the same.
+fn maybe_uniform_type(
+  types: impl Iterator<Item = Option<TsType>>,
+) -> Option<TsType> {
+  let mut result: Option<TsType> = None;
+  for ts_type in types {
+    let ts_type = ts_type?;
+    match &result {
+      Some(first) if !first.eq_ignore_span(&ts_type) => return None,
+      Some(_) => {}
+      None => result = Some(ts_type),
+    }
+  }
+  result
+}
+
+/// Wraps the type returned by a function's return statements in the
+/// type the function returns (ex. `Promise<T>` for async functions).
+fn wrap_function_return_type(
+  n: &Function,
+  return_type: TsType,
+  maybe_yield_type: Option<TsType>,
+) -> TsType {
+  let unknown_type = || ts_keyword_type(TsKeywordTypeKind::TsUnknownKeyword);
+  if n.is_generator {
+    type_ref(
+      if n.is_async {
+        "AsyncGenerator"
+      } else {
+        "Generator"
+      },
+      vec![
+        maybe_yield_type.unwrap_or_else(unknown_type),
+        return_type,
+        unknown_type(),
+      ],
+    )
+  } else if n.is_async && maybe_promise_type_arg(&return_type).is_none() {
+    type_ref("Promise", vec![return_type])
+  } else {
+    return_type
+  }
+}
+
+/// Gets `T` of a `Promise<T>` type.
+fn maybe_promise_type_arg(ts_type: &TsType) -> Option<&TsType> {
+  match ts_type {
+    TsType::TsTypeRef(TsTypeRef {
+      type_name: TsEntityName::Ident(ident),
+      type_params: Some(type_params),
+      ..
+    }) if &*ident.sym == "Promise" && type_params.params.len() == 1 => {
+      Some(&type_params.params[0])
+    }
+    _ => None,
+  }
+}
+
+fn type_ref(name: &str, params: Vec<TsType>) -> TsType {
+  TsType::TsTypeRef(TsTypeRef {
+    span: DUMMY_SP,
+    type_name: TsEntityName::Ident(Ident::new(name.into(), DUMMY_SP)),
+    type_params: Some(Box::new(TsTypeParamInstantiation {
+      span: DUMMY_SP,
+      params: params.into_iter().map(Box::new).collect(),
+    })),
+  })
+}
+
+#[derive(Clone, Copy, Default)]
+struct InferContext {
+  /// If within an `as const` assertion, which gives readonly literal
+  /// types.
+  is_const: bool,
+  /// If inferring a type to insert into the source when fixing.
+  is_fix: bool,
+}
+
+struct YieldedExpr {
+  range: SourceRange,
+  /// The yielded value, which is `None` for a delegated yield.
+  arg: Option<Expr>,
+}
+
+fn get_yielded_exprs_from_function_body(body: &BlockStmt) -> Vec<YieldedExpr> {
+  let mut collector = YieldExprCollector::default();
+  body.visit_children_with(&mut collector);
+  collector.yields
+}
+
+/// Collects the yield expressions in a generator body.
+#[derive(Default)]
+struct YieldExprCollector {
+  yields: Vec<YieldedExpr>,
+}
+
+impl Visit for YieldExprCollector {
+  fn visit_yield_expr(&mut self, n: &YieldExpr) {
+    self.yields.push(YieldedExpr {
+      range: n.range(),
+      arg: match &n.arg {
+        Some(arg) if !n.delegate => Some((**arg).clone()),
+        _ => None,
+      },
+    });
+    n.visit_children_with(self);
+  }
+
+  // yields in nested functions belong to those functions
+  fn visit_function(&mut self, _n: &Function) {}
+
+  fn visit_arrow_expr(&mut self, _n: &ArrowExpr) {}
+
+  fn visit_class(&mut self, _n: &Class) {}
+}
+
+/// Collects the root identifiers of the names referenced by a type.
+#[derive(Default)]
+struct TypeNameCollector {
+  idents: Vec<Ident>,
+  has_import_type: bool,
+}
+
+impl Visit for TypeNameCollector {
+  fn visit_ts_entity_name(&mut self, n: &TsEntityName) {
+    self.idents.push(ts_entity_name_root_ident(n).clone());
+  }
+
+  fn visit_ts_import_type(&mut self, _n: &TsImportType) {
+    self.has_import_type = true;
+  }
+}
+
+fn ts_entity_name_root_ident(name: &TsEntityName) -> &Ident {
+  match name {
+    TsEntityName::Ident(ident) => ident,
+    TsEntityName::TsQualifiedName(qualified) => {
+      ts_entity_name_root_ident(&qualified.left)
     }
   }
+}
+
+fn ts_entity_name_type(name: &TsEntityName) -> TsType {
+  TsType::TsTypeRef(TsTypeRef {
+    span: DUMMY_SP,
+    type_name: name.clone(),
+    type_params: None,
+  })
+}
+
+fn expr_to_ts_entity_name(expr: &Expr) -> Option<TsEntityName> {
+  match expr {
+    Expr::Ident(ident) => Some(TsEntityName::Ident(ident.clone())),
+    Expr::Member(member) => match &member.prop {
+      MemberProp::Ident(prop) => {
+        Some(TsEntityName::TsQualifiedName(Box::new(TsQualifiedName {
+          left: expr_to_ts_entity_name(&member.obj)?,
+          right: prop.clone(),
+        })))
+      }
+      MemberProp::PrivateName(_) | MemberProp::Computed(_) => None,
+    },
+    _ => None,
+  }
+}
+
+fn get_return_stmts_from_function(func: &Function) -> Vec<&ReturnStmt> {
+  let mut return_stmts = Vec::new();
+  if let Some(body) = &func.body {
+    collect_return_stmts_from_stmts(&body.stmts, &mut return_stmts);
+  }
+  return_stmts
+}
 
-  None
+fn collect_return_stmts_from_stmts<'a>(
+  stmts: &'a [Stmt],
+  return_stmts: &mut Vec<&'a ReturnStmt>,
+) {
+  for stmt in stmts {
+    collect_return_stmts_from_stmt(stmt, return_stmts);
+  }
 }
 
-fn get_return_stmt_from_stmt<'a>(stmt: &'a Stmt) -> Option<&'a ReturnStmt> {
+fn collect_return_stmts_from_stmt<'a>(
+  stmt: &'a Stmt,
+  return_stmts: &mut Vec<&'a ReturnStmt>,
+) {
   match stmt {
-    Stmt::Block(n) => get_return_stmt_from_stmts(&n.stmts),
-    Stmt::With(n) => get_return_stmt_from_stmt(&n.body),
-    Stmt::Return(n) => Some(n),
-    Stmt::Labeled(n) => get_return_stmt_from_stmt(&n.body),
-    Stmt::If(n) => get_return_stmt_from_stmt(&n.cons),
-    Stmt::Switch(n) => n
-      .cases
-      .iter()
-      .find_map(|case| get_return_stmt_from_stmts(&case.cons)),
-    Stmt::Try(n) => get_return_stmt_from_stmts(&n.block.stmts)
-      .or_else(|| {
-        n.handler
-          .as_ref()
-          .and_then(|h| get_return_stmt_from_stmts(&h.body.stmts))
-      })
-      .or_else(|| {
-        n.finalizer
-          .as_ref()
-          .and_then(|f| get_return_stmt_from_stmts(&f.stmts))
-      }),
-    Stmt::While(n) => get_return_stmt_from_stmt(&n.body),
-    Stmt::DoWhile(n) => get_return_stmt_from_stmt(&n.body),
-    Stmt::For(n) => get_return_stmt_from_stmt(&n.body),
-    Stmt::ForIn(n) => get_return_stmt_from_stmt(&n.body),
-    Stmt::ForOf(n) => get_return_stmt_from_stmt(&n.body),
+    Stmt::Block(n) => collect_return_stmts_from_stmts(&n.stmts, return_stmts),
+    Stmt::With(n) => collect_return_stmts_from_stmt(&n.body, return_stmts),
+    Stmt::Return(n) => return_stmts.push(n),
+    Stmt::Labeled(n) => collect_return_stmts_from_stmt(&n.body, return_stmts),
+    Stmt::If(n) => {
+      collect_return_stmts_from_stmt(&n.cons, return_stmts);
+      if let Some(alt) = &n.alt {
+        collect_return_stmts_from_stmt(alt, return_stmts);
+      }
+    }
+    Stmt::Switch(n) => {
+      for case in &n.cases {
+        collect_return_stmts_from_stmts(&case.cons, return_stmts);
+      }
+    }
+    Stmt::Try(n) => {
+      collect_return_stmts_from_stmts(&n.block.stmts, return_stmts);
+      if let Some(handler) = &n.handler {
+        collect_return_stmts_from_stmts(&handler.body.stmts, return_stmts);
+      }
+      if let Some(finalizer) = &n.finalizer {
+        collect_return_stmts_from_stmts(&finalizer.stmts, return_stmts);
+      }
+    }
+    Stmt::While(n) => collect_return_stmts_from_stmt(&n.body, return_stmts),
+    Stmt::DoWhile(n) => collect_return_stmts_from_stmt(&n.body, return_stmts),
+    Stmt::For(n) => collect_return_stmts_from_stmt(&n.body, return_stmts),
+    Stmt::ForIn(n) => collect_return_stmts_from_stmt(&n.body, return_stmts),
+    Stmt::ForOf(n) => collect_return_stmts_from_stmt(&n.body, return_stmts),
     Stmt::Break(_)
     | Stmt::Continue(_)
     | Stmt::Throw(_)
     | Stmt::Debugger(_)
     | Stmt::Decl(_)
     | Stmt::Expr(_)
-    | Stmt::Empty(_) => None,
+    | Stmt::Empty(_) => {}
   }
 }
 
@@ -1477,7 +3292,7 @@ fn has_internal_jsdoc(source: &ParsedSource, pos: SourcePos) -> bool {
   if let Some(comments) = source.comments().get_leading(pos) {
     comments.iter().any(|c| {
       c.kind == CommentKind::Block
-        && c.text.starts_with("*")
+        && c.text.starts_with('*')
         && c.text.contains("@internal")
     })
   } else {
@@ -0,0 +1,106 @@
+use deno_ast::ModuleSpecifier;
+use deno_graph::source::CacheSetting;
+use deno_graph::source::LoadFuture;
+use deno_graph::source::LoadResponse;
+use deno_graph::source::Loader;
+use deno_graph::source::ResolutionMode;
+use deno_graph::source::ResolveError;
+use deno_graph::source::Resolver;
+use deno_graph::ModuleGraph;
+
+use crate::resolver::ImportMapResolver;
+use crate::JsxOptions;
+
+/// deno_graph resolves `jsr:` specifiers with the registry and errors
+/// when they're external, so they're resolved to this scheme instead
+/// and converted back to `jsr:` when emitting.
+const EXTERNAL_JSR_PREFIX: &str = "libpack-jsr:";
+
+/// Gets if the specifier is for an npm, jsr, or node built-in package,
+/// which are always left as an external import in the output.
+pub fn is_package_specifier(specifier: &ModuleSpecifier) -> bool {
+  matches!(specifier.scheme(), "npm" | "jsr" | "node")
+    || specifier.as_str().starts_with(EXTERNAL_JSR_PREFIX)
+}
+
+/// Gets if the module is only known by its specifier, which is the case
+/// for packages and remote modules the loader left external, so its
+/// exports can only be referred to through an import of it.
+pub fn is_opaque_external(
+  graph: &ModuleGraph,
+  specifier: &ModuleSpecifier,
+) -> bool {
+  is_package_specifier(specifier)
+    || matches!(graph.get(specifier), Some(deno_graph::Module::External(_)))
+}
+
+/// Gets the specifier text to use in an external import.
+pub fn external_specifier_text(specifier: &ModuleSpecifier) -> String {
+  match specifier.as_str().strip_prefix(EXTERNAL_JSR_PREFIX) {
+    Some(rest) => format!("jsr:{}", rest),
+    None => specifier.to_string(),
+  }
+}
+
+/// Resolves with the import map when provided and keeps `jsr:`
+/// specifiers out of deno_graph's registry resolution.
+#[derive(Debug)]
+pub struct PackResolver<'a> {
+  pub import_map: Option<&'a ImportMapResolver>,
+  pub jsx: &'a JsxOptions,
+}
+
+impl<'a> Resolver for PackResolver<'a> {
+  /// Adds the JSX runtime to the dependencies of each JSX module so it
+  /// is imported like any other module in the output.
+  fn default_jsx_import_source(&self) -> Option<String> {
+    self.jsx.automatic_import_source().map(ToOwned::to_owned)
+  }
+
+  fn jsx_import_source_module(&self) -> &str {
+    self.jsx.import_source_module()
+  }
+
+  fn resolve(
+    &self,
+    specifier: &str,
+    referrer: &ModuleSpecifier,
+    mode: ResolutionMode,
+  ) -> Result<ModuleSpecifier, ResolveError> {
+    let resolved = match self.import_map {
+      Some(import_map) => import_map.resolve(specifier, referrer, mode)?,
+      None => deno_graph::resolve_import(specifier, referrer)?,
+    };
+    if resolved.scheme() == "jsr" {
+      let text = &resolved.as_str()["jsr:".len()..];
+      Ok(
+        ModuleSpecifier::parse(&format!("{}{}", EXTERNAL_JSR_PREFIX, text))
+          .map_err(|err| ResolveError::Other(err.into()))?,
+      )
+    } else {
+      Ok(resolved)
+    }
+  }
+}
+
+/// Loads package specifiers as external modules and everything else
+/// with the inner loader.
+pub struct PackLoader<'a> {
+  pub inner: &'a mut dyn Loader,
+}
+
+impl<'a> Loader for PackLoader<'a> {
+  fn load(
+    &mut self,
+    specifier: &ModuleSpecifier,
+    is_dynamic: bool,
+    cache_setting: CacheSetting,
+  ) -> LoadFuture {
+    if is_package_specifier(specifier) {
+      let specifier = specifier.clone();
+      Box::pin(async move { Ok(Some(LoadResponse::External { specifier })) })
+    } else {
+      self.inner.load(specifier, is_dynamic, cache_setting)
+    }
+  }
+}
@@ -0,0 +1,82 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use deno_ast::swc::ast::*;
+use deno_ast::swc::common::comments::SingleThreadedComments;
+use deno_ast::swc::common::SourceMap;
+use deno_ast::ModuleSpecifier;
+use serde::Serialize;
+
+use crate::helpers::print_program;
+
+/// Replacement of a range of a module's source text.
+#[derive(Debug, Clone, Serialize, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[serde(rename_all = "camelCase")]
+pub struct TextEdit {
+  pub specifier: ModuleSpecifier,
+  /// Byte index in the UTF-8 source text where the range starts.
+  pub start: usize,
+  /// Byte index in the UTF-8 source text where the range ends.
+  pub end: usize,
+  pub new_text: String,
+}
+
+/// Applies the edits of a single module to its source text.
+pub fn apply_text_edits(text: &str, edits: &[TextEdit]) -> String {
+  let mut edits = edits.iter().collect::<Vec<_>>();
+  edits.sort_by_key(|edit| (edit.start, edit.end));
+  let mut result = String::with_capacity(text.len());
+  let mut last_end = 0;
+  for edit in edits {
+    // skip overlapping edits
+    if edit.start < last_end {
+      continue;
+    }
+    result.push_str(&text[last_end..edit.start]);
+    result.push_str(&edit.new_text);
+    last_end = edit.end;
+  }
+  result.push_str(&text[last_end..]);
+  result
+}
+
+/// Collects the text edits for the findings that can be fixed
+/// automatically.
+#[derive(Default)]
+pub struct FixCollector {
+  edits: RefCell<Vec<TextEdit>>,
+}
+
+impl FixCollector {
+  pub fn insert(
+    &self,
+    specifier: &ModuleSpecifier,
+    index: usize,
+    text: String,
+  ) {
+    self.edits.borrow_mut().push(TextEdit {
+      specifier: specifier.clone(),
+      start: index,
+      end: index,
+      new_text: text,
+    });
+  }
+
+  pub fn into_edits(self) -> Vec<TextEdit> {
+    let mut edits = self.edits.into_inner();
+    // a module is transformed once per entry point that uses it
+    edits.sort();
+    edits.dedup();
+    edits
+  }
+}
+
+/// Prints the type as it would appear in a type annotation.
+pub fn print_type(ts_type: &TsType) -> Result<String, anyhow::Error> {
+  let printed = print_program(
+    ts_type,
+    &Rc::new(SourceMap::default()),
+    &SingleThreadedComments::default(),
+  )?;
+  Ok(printed.text)
+}
@@ -0,0 +1,219 @@
+use std::collections::HashMap;
+use std::path::Path;
+use std::path::PathBuf;
+
+use anyhow::bail;
+use anyhow::Context;
+use deno_ast::ModuleSpecifier;
+use deno_graph::source::CacheSetting;
+use deno_graph::source::LoadFuture;
+use deno_graph::source::LoadResponse;
+use deno_graph::source::LoadResult;
+use deno_graph::source::Loader;
+use serde::Deserialize;
+use sha2::Digest;
+
+use crate::helpers::is_packed_specifier;
+use crate::IncludeRemoteOptions;
+
+/// Maximum number of cached redirects to follow for a remote module.
+const MAX_REDIRECTS: usize = 10;
+
+/// Loads `file:` specifiers from the file system and `http:`/`https:`
+/// specifiers from a Deno cache directory (`DENO_DIR/deps`).
+///
+/// Remote modules are never fetched from the network, so the ones that
+/// are packed must be cached beforehand (ex. with `deno cache mod.ts`).
+/// The rest are loaded as external modules.
+#[derive(Debug, Clone)]
+pub struct FileSystemLoader {
+  deno_dir: PathBuf,
+  include_remote: Option<IncludeRemoteOptions>,
+}
+
+impl FileSystemLoader {
+  /// Creates a loader that serves remote modules from the provided
+  /// Deno cache directory.
+  pub fn new(deno_dir: impl Into<PathBuf>) -> Self {
+    Self {
+      deno_dir: deno_dir.into(),
+      include_remote: None,
+    }
+  }
+
+  /// Sets the remote modules that are packed, which should be the same
+  /// as `PackOptions::include_remote`.
+  pub fn with_include_remote(
+    mut self,
+    include_remote: Option<IncludeRemoteOptions>,
+  ) -> Self {
+    self.include_remote = include_remote;
+    self
+  }
+
+  /// Creates a loader that uses the `DENO_DIR` environment variable or
+  /// Deno's default cache directory for the current platform.
+  pub fn from_env() -> Result<Self, anyhow::Error> {
+    let deno_dir = match std::env::var_os("DENO_DIR") {
+      Some(deno_dir) => PathBuf::from(deno_dir),
+      None => default_deno_dir()
+        .context("Could not resolve the Deno cache directory.")?,
+    };
+    Ok(Self::new(deno_dir))
+  }
+
+  pub fn deno_dir(&self) -> &Path {
+    &self.deno_dir
+  }
+
+  fn load_remote(&self, specifier: &ModuleSpecifier) -> LoadResult {
+    let mut specifier = specifier.clone();
+    for _ in 0..=MAX_REDIRECTS {
+      let Some(cache_path) = self.cache_file_path(&specifier) else {
+        bail!("Unsupported remote specifier {}", specifier);
+      };
+      let Some(headers) = read_cached_headers(&cache_path)? else {
+        bail!(
+          concat!(
+            "Remote module {} was not found in the Deno cache at {}. ",
+            "Cache it first with `deno cache`."
+          ),
+          specifier,
+          self.deno_dir.display(),
+        );
+      };
+      if let Some(location) = headers.get("location") {
+        specifier = specifier.join(location).with_context(|| {
+          format!("Invalid redirect location {} for {}", location, specifier)
+        })?;
+        continue;
+      }
+      let content = std::fs::read_to_string(&cache_path)
+        .with_context(|| format!("Failed reading {}.", cache_path.display()))?;
+      return Ok(Some(LoadResponse::Module {
+        content: content.into(),
+        specifier,
+        maybe_headers: Some(headers),
+      }));
+    }
+    bail!("Too many redirects for {}", specifier)
+  }
+
+  /// Gets the path of a remote module in the cache, which is
+  /// `deps/<scheme>/<host>[_PORT<port>]/<sha256 of the path and query>`.
+  fn cache_file_path(&self, specifier: &ModuleSpecifier) -> Option<PathBuf> {
+    let host = specifier.host_str()?;
+    let host_dir = match specifier.port() {
+      Some(port) => format!("{}_PORT{}", host, port),
+      None => host.to_string(),
+    };
+    let mut path_and_query = specifier.path().to_string();
+    if let Some(query) = specifier.query() {
+      path_and_query.push('?');
+      path_and_query.push_str(query);
+    }
+    let hash = sha2::Sha256::digest(path_and_query.as_bytes());
+    let file_name = hash
+      .iter()
+      .map(|byte| format!("{:02x}", byte))
+      .collect::<String>();
+    Some(
+      self
+        .deno_dir
+        .join("deps")
+        .join(specifier.scheme())
+        .join(host_dir)
+        .join(file_name),
+    )
+  }
+}
+
+impl Loader for FileSystemLoader {
+  fn load(
+    &mut self,
+    specifier: &ModuleSpecifier,
+    _is_dynamic: bool,
+    _cache_setting: CacheSetting,
+  ) -> LoadFuture {
+    let result = match specifier.scheme() {
+      "file" => load_file(specifier),
+      "http" | "https" => {
+        if is_packed_specifier(specifier, self.include_remote.as_ref()) {
+          self.load_remote(specifier)
+        } else {
+          Ok(Some(LoadResponse::External {
+            specifier: specifier.clone(),
+          }))
+        }
+      }
+      _ => Ok(None),
+    };
+    Box::pin(async move { result })
+  }
+}
+
+fn load_file(specifier: &ModuleSpecifier) -> LoadResult {
+  let path = specifier
+    .to_file_path()
+    .map_err(|_| anyhow::anyhow!("Invalid file path {}", specifier))?;
+  match std::fs::read_to_string(&path) {
+    Ok(text) => Ok(Some(LoadResponse::Module {
+      content: text.into(),
+      specifier: specifier.clone(),
+      maybe_headers: None,
+    })),
+    Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(None),
+    Err(err) => {
+      Err(err).with_context(|| format!("Failed reading {}.", path.display()))
+    }
+  }
+}
+
+#[derive(Deserialize)]
+struct CachedMetadata {
+  headers: HashMap<String, String>,
+}
+
+/// Reads the headers from the `.metadata.json` file next to a cached
+/// remote module.
+fn read_cached_headers(
+  cache_path: &Path,
+) -> Result<Option<HashMap<String, String>>, anyhow::Error> {
+  let mut metadata_path = cache_path.as_os_str().to_owned();
+  metadata_path.push(".metadata.json");
+  let metadata_path = PathBuf::from(metadata_path);
+  let text = match std::fs::read_to_string(&metadata_path) {
+    Ok(text) => text,
+    Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+    Err(err) => {
+      return Err(err).with_context(|| {
+        format!("Failed reading {}.", metadata_path.display())
+      })
+    }
+  };
+  let metadata: CachedMetadata = serde_json::from_str(&text)
+    .with_context(|| format!("Failed parsing {}.", metadata_path.display()))?;
+  Ok(Some(
+    metadata
+      .headers
+      .into_iter()
+      .map(|(key, value)| (key.to_lowercase(), value))
+      .collect(),
+  ))
+}
+
+fn default_deno_dir() -> Option<PathBuf> {
+  if cfg!(windows) {
+    std::env::var_os("LOCALAPPDATA").map(|dir| PathBuf::from(dir).join("deno"))
+  } else if cfg!(target_os = "macos") {
+    std::env::var_os("HOME")
+      .map(|dir| PathBuf::from(dir).join("Library/Caches/deno"))
+  } else {
+    std::env::var_os("XDG_CACHE_HOME")
+      .map(PathBuf::from)
+      .or_else(|| {
+        std::env::var_os("HOME").map(|dir| PathBuf::from(dir).join(".cache"))
+      })
+      .map(|dir| dir.join("deno"))
+  }
+}
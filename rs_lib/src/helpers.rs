@@ -1,3 +1,4 @@
+use std::fmt;
 use std::rc::Rc;
 
 use deno_ast::swc::ast::*;
@@ -7,23 +8,97 @@ use deno_ast::swc::codegen::Node;
 use deno_ast::swc::common::comments::Comment;
 use deno_ast::swc::common::comments::Comments;
 use deno_ast::swc::common::comments::SingleThreadedComments;
+use deno_ast::swc::common::source_map::LineCol;
 use deno_ast::swc::common::BytePos;
+use deno_ast::swc::common::SourceFile;
 use deno_ast::swc::common::SourceMap;
 use deno_ast::swc::common::Span;
+use deno_ast::swc::common::SyntaxContext;
 use deno_ast::swc::common::DUMMY_SP;
 use deno_ast::swc::visit::VisitMut;
 use deno_ast::swc::visit::VisitMutWith;
 use deno_ast::ModuleSpecifier;
 use deno_ast::ParsedSource;
+use deno_ast::SourceMapConfig;
+use deno_ast::StartSourcePos;
+use serde::de::MapAccess;
+use serde::de::SeqAccess;
+use serde::de::Visitor;
+use serde::Deserialize;
+
+use crate::Diagnostic;
+use crate::DiagnosticCode;
+use crate::IncludeRemoteOptions;
+use crate::LineAndColumnDisplay;
+use crate::SourceMapOptions;
 
 pub fn ident(name: String) -> Ident {
   Ident {
     span: DUMMY_SP,
-    sym: name.clone().into(),
+    sym: name.into(),
     optional: false,
   }
 }
 
+/// Gets if the text can be emitted as an identifier.
+pub fn is_valid_ident(text: &str) -> bool {
+  let mut chars = text.chars();
+  match chars.next() {
+    Some(c) if c.is_alphabetic() || c == '_' || c == '$' => {}
+    _ => return false,
+  }
+  chars.all(|c| c.is_alphanumeric() || c == '_' || c == '$')
+}
+
+/// Creates a module export name, which is a string literal when
+/// the name is not a valid identifier (ex. `export { a as "a-b" }`).
+pub fn module_export_name(name: String) -> ModuleExportName {
+  if is_valid_ident(&name) {
+    ModuleExportName::Ident(ident(name))
+  } else {
+    ModuleExportName::Str(str_lit(name))
+  }
+}
+
+/// Gets the text of a module export name without quotes.
+pub fn module_export_name_text(name: &ModuleExportName) -> String {
+  match name {
+    ModuleExportName::Ident(ident) => ident.sym.to_string(),
+    ModuleExportName::Str(str) => str.value.to_string(),
+  }
+}
+
+/// Creates a property name, which is quoted when the name is
+/// not a valid identifier.
+pub fn prop_name(name: String) -> PropName {
+  if is_valid_ident(&name) {
+    PropName::Ident(ident(name))
+  } else {
+    PropName::Str(str_lit(name))
+  }
+}
+
+/// Creates a member property, which is computed when the name is
+/// not a valid identifier (ex. `pack0["a-b"]`).
+pub fn member_prop(name: String) -> MemberProp {
+  if is_valid_ident(&name) {
+    MemberProp::Ident(ident(name))
+  } else {
+    MemberProp::Computed(ComputedPropName {
+      span: DUMMY_SP,
+      expr: Box::new(Expr::Lit(Lit::Str(str_lit(name)))),
+    })
+  }
+}
+
+fn str_lit(value: String) -> Str {
+  Str {
+    span: DUMMY_SP,
+    value: value.into(),
+    raw: None,
+  }
+}
+
 pub fn ts_keyword_type(kind: TsKeywordTypeKind) -> TsType {
   TsType::TsKeywordType(TsKeywordType {
     span: DUMMY_SP,
@@ -37,7 +112,7 @@ pub fn export_x_as_y(x: String, y: String) -> ModuleItem {
     specifiers: vec![ExportSpecifier::Named(ExportNamedSpecifier {
       span: DUMMY_SP,
       orig: ident(x).into(),
-      exported: Some(ident(y).into()),
+      exported: Some(module_export_name(y)),
       is_type_only: false,
     })],
     src: None,
@@ -50,7 +125,7 @@ pub fn member_x_y(left: String, right: String) -> MemberExpr {
   MemberExpr {
     span: DUMMY_SP,
     obj: ident(left).into(),
-    prop: ident(right).into(),
+    prop: member_prop(right),
   }
 }
 
@@ -117,6 +192,22 @@ pub fn object_define_property(name: String, key: String, expr: Expr) -> Stmt {
   })
 }
 
+/// Creates a getter property (ex. `get a() { return expr; }`).
+pub fn getter_prop(key: String, expr: Expr) -> PropOrSpread {
+  PropOrSpread::Prop(Box::new(Prop::Getter(GetterProp {
+    span: DUMMY_SP,
+    key: prop_name(key),
+    type_ann: None,
+    body: Some(BlockStmt {
+      span: DUMMY_SP,
+      stmts: Vec::from([Stmt::Return(ReturnStmt {
+        span: DUMMY_SP,
+        arg: Some(Box::new(expr)),
+      })]),
+    }),
+  })))
+}
+
 pub fn module_has_default_export(module: &Module) -> bool {
   module.body.iter().any(|item| match item {
     ModuleItem::ModuleDecl(decl) => match decl {
@@ -139,11 +230,70 @@ pub fn is_remote_specifier(specifier: &ModuleSpecifier) -> bool {
   matches!(specifier.scheme(), "https" | "http")
 }
 
+/// Gets if the module should be packed into the output rather than
+/// left as an external import.
+pub fn is_packed_specifier(
+  specifier: &ModuleSpecifier,
+  include_remote: Option<&IncludeRemoteOptions>,
+) -> bool {
+  if specifier.scheme() == "file" {
+    true
+  } else if is_remote_specifier(specifier) {
+    include_remote
+      .map(|options| options.is_match(specifier))
+      .unwrap_or(false)
+  } else {
+    false
+  }
+}
+
+/// Matches a URL against a pattern where `*` matches any sequence
+/// of characters.
+pub fn url_pattern_matches(pattern: &str, url: &str) -> bool {
+  let mut parts = pattern.split('*');
+  let first = parts.next().unwrap();
+  let Some(mut remaining) = url.strip_prefix(first) else {
+    return false;
+  };
+  let mut parts = parts.collect::<Vec<_>>();
+  let Some(last) = parts.pop() else {
+    return remaining.is_empty(); // no wildcard
+  };
+  for part in parts {
+    match remaining.find(part) {
+      Some(index) => remaining = &remaining[index + part.len()..],
+      None => return false,
+    }
+  }
+  remaining.ends_with(last)
+}
+
 pub fn adjust_spans(start_pos: BytePos, module: &mut Module) {
   let mut span_adjuster = SpanAdjuster { start_pos };
   module.visit_mut_with(&mut span_adjuster);
 }
 
+/// Removes the identifiers' scope analysis from the module so that
+/// they're resolved again when transpiled along with other modules.
+pub fn remove_syntax_contexts(module: &mut Module) {
+  module.visit_mut_with(&mut SyntaxContextRemover);
+}
+
+struct SyntaxContextRemover;
+
+impl VisitMut for SyntaxContextRemover {
+  fn visit_mut_span(&mut self, span: &mut Span) {
+    span.ctxt = SyntaxContext::empty();
+  }
+}
+
+/// Converts a position in a parsed source to a position in the
+/// source file at `source_file_start_pos` in the shared source map.
+fn adjust_pos(source_file_start_pos: BytePos, pos: BytePos) -> BytePos {
+  // parsed sources start at `START_SOURCE_POS` and not zero
+  source_file_start_pos + pos - StartSourcePos::START_SOURCE_POS.as_byte_pos()
+}
+
 struct SpanAdjuster {
   start_pos: BytePos,
 }
@@ -152,17 +302,44 @@ impl VisitMut for SpanAdjuster {
   fn visit_mut_span(&mut self, span: &mut Span) {
     if !span.is_dummy() {
       // adjust the span to be within the source map
-      span.lo = self.start_pos + span.lo;
-      span.hi = self.start_pos + span.hi;
+      span.lo = adjust_pos(self.start_pos, span.lo);
+      span.hi = adjust_pos(self.start_pos, span.hi);
     }
   }
 }
 
+pub struct PrintedProgram {
+  pub text: String,
+  /// Mappings from positions in the source map to the printed text.
+  pub src_map_buf: Vec<(BytePos, LineCol)>,
+}
+
+impl PrintedProgram {
+  /// Builds a v3 source map from the printed program's mappings.
+  pub fn build_source_map(
+    &self,
+    source_map: &SourceMap,
+    options: &SourceMapOptions,
+  ) -> Result<String, anyhow::Error> {
+    let mut buf = Vec::new();
+    source_map
+      .build_source_map_with_config(
+        &self.src_map_buf,
+        None,
+        SourceMapConfig {
+          inline_sources: options.sources_content,
+        },
+      )
+      .to_writer(&mut buf)?;
+    Ok(String::from_utf8(buf)?)
+  }
+}
+
 pub fn print_program(
   program: &impl Node,
   source_map: &Rc<SourceMap>,
   comments: &SingleThreadedComments,
-) -> Result<String, anyhow::Error> {
+) -> Result<PrintedProgram, anyhow::Error> {
   let mut src_map_buf = vec![];
   let mut buf = vec![];
   {
@@ -186,7 +363,155 @@ pub fn print_program(
     };
     program.emit_with(&mut emitter)?;
   }
-  Ok(String::from_utf8(buf)?)
+  Ok(PrintedProgram {
+    text: String::from_utf8(buf)?,
+    src_map_buf,
+  })
+}
+
+/// Parses the text of a JSON module as an expression.
+///
+/// The text is parsed as JSON rather than JavaScript so that only JSON
+/// is emitted, and the object keys are kept in their original order.
+pub fn parse_json_module_expr(
+  source_file: &SourceFile,
+  specifier: &ModuleSpecifier,
+) -> Result<Expr, Box<Diagnostic>> {
+  match serde_json::from_str::<JsonExpr>(&source_file.src) {
+    Ok(JsonExpr(expr)) => Ok(expr),
+    Err(err) => {
+      // the location is already provided separately
+      let message = err.to_string();
+      let message = message
+        .strip_suffix(&format!(
+          " at line {} column {}",
+          err.line(),
+          err.column()
+        ))
+        .unwrap_or(&message);
+      Err(Box::new(Diagnostic {
+        code: DiagnosticCode::InvalidJsonModule,
+        severity: DiagnosticCode::InvalidJsonModule.default_severity(),
+        message: format!("Invalid JSON: {}.", message),
+        specifier: specifier.clone(),
+        line_and_column: Some(LineAndColumnDisplay {
+          line_number: err.line(),
+          column_number: err.column(),
+        }),
+        end_line_and_column: None,
+        related: Vec::new(),
+        hint: None,
+      }))
+    }
+  }
+}
+
+/// A JSON value deserialized as the expression of its object literal.
+struct JsonExpr(Expr);
+
+impl<'de> Deserialize<'de> for JsonExpr {
+  fn deserialize<D: serde::Deserializer<'de>>(
+    deserializer: D,
+  ) -> Result<Self, D::Error> {
+    deserializer.deserialize_any(JsonExprVisitor)
+  }
+}
+
+struct JsonExprVisitor;
+
+impl<'de> Visitor<'de> for JsonExprVisitor {
+  type Value = JsonExpr;
+
+  fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+    formatter.write_str("a JSON value")
+  }
+
+  fn visit_unit<E: serde::de::Error>(self) -> Result<JsonExpr, E> {
+    Ok(JsonExpr(Expr::Lit(Lit::Null(Null { span: DUMMY_SP }))))
+  }
+
+  fn visit_bool<E: serde::de::Error>(self, value: bool) -> Result<JsonExpr, E> {
+    Ok(JsonExpr(Expr::Lit(Lit::Bool(Bool {
+      span: DUMMY_SP,
+      value,
+    }))))
+  }
+
+  fn visit_i64<E: serde::de::Error>(self, value: i64) -> Result<JsonExpr, E> {
+    self.visit_f64(value as f64)
+  }
+
+  fn visit_u64<E: serde::de::Error>(self, value: u64) -> Result<JsonExpr, E> {
+    self.visit_f64(value as f64)
+  }
+
+  fn visit_f64<E: serde::de::Error>(self, value: f64) -> Result<JsonExpr, E> {
+    let number = Expr::Lit(Lit::Num(Number {
+      span: DUMMY_SP,
+      value: value.abs(),
+      raw: None,
+    }));
+    Ok(JsonExpr(if value.is_sign_negative() {
+      Expr::Unary(UnaryExpr {
+        span: DUMMY_SP,
+        op: UnaryOp::Minus,
+        arg: Box::new(number),
+      })
+    } else {
+      number
+    }))
+  }
+
+  fn visit_str<E: serde::de::Error>(self, value: &str) -> Result<JsonExpr, E> {
+    Ok(JsonExpr(Expr::Lit(Lit::Str(value.into()))))
+  }
+
+  fn visit_seq<A: SeqAccess<'de>>(
+    self,
+    mut seq: A,
+  ) -> Result<JsonExpr, A::Error> {
+    let mut elems = Vec::new();
+    while let Some(JsonExpr(expr)) = seq.next_element()? {
+      elems.push(Some(ExprOrSpread {
+        spread: None,
+        expr: Box::new(expr),
+      }));
+    }
+    Ok(JsonExpr(Expr::Array(ArrayLit {
+      span: DUMMY_SP,
+      elems,
+    })))
+  }
+
+  fn visit_map<A: MapAccess<'de>>(
+    self,
+    mut map: A,
+  ) -> Result<JsonExpr, A::Error> {
+    let mut props = Vec::new();
+    while let Some((key, JsonExpr(value))) =
+      map.next_entry::<String, JsonExpr>()?
+    {
+      let key = Str::from(key);
+      let key = if &*key.value == "__proto__" {
+        // a `__proto__` key in an object literal sets the prototype
+        // instead of defining a property
+        PropName::Computed(ComputedPropName {
+          span: DUMMY_SP,
+          expr: Box::new(Expr::Lit(Lit::Str(key))),
+        })
+      } else {
+        PropName::Str(key)
+      };
+      props.push(PropOrSpread::Prop(Box::new(Prop::KeyValue(KeyValueProp {
+        key,
+        value: Box::new(value),
+      }))));
+    }
+    Ok(JsonExpr(Expr::Object(ObjectLit {
+      span: DUMMY_SP,
+      props,
+    })))
+  }
 }
 
 pub fn fill_leading_comments(
@@ -196,7 +521,7 @@ pub fn fill_leading_comments(
   filter: impl Fn(&Comment) -> bool,
 ) {
   for (byte_pos, comment_vec) in parsed_source.comments().leading_map() {
-    let byte_pos = source_file_start_pos + *byte_pos;
+    let byte_pos = adjust_pos(source_file_start_pos, *byte_pos);
     for comment in comment_vec {
       if filter(comment) {
         global_comments.add_leading(
@@ -214,7 +539,7 @@ pub fn fill_trailing_comments(
   global_comments: &SingleThreadedComments,
 ) {
   for (byte_pos, comment_vec) in parsed_source.comments().trailing_map() {
-    let byte_pos = source_file_start_pos + *byte_pos;
+    let byte_pos = adjust_pos(source_file_start_pos, *byte_pos);
     for comment in comment_vec {
       global_comments.add_trailing(
         byte_pos,
@@ -231,10 +556,37 @@ fn adjusted_comment(
   Comment {
     kind: comment.kind,
     span: Span::new(
-      source_file_start_pos + comment.span.lo,
-      source_file_start_pos + comment.span.hi,
+      adjust_pos(source_file_start_pos, comment.span.lo),
+      adjust_pos(source_file_start_pos, comment.span.hi),
       comment.span.ctxt,
     ),
     text: comment.text.clone(),
   }
 }
+
+/// Measures elapsed time, which uses `Date.now()` in Wasm because
+/// `std::time::Instant` isn't supported there.
+pub struct Stopwatch {
+  #[cfg(not(target_arch = "wasm32"))]
+  start: std::time::Instant,
+  #[cfg(target_arch = "wasm32")]
+  start: f64,
+}
+
+impl Stopwatch {
+  pub fn start() -> Self {
+    Self {
+      #[cfg(not(target_arch = "wasm32"))]
+      start: std::time::Instant::now(),
+      #[cfg(target_arch = "wasm32")]
+      start: js_sys::Date::now(),
+    }
+  }
+
+  pub fn elapsed_ms(&self) -> f64 {
+    #[cfg(not(target_arch = "wasm32"))]
+    return self.start.elapsed().as_secs_f64() * 1000.0;
+    #[cfg(target_arch = "wasm32")]
+    return js_sys::Date::now() - self.start;
+  }
+}
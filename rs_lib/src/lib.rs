@@ -1,23 +1,65 @@
+use std::collections::HashMap;
+use std::collections::HashSet;
+
 use anyhow::Context;
 use deno_ast::ModuleSpecifier;
+#[cfg(target_arch = "wasm32")]
 use deno_graph::source::CacheSetting;
 use deno_graph::source::Loader;
-use deno_graph::source::ResolutionMode;
-use deno_graph::source::ResolveError;
-use deno_graph::CapturingModuleAnalyzer;
+use deno_graph::CapturingModuleParser;
+use deno_graph::DefaultModuleAnalyzer;
 use deno_graph::DefaultModuleParser;
 use deno_graph::ParsedSourceStore;
 use serde::Deserialize;
 use serde::Serialize;
 
+mod diagnostics;
 mod dts;
+mod externals;
+mod fix;
+#[cfg(not(target_arch = "wasm32"))]
+mod fs_loader;
 mod helpers;
 mod pack_js;
+mod parsed_sources;
+mod reporters;
+mod resolver;
+
+pub use diagnostics::Diagnostic;
+pub use diagnostics::DiagnosticCode;
+pub use diagnostics::DiagnosticSeverity;
+pub use diagnostics::DiagnosticSeverityConfig;
+pub use diagnostics::DiagnosticsOptions;
+pub use diagnostics::LineAndColumnDisplay;
+pub use diagnostics::RelatedDiagnostic;
+pub use diagnostics::Reporter;
+pub use fix::apply_text_edits;
+pub use fix::TextEdit;
+#[cfg(not(target_arch = "wasm32"))]
+pub use fs_loader::FileSystemLoader;
+pub use pack_js::ModuleTiming;
+pub use pack_js::PackTimings;
+pub use reporters::DiagnosticsFormat;
+pub use reporters::JsonLinesReporter;
+pub use reporters::SarifReporter;
 
 #[cfg(target_arch = "wasm32")]
 use wasm_bindgen::prelude::*;
 
+use crate::diagnostics::ConfiguredReporter;
+use crate::diagnostics::DedupReporter;
+use crate::diagnostics::DeferredReporter;
+use crate::externals::PackLoader;
+use crate::externals::PackResolver;
+use crate::fix::FixCollector;
+use crate::helpers::is_packed_specifier;
 use crate::helpers::module_has_default_export;
+use crate::helpers::url_pattern_matches;
+use crate::parsed_sources::ParsedSources;
+use crate::reporters::FormattedReport;
+use crate::reporters::TeeReporter;
+use crate::resolver::ImportMapEntryRef;
+use crate::resolver::ImportMapResolver;
 
 #[cfg(target_arch = "wasm32")]
 #[wasm_bindgen(module = "/helpers.js")]
@@ -102,48 +144,240 @@ pub async fn pack(
   }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[derive(Deserialize)]
 #[serde(rename_all = "camelCase")]
-pub struct LineAndColumnDisplay {
-  pub line_number: usize,
-  pub column_number: usize,
+pub struct PackOptions {
+  pub entry_points: Vec<String>,
+  pub import_map: Option<String>,
+  /// Emits a source map for the JavaScript output when provided.
+  pub source_map: Option<SourceMapOptions>,
+  /// Emits a declaration map for the declaration output when provided.
+  pub declaration_map: Option<SourceMapOptions>,
+  /// Packs remote modules into the output instead of importing them
+  /// when provided.
+  pub include_remote: Option<IncludeRemoteOptions>,
+  /// Outputs an import map for running tests against the output when
+  /// provided.
+  pub test_import_map: Option<TestImportMapOptions>,
+  /// Outputs an import map with only the remote mappings used by the
+  /// output instead of the whole import map when provided.
+  pub minimal_import_map: Option<MinimalImportMapOptions>,
+  /// Changes the severity of diagnostics when provided.
+  pub diagnostics: Option<DiagnosticsOptions>,
+  /// Outputs the reported diagnostics in a machine-readable format
+  /// when provided.
+  pub diagnostics_report: Option<DiagnosticsReportOptions>,
+  /// Outputs text edits that insert the missing explicit types that
+  /// can be inferred instead of reporting them.
+  #[serde(default)]
+  pub fix: bool,
+  /// How JSX in `.jsx` and `.tsx` modules is transformed. Defaults to
+  /// the `compilerOptions` of the deno.json provided as the import map.
+  pub jsx: Option<JsxOptions>,
+  /// Outputs the declarations of the packed modules in a single scope
+  /// and refers to them directly instead of through a namespace
+  /// object per module. Declarations with colliding names are renamed.
+  #[serde(default)]
+  pub scope_hoist: bool,
+  /// Outputs how long packing the JavaScript of each entry point and
+  /// each of its modules took.
+  #[serde(default)]
+  pub timings: bool,
+  /// URL of the folder the output files are written to
+  /// (ex. `file:///project/dist/`). Outputs the files to write there
+  /// for each entry point when provided.
+  pub output_folder: Option<String>,
+}
+
+/// JSX settings named the same as in a deno.json's `compilerOptions`.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct JsxOptions {
+  #[serde(default)]
+  pub jsx: JsxTransform,
+  /// Function called to create elements with the classic `react`
+  /// transform (ex. `h`). Defaults to `React.createElement`.
+  pub jsx_factory: Option<String>,
+  /// Defaults to `React.Fragment`.
+  pub jsx_fragment_factory: Option<String>,
+  /// Module the automatic transforms import the JSX runtime from
+  /// (ex. `npm:preact` imports `npm:preact/jsx-runtime`). Defaults
+  /// to `react`.
+  pub jsx_import_source: Option<String>,
 }
 
-impl From<deno_ast::LineAndColumnDisplay> for LineAndColumnDisplay {
-  fn from(value: deno_ast::LineAndColumnDisplay) -> Self {
-    Self {
-      line_number: value.line_number,
-      column_number: value.column_number,
+impl JsxOptions {
+  /// Gets the module the JSX runtime is imported from relative to,
+  /// which is only used by the automatic transforms.
+  pub fn automatic_import_source(&self) -> Option<&str> {
+    match self.jsx {
+      JsxTransform::React => None,
+      JsxTransform::ReactJsx
+      | JsxTransform::ReactJsxDev
+      | JsxTransform::Precompile => {
+        Some(self.jsx_import_source.as_deref().unwrap_or("react"))
+      }
     }
   }
+
+  /// Gets the name of the module imported from the import source.
+  pub fn import_source_module(&self) -> &'static str {
+    match self.jsx {
+      JsxTransform::ReactJsxDev => "jsx-dev-runtime",
+      JsxTransform::React
+      | JsxTransform::ReactJsx
+      | JsxTransform::Precompile => "jsx-runtime",
+    }
+  }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+pub enum JsxTransform {
+  /// Calls the factory (ex. `React.createElement`) for each element.
+  #[default]
+  #[serde(rename = "react")]
+  React,
+  /// Imports the element functions from `<import source>/jsx-runtime`.
+  #[serde(rename = "react-jsx")]
+  ReactJsx,
+  /// Same as `react-jsx`, but with the development runtime.
+  #[serde(rename = "react-jsxdev")]
+  ReactJsxDev,
+  /// Serializes the static parts of elements to strings.
+  #[serde(rename = "precompile")]
+  Precompile,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[derive(Debug, Clone, Deserialize)]
 #[serde(rename_all = "camelCase")]
-pub struct Diagnostic {
-  pub message: String,
-  pub specifier: ModuleSpecifier,
-  pub line_and_column: Option<LineAndColumnDisplay>,
+pub struct DiagnosticsReportOptions {
+  pub format: DiagnosticsFormat,
+  /// URL the SARIF locations are made relative to
+  /// (ex. `file:///project/`).
+  pub base_url: Option<String>,
 }
 
-pub trait Reporter {
-  fn diagnostic(&self, diagnostic: Diagnostic);
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MinimalImportMapOptions {
+  /// Test files to resolve with the import map in order to report
+  /// the entries that are only used by tests.
+  #[serde(default)]
+  pub test_files: Vec<String>,
 }
 
-#[derive(Deserialize)]
+#[derive(Debug, Clone, Deserialize)]
 #[serde(rename_all = "camelCase")]
-pub struct PackOptions {
-  pub entry_points: Vec<String>,
-  pub import_map: Option<String>,
+pub struct TestImportMapOptions {
+  /// URL of the folder the output files are written to
+  /// (ex. `file:///project/dist/`).
+  pub output_folder: String,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IncludeRemoteOptions {
+  /// URL patterns of the remote modules to pack. All remote modules
+  /// are packed when empty. A `*` matches any sequence of characters
+  /// (ex. `https://deno.land/std@0.200.0/*`).
+  #[serde(default)]
+  pub allow: Vec<String>,
+  /// URL patterns of the remote modules to never pack.
+  #[serde(default)]
+  pub deny: Vec<String>,
+}
+
+impl IncludeRemoteOptions {
+  pub fn is_match(&self, specifier: &ModuleSpecifier) -> bool {
+    let url = specifier.as_str();
+    (self.allow.is_empty()
+      || self.allow.iter().any(|p| url_pattern_matches(p, url)))
+      && !self.deny.iter().any(|p| url_pattern_matches(p, url))
+  }
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SourceMapOptions {
+  /// Include the original source text in the `sourcesContent` field.
+  #[serde(default)]
+  pub sources_content: bool,
+  /// Append a `//# sourceMappingURL` comment to the output that points
+  /// to a `.map` file next to the output file (ex. `mod.js.map` or
+  /// `mod.d.ts.map`).
+  #[serde(default)]
+  pub url_comment: bool,
 }
 
 #[derive(Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct PackOutput {
+  /// Output for each entry point in the order they were provided.
+  pub entry_points: Vec<EntryPointOutput>,
+  /// The provided import map or, when `PackOptions::minimal_import_map`
+  /// was provided, the import map with only the remote mappings used
+  /// by the output.
+  pub import_map: Option<String>,
+  /// Import map entries not used by the output when
+  /// `PackOptions::minimal_import_map` was provided.
+  pub unused_import_map_entries: Vec<UnusedImportMapEntry>,
+  /// Import map that redirects each entry point to its `.ts` file in
+  /// the output folder when `PackOptions::test_import_map` was provided.
+  ///
+  /// This contains every entry of the provided import map with its
+  /// addresses and scopes resolved to absolute URLs, so it can be used
+  /// from any location (ex. a `data:` URL).
+  pub test_import_map: Option<String>,
+  /// The reported diagnostics in the format of
+  /// `PackOptions::diagnostics_report` when provided.
+  pub diagnostics_report: Option<String>,
+  /// Edits to the source files that insert the inferred types when
+  /// `PackOptions::fix` is true.
+  pub fixes: Vec<TextEdit>,
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct UnusedImportMapEntry {
+  /// The import map or config file the entry is in.
+  pub specifier: ModuleSpecifier,
+  /// Key of the scope the entry is in or `None` for the top level
+  /// `imports`.
+  pub scope: Option<String>,
+  pub key: String,
+  /// Whether the entry is used by the test files.
+  pub used_by_tests: bool,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EntryPointOutput {
+  pub specifier: ModuleSpecifier,
   pub js: String,
+  /// Source map for `js` when `PackOptions::source_map` was provided.
+  pub js_source_map: Option<String>,
   pub dts: String,
-  pub import_map: Option<String>,
+  /// Declaration map for `dts` when `PackOptions::declaration_map`
+  /// was provided.
+  pub declaration_map: Option<String>,
   pub has_default_export: bool,
+  /// How long packing the JavaScript took when `PackOptions::timings`
+  /// is true.
+  pub timings: Option<PackTimings>,
+  /// The files to write to `PackOptions::output_folder` when provided.
+  ///
+  /// These are the JavaScript file that references its declaration
+  /// file, a `.ts` file that re-exports the JavaScript with its types,
+  /// the declaration file, and their maps.
+  pub files: Vec<OutputFile>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OutputFile {
+  /// URL of the file relative to the output folder (ex. `mod.d.ts`).
+  pub relative_url: String,
+  pub text: String,
 }
 
 pub async fn rs_pack(
@@ -153,124 +387,403 @@ pub async fn rs_pack(
 ) -> Result<PackOutput, anyhow::Error> {
   let mut graph = deno_graph::ModuleGraph::new(deno_graph::GraphKind::All);
   let entry_points = parse_module_specifiers(&options.entry_points)?;
+  ensure_unique_output_file_stems(&entry_points)?;
   let source_parser = DefaultModuleParser::new_for_analysis();
-  let capturing_analyzer =
-    CapturingModuleAnalyzer::new(Some(Box::new(source_parser)), None);
+  let parsed_sources = ParsedSources::default();
+  let parser =
+    CapturingModuleParser::new(Some(&source_parser), &parsed_sources);
+  let analyzer = DefaultModuleAnalyzer::new(&parser);
   let maybe_import_map = match &options.import_map {
     Some(import_map_url) => Some(
-      ImportMapResolver::load(
-        &ModuleSpecifier::parse(&import_map_url)?,
-        loader,
-      )
-      .await
-      .context("Error loading import map.")?,
+      ImportMapResolver::load(&ModuleSpecifier::parse(import_map_url)?, loader)
+        .await
+        .context("Error loading import map.")?,
     ),
     None => None,
   };
+  let jsx = match &options.jsx {
+    Some(jsx) => jsx.clone(),
+    None => maybe_import_map
+      .as_ref()
+      .and_then(|resolver| resolver.jsx.clone())
+      .unwrap_or_default(),
+  };
+  let resolver = PackResolver {
+    import_map: maybe_import_map.as_ref(),
+    jsx: &jsx,
+  };
   graph
     .build(
-      entry_points,
-      loader,
+      entry_points.clone(),
+      &mut PackLoader { inner: loader },
       deno_graph::BuildOptions {
         is_dynamic: false,
         imports: vec![],
-        resolver: maybe_import_map.as_ref().map(|r| r.as_resolver()),
-        module_analyzer: Some(&capturing_analyzer),
+        resolver: Some(&resolver),
+        module_analyzer: Some(&analyzer),
         reporter: None,
         npm_resolver: None,
         workspace_members: Vec::new(),
       },
     )
     .await;
-  graph.valid()?;
-  let parser = capturing_analyzer.as_capturing_parser();
-  let js = pack_js::pack(
-    &graph,
-    &parser,
-    pack_js::PackOptions {
-      include_remote: false,
+  if let Err(err) = graph.valid() {
+    return Err(match &maybe_import_map {
+      Some(resolver) => resolver.add_graph_error_context(err),
+      None => err.into(),
+    });
+  }
+  let (import_map, unused_import_map_entries) = match &maybe_import_map {
+    Some(resolver) => match &options.minimal_import_map {
+      Some(minimal_options) => {
+        let (import_map, unused_entries) = build_minimal_import_map(
+          resolver,
+          &graph,
+          options.include_remote.as_ref(),
+          &jsx,
+          minimal_options,
+          loader,
+        )
+        .await?;
+        (Some(import_map), unused_entries)
+      }
+      None => (Some(resolver.import_map.to_json()), Vec::new()),
     },
-  )?;
-  let dts = dts::pack_dts(&graph, &parser, reporter)?;
+    None => (None, Vec::new()),
+  };
+  let diagnostics_report = match &options.diagnostics_report {
+    Some(report_options) => Some(FormattedReport::new(
+      report_options.format,
+      match &report_options.base_url {
+        Some(base_url) => Some(ModuleSpecifier::parse(base_url)?),
+        None => None,
+      },
+    )),
+    None => None,
+  };
+  let reporter = TeeReporter {
+    inner: reporter,
+    report: diagnostics_report.as_ref(),
+  };
+  let reporter =
+    ConfiguredReporter::new(&reporter, &graph, options.diagnostics.as_ref())?;
+  let reporter = DedupReporter::new(&reporter);
+  let test_import_map = match &options.test_import_map {
+    Some(test_import_map_options) => Some(build_test_import_map(
+      maybe_import_map.as_ref().map(|r| &r.import_map),
+      &entry_points,
+      test_import_map_options,
+    )?),
+    None => None,
+  };
+  let output_folder = match &options.output_folder {
+    Some(output_folder) => Some(parse_folder_url(output_folder)?),
+    None => None,
+  };
+  let fixes = options.fix.then(FixCollector::default);
+  let entry_points_len = entry_points.len();
+  let mut entry_point_outputs = Vec::with_capacity(entry_points_len);
+  let dts_reporter = DeferredReporter::new(&reporter);
+  for (index, entry_point) in entry_points.into_iter().enumerate() {
+    let has_default_export =
+      root_has_default_export(&graph, &parsed_sources, &entry_point);
+    let dts_output = dts::pack_dts(
+      &graph,
+      &parser,
+      &entry_point,
+      dts::PackDtsOptions {
+        include_remote: options.include_remote.clone(),
+        declaration_map: options.declaration_map.clone(),
+        fixes: fixes.as_ref(),
+      },
+      &dts_reporter,
+    )?;
+    let mut dts = dts_output.text;
+    if let Some(declaration_map_options) = &options.declaration_map {
+      if declaration_map_options.url_comment {
+        append_source_map_url_comment(
+          &mut dts,
+          &format!("{}.d.ts.map", output_file_stem(&entry_point)),
+        );
+      }
+    }
+    let js_output = pack_js::pack(
+      &graph,
+      &parsed_sources,
+      &entry_point,
+      pack_js::PackOptions {
+        include_remote: options.include_remote.clone(),
+        source_map: options.source_map.clone(),
+        jsx: &jsx,
+        // the js is the last output that needs the modules
+        move_asts: index == entry_points_len - 1,
+        scope_hoist: options.scope_hoist,
+      },
+      &reporter,
+    )?;
+    dts_reporter.flush();
+    let mut js = js_output.text;
+    if let Some(source_map_options) = &options.source_map {
+      if source_map_options.url_comment {
+        append_source_map_url_comment(
+          &mut js,
+          &format!("{}.js.map", output_file_stem(&entry_point)),
+        );
+      }
+    }
+    let mut entry_point_output = EntryPointOutput {
+      specifier: entry_point,
+      js,
+      js_source_map: js_output.source_map,
+      dts,
+      declaration_map: dts_output.declaration_map,
+      has_default_export,
+      timings: options.timings.then_some(js_output.timings),
+      files: Vec::new(),
+    };
+    if let Some(output_folder) = &output_folder {
+      entry_point_output.files =
+        get_output_files(&entry_point_output, output_folder)?;
+    }
+    entry_point_outputs.push(entry_point_output);
+  }
 
   Ok(PackOutput {
-    js,
-    dts,
-    import_map: maybe_import_map.map(|r| r.0.to_json()),
-    has_default_export: root_has_default_export(&graph, &capturing_analyzer),
+    entry_points: entry_point_outputs,
+    import_map,
+    unused_import_map_entries,
+    test_import_map,
+    diagnostics_report: diagnostics_report.map(|r| r.into_string()),
+    fixes: fixes.map(|f| f.into_edits()).unwrap_or_default(),
   })
 }
 
+async fn build_minimal_import_map(
+  resolver: &ImportMapResolver,
+  graph: &deno_graph::ModuleGraph,
+  include_remote: Option<&IncludeRemoteOptions>,
+  jsx: &JsxOptions,
+  options: &MinimalImportMapOptions,
+  loader: &mut dyn Loader,
+) -> Result<(String, Vec<UnusedImportMapEntry>), anyhow::Error> {
+  // only the resolutions of modules in the output are necessary
+  let output_entries = resolver
+    .take_resolutions()
+    .into_iter()
+    .filter(|resolution| {
+      graph.get(&resolution.referrer).is_some()
+        && is_packed_specifier(&resolution.referrer, include_remote)
+    })
+    .map(|resolution| resolution.entry)
+    .collect::<HashSet<_>>();
+  let test_entries = if options.test_files.is_empty() {
+    HashSet::new()
+  } else {
+    let mut test_graph =
+      deno_graph::ModuleGraph::new(deno_graph::GraphKind::All);
+    test_graph
+      .build(
+        parse_module_specifiers(&options.test_files)?,
+        &mut PackLoader { inner: loader },
+        deno_graph::BuildOptions {
+          resolver: Some(&PackResolver {
+            import_map: Some(resolver),
+            jsx,
+          }),
+          ..Default::default()
+        },
+      )
+      .await;
+    resolver
+      .take_resolutions()
+      .into_iter()
+      .map(|resolution| resolution.entry)
+      .collect::<HashSet<ImportMapEntryRef>>()
+  };
+  let unused_entries = resolver
+    .entries()
+    .into_iter()
+    .filter(|entry| !output_entries.contains(entry))
+    .map(|entry| UnusedImportMapEntry {
+      specifier: resolver.entry_source(&entry).clone(),
+      used_by_tests: test_entries.contains(&entry),
+      scope: entry.scope,
+      key: entry.key,
+    })
+    .collect();
+  Ok((resolver.to_minimal_json(&output_entries), unused_entries))
+}
+
+fn build_test_import_map(
+  maybe_import_map: Option<&import_map::ImportMap>,
+  entry_points: &[ModuleSpecifier],
+  options: &TestImportMapOptions,
+) -> Result<String, anyhow::Error> {
+  let output_folder = parse_folder_url(&options.output_folder)
+    .context("Invalid test import map output folder.")?;
+  let mut import_map = match maybe_import_map {
+    Some(import_map) => import_map.clone(),
+    None => import_map::ImportMap::new(output_folder.clone()),
+  };
+  for entry_point in entry_points {
+    let shim_url =
+      output_folder.join(&format!("{}.ts", output_file_stem(entry_point)))?;
+    import_map
+      .imports_mut()
+      .append(entry_point.to_string(), shim_url.to_string())
+      .map_err(|err| anyhow::anyhow!("{}", err))
+      .context("Failed adding entry point to test import map.")?;
+  }
+  // serializing outputs the resolved keys and addresses rather than
+  // the text found in the file
+  Ok(serde_json::to_string_pretty(&import_map)?)
+}
+
 fn root_has_default_export(
   graph: &deno_graph::ModuleGraph,
-  analyzer: &CapturingModuleAnalyzer,
+  parsed_sources: &ParsedSources,
+  root: &ModuleSpecifier,
 ) -> bool {
-  assert_eq!(graph.roots.len(), 1);
-  let root = &graph.roots[0];
-  let parsed_source = analyzer.get_parsed_source(root).unwrap();
+  let root = graph.resolve(root);
+  let parsed_source = parsed_sources.get_parsed_source(&root).unwrap();
   module_has_default_export(parsed_source.module())
 }
 
-fn parse_module_specifiers(
-  values: &[String],
-) -> Result<Vec<ModuleSpecifier>, anyhow::Error> {
-  let mut specifiers = Vec::new();
-  for value in values {
-    let entry_point = ModuleSpecifier::parse(&value)?;
-    specifiers.push(entry_point);
+/// Gets the file name without the extension that the output files
+/// of an entry point are named after (ex. `mod` for `mod.ts`).
+fn output_file_stem(specifier: &ModuleSpecifier) -> &str {
+  let file_name = specifier
+    .path_segments()
+    .and_then(|mut segments| segments.next_back())
+    .unwrap_or("");
+  match file_name.rfind('.') {
+    Some(index) if index > 0 => &file_name[..index],
+    _ => file_name,
   }
-  Ok(specifiers)
 }
 
-#[derive(Debug)]
-struct ImportMapResolver(import_map::ImportMap);
-
-impl ImportMapResolver {
-  pub async fn load(
-    import_map_url: &ModuleSpecifier,
-    loader: &mut dyn Loader,
-  ) -> anyhow::Result<Self> {
-    let response = loader
-      .load(import_map_url, false, CacheSetting::Use)
-      .await?
-      .ok_or_else(|| anyhow::anyhow!("Could not find {}", import_map_url))?;
-    match response {
-      deno_graph::source::LoadResponse::External { specifier } => {
-        anyhow::bail!("Did not expect external import map {}", specifier)
-      }
-      deno_graph::source::LoadResponse::Module {
-        content, specifier, ..
-      } => {
-        let value = jsonc_parser::parse_to_serde_value(
-          &content,
-          &jsonc_parser::ParseOptions {
-            allow_comments: true,
-            allow_loose_object_property_names: true,
-            allow_trailing_commas: true,
-          },
-        )?
-        .unwrap_or_else(|| serde_json::Value::Object(Default::default()));
-        let result = import_map::parse_from_value(&specifier, value)?;
-        Ok(ImportMapResolver(result.import_map))
+/// Errors when the output files of two entry points would have the same
+/// names (ex. `a/mod.ts` and `b/mod.ts`), which would overwrite each other.
+fn ensure_unique_output_file_stems(
+  entry_points: &[ModuleSpecifier],
+) -> Result<(), anyhow::Error> {
+  let mut stems = HashMap::with_capacity(entry_points.len());
+  for entry_point in entry_points {
+    let stem = output_file_stem(entry_point);
+    if let Some(other) = stems.insert(stem, entry_point) {
+      anyhow::bail!(
+        "Entry points {} and {} would both output files named '{}'. Rename one of them.",
+        other,
+        entry_point,
+        stem,
+      );
+    }
+  }
+  Ok(())
+}
+
+/// Parses the URL of a folder, which may be missing its trailing slash.
+fn parse_folder_url(url: &str) -> Result<ModuleSpecifier, anyhow::Error> {
+  let mut url = url.to_string();
+  if !url.ends_with('/') {
+    url.push('/');
+  }
+  Ok(ModuleSpecifier::parse(&url)?)
+}
+
+/// Gets the files to write to the output folder for an entry point.
+fn get_output_files(
+  output: &EntryPointOutput,
+  output_folder: &ModuleSpecifier,
+) -> Result<Vec<OutputFile>, anyhow::Error> {
+  let stem = output_file_stem(&output.specifier);
+  let mut files = Vec::with_capacity(5);
+  files.push(OutputFile {
+    relative_url: format!("{stem}.js"),
+    text: format!("/// <reference types=\"./{stem}.d.ts\" />\n{}", output.js),
+  });
+  if let Some(source_map) = &output.js_source_map {
+    files.push(OutputFile {
+      relative_url: format!("{stem}.js.map"),
+      // account for the types reference comment on the first line
+      text: relative_source_map(source_map, output_folder, 1)?,
+    });
+  }
+  let mut ts_text = format!(
+    "// @deno-types=\"./{0}.d.ts\"\nexport * from \"./{0}.js\";\n",
+    stem
+  );
+  if output.has_default_export {
+    ts_text.push_str(&format!(
+      concat!(
+        "// @deno-types=\"./{0}.d.ts\"\n",
+        "import defaultExport from \"./{0}.js\";\n",
+        "export default defaultExport;",
+      ),
+      stem
+    ));
+  }
+  files.push(OutputFile {
+    relative_url: format!("{stem}.ts"),
+    text: ts_text,
+  });
+  files.push(OutputFile {
+    relative_url: format!("{stem}.d.ts"),
+    text: output.dts.clone(),
+  });
+  if let Some(declaration_map) = &output.declaration_map {
+    files.push(OutputFile {
+      relative_url: format!("{stem}.d.ts.map"),
+      text: relative_source_map(declaration_map, output_folder, 0)?,
+    });
+  }
+  Ok(files)
+}
+
+/// Shifts the mappings of a source map down by the provided number of
+/// lines and makes its local sources relative to the output folder so
+/// that they resolve in editors.
+fn relative_source_map(
+  text: &str,
+  output_folder: &ModuleSpecifier,
+  line_offset: usize,
+) -> Result<String, anyhow::Error> {
+  let mut source_map: serde_json::Value = serde_json::from_str(text)?;
+  if let Some(mappings) = source_map["mappings"].as_str() {
+    source_map["mappings"] =
+      format!("{}{}", ";".repeat(line_offset), mappings).into();
+  }
+  if let Some(sources) = source_map["sources"].as_array_mut() {
+    for source in sources {
+      let maybe_relative_url = source
+        .as_str()
+        .filter(|s| s.starts_with("file:"))
+        .and_then(|s| ModuleSpecifier::parse(s).ok())
+        .and_then(|specifier| output_folder.make_relative(&specifier));
+      if let Some(relative_url) = maybe_relative_url {
+        *source = relative_url.into();
       }
     }
   }
+  Ok(serde_json::to_string(&source_map)?)
+}
 
-  pub fn as_resolver(&self) -> &dyn deno_graph::source::Resolver {
-    self
+fn append_source_map_url_comment(text: &mut String, url: &str) {
+  if !text.ends_with('\n') {
+    text.push('\n');
   }
+  text.push_str("//# sourceMappingURL=");
+  text.push_str(url);
+  text.push('\n');
 }
 
-impl deno_graph::source::Resolver for ImportMapResolver {
-  fn resolve(
-    &self,
-    specifier: &str,
-    referrer: &ModuleSpecifier,
-    _mode: ResolutionMode,
-  ) -> Result<ModuleSpecifier, ResolveError> {
-    self
-      .0
-      .resolve(specifier, referrer)
-      .map_err(|err| ResolveError::Other(err.into()))
+fn parse_module_specifiers(
+  values: &[String],
+) -> Result<Vec<ModuleSpecifier>, anyhow::Error> {
+  let mut specifiers = Vec::new();
+  for value in values {
+    let entry_point = ModuleSpecifier::parse(value)?;
+    specifiers.push(entry_point);
   }
+  Ok(specifiers)
 }
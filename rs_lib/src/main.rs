@@ -0,0 +1,474 @@
+use std::cell::Cell;
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+use std::io::Write;
+use std::path::Component;
+use std::path::Path;
+use std::path::PathBuf;
+
+use anyhow::bail;
+use anyhow::Context;
+use anyhow::Result;
+use deno_ast::ModuleSpecifier;
+use rs_lib::apply_text_edits;
+use rs_lib::rs_pack;
+use rs_lib::Diagnostic;
+use rs_lib::DiagnosticSeverity;
+use rs_lib::DiagnosticSeverityConfig;
+use rs_lib::DiagnosticsFormat;
+use rs_lib::DiagnosticsOptions;
+use rs_lib::DiagnosticsReportOptions;
+use rs_lib::FileSystemLoader;
+use rs_lib::IncludeRemoteOptions;
+use rs_lib::MinimalImportMapOptions;
+use rs_lib::ModuleTiming;
+use rs_lib::PackOptions;
+use rs_lib::PackOutput;
+use rs_lib::PackTimings;
+use rs_lib::Reporter;
+use rs_lib::SourceMapOptions;
+use rs_lib::TextEdit;
+
+const USAGE: &str = "Usage:
+  libpack build <entry-point> [...entry-points] --output-folder=<folder>
+  libpack check <entry-point> [...entry-points]
+  libpack fix <entry-point> [...entry-points]
+
+Options:
+  --output-folder=<folder>  Folder to write the output to (build only).
+  --no-deno-json            Don't use the deno.json or deno.jsonc next to
+                            the entry point as the import map.
+  --source-map              Output a source map next to each JS file.
+  --declaration-map         Output a declaration map next to each
+                            declaration file.
+  --include-remote          Pack remote modules into the output.
+  --include-remote-allow=<pattern>
+                            Only pack the remote modules matching the
+                            pattern. Can be provided multiple times.
+  --include-remote-deny=<pattern>
+                            Never pack the remote modules matching the
+                            pattern. Can be provided multiple times.
+  --minimal-import-map      Output an import_map.json with only the remote
+                            mappings used by the output and warn about
+                            the unused entries.
+  --scope-hoist             Output the modules' declarations in a single
+                            scope instead of a function per module.
+  --diagnostic-severity=<code>=<severity>
+                            Change the severity of a diagnostic code
+                            (ex. LP1001) to error, warning, info, or off.
+                            Can be provided multiple times.
+  --diagnostics-format=<format>
+                            Output the diagnostics to stdout as json-lines
+                            or sarif instead of as text to stderr.
+                            Defaults to text.
+  --timings                 Print how long packing the JS of each entry
+                            point took and its slowest modules to stderr.
+
+The fix command inserts the missing types that can be inferred (ex. literals,
+`as` assertions, `new X()` constructions, and simple returns) into the source
+files and reports the rest.
+
+Diagnostics can be suppressed with a `// libpack-ignore <code>` comment on
+the line before.
+
+Packed remote modules are loaded from the Deno cache (DENO_DIR), so they must
+be cached beforehand with `deno cache`. The rest are left external.";
+
+fn main() {
+  if let Err(err) = run(std::env::args().skip(1)) {
+    eprintln!("error: {:#}", err);
+    std::process::exit(1);
+  }
+}
+
+fn run(args: impl Iterator<Item = String>) -> Result<()> {
+  let args = parse_args(args)?;
+  match args.positional.first().map(|s| s.as_str()) {
+    Some("build") => build_command(&args),
+    Some("check") => check_command(&args),
+    Some("fix") => fix_command(&args),
+    _ => bail!(
+      "Unexpected command. Expected 'build', 'check', or 'fix'.\n\n{USAGE}"
+    ),
+  }
+}
+
+#[derive(Default)]
+struct CliArgs {
+  positional: Vec<String>,
+  output_folder: Option<String>,
+  no_deno_json: bool,
+  source_map: bool,
+  declaration_map: bool,
+  include_remote: bool,
+  include_remote_allow: Vec<String>,
+  include_remote_deny: Vec<String>,
+  minimal_import_map: bool,
+  scope_hoist: bool,
+  diagnostic_severities: HashMap<String, DiagnosticSeverityConfig>,
+  diagnostics_format: Option<DiagnosticsFormat>,
+  timings: bool,
+}
+
+impl CliArgs {
+  pub fn include_remote_options(&self) -> Option<IncludeRemoteOptions> {
+    if !self.include_remote_allow.is_empty()
+      || !self.include_remote_deny.is_empty()
+    {
+      Some(IncludeRemoteOptions {
+        allow: self.include_remote_allow.clone(),
+        deny: self.include_remote_deny.clone(),
+      })
+    } else if self.include_remote {
+      Some(IncludeRemoteOptions::default())
+    } else {
+      None
+    }
+  }
+}
+
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<CliArgs> {
+  let mut cli_args = CliArgs::default();
+  while let Some(arg) = args.next() {
+    let Some(flag) = arg.strip_prefix("--") else {
+      cli_args.positional.push(arg);
+      continue;
+    };
+    let (name, value) = match flag.split_once('=') {
+      Some((name, value)) => (name, Some(value.to_string())),
+      None => (flag, None),
+    };
+    let mut take_value = || match value.clone() {
+      Some(value) => Ok(value),
+      None => args
+        .next()
+        .with_context(|| format!("Expected a value for --{}.", name)),
+    };
+    match name {
+      "output-folder" => cli_args.output_folder = Some(take_value()?),
+      "no-deno-json" => cli_args.no_deno_json = true,
+      "source-map" => cli_args.source_map = true,
+      "declaration-map" => cli_args.declaration_map = true,
+      "include-remote" => cli_args.include_remote = true,
+      "include-remote-allow" => {
+        cli_args.include_remote_allow.push(take_value()?)
+      }
+      "include-remote-deny" => cli_args.include_remote_deny.push(take_value()?),
+      "minimal-import-map" => cli_args.minimal_import_map = true,
+      "scope-hoist" => cli_args.scope_hoist = true,
+      "timings" => cli_args.timings = true,
+      "diagnostic-severity" => {
+        let value = take_value()?;
+        let Some((code, severity)) = value.rsplit_once('=') else {
+          bail!("Expected --diagnostic-severity=<code>=<severity>.");
+        };
+        let Some(severity) = DiagnosticSeverityConfig::parse(severity) else {
+          bail!(
+            "Unknown severity '{}'. Expected error, warning, info, or off.",
+            severity
+          );
+        };
+        cli_args
+          .diagnostic_severities
+          .insert(code.to_string(), severity);
+      }
+      "diagnostics-format" => {
+        cli_args.diagnostics_format = match take_value()?.as_str() {
+          "text" => None,
+          "json-lines" => Some(DiagnosticsFormat::JsonLines),
+          "sarif" => Some(DiagnosticsFormat::Sarif),
+          format => bail!(
+            concat!(
+              "Unknown diagnostics format '{}'. ",
+              "Expected text, json-lines, or sarif."
+            ),
+            format
+          ),
+        };
+      }
+      _ => bail!("Unknown flag --{}.\n\n{USAGE}", name),
+    }
+  }
+  Ok(cli_args)
+}
+
+fn build_command(args: &CliArgs) -> Result<()> {
+  let Some(output_folder) = &args.output_folder else {
+    bail!("Expected --output-folder to be set.");
+  };
+  let output_folder = resolve_path(Path::new(output_folder))?;
+  let output_folder_url = ModuleSpecifier::from_directory_path(&output_folder)
+    .map_err(|_| anyhow::anyhow!("Invalid path {}", output_folder.display()))?;
+  let (output, reporter) =
+    pack_entry_points(args, Some(&output_folder_url), false)?;
+  std::fs::create_dir_all(&output_folder)
+    .with_context(|| format!("Failed creating {}.", output_folder.display()))?;
+  for file in output.entry_points.iter().flat_map(|output| &output.files) {
+    let path = output_folder_url
+      .join(&file.relative_url)?
+      .to_file_path()
+      .map_err(|_| {
+        anyhow::anyhow!("Invalid file path {}", file.relative_url)
+      })?;
+    write_file(&path, &file.text)?;
+  }
+  if args.minimal_import_map {
+    if let Some(import_map) = &output.import_map {
+      write_file(&output_folder.join("import_map.json"), import_map)?;
+    }
+  }
+  reporter.result()
+}
+
+fn check_command(args: &CliArgs) -> Result<()> {
+  let (_, reporter) = pack_entry_points(args, None, false)?;
+  reporter.result()
+}
+
+fn fix_command(args: &CliArgs) -> Result<()> {
+  let (output, reporter) = pack_entry_points(args, None, true)?;
+  let mut edits_by_specifier: BTreeMap<&ModuleSpecifier, Vec<TextEdit>> =
+    BTreeMap::new();
+  for edit in &output.fixes {
+    edits_by_specifier
+      .entry(&edit.specifier)
+      .or_default()
+      .push(edit.clone());
+  }
+  for (specifier, edits) in &edits_by_specifier {
+    let path = specifier
+      .to_file_path()
+      .map_err(|_| anyhow::anyhow!("Invalid file path {}", specifier))?;
+    let text = std::fs::read_to_string(&path)
+      .with_context(|| format!("Failed reading {}.", path.display()))?;
+    write_file(&path, &apply_text_edits(&text, edits))?;
+  }
+  if !output.fixes.is_empty() {
+    let count = output.fixes.len();
+    let file_count = edits_by_specifier.len();
+    eprintln!(
+      "Fixed {} issue{} in {} file{}.",
+      count,
+      if count != 1 { "s" } else { "" },
+      file_count,
+      if file_count != 1 { "s" } else { "" },
+    );
+  }
+  reporter.result()
+}
+
+fn pack_entry_points(
+  args: &CliArgs,
+  output_folder: Option<&ModuleSpecifier>,
+  fix: bool,
+) -> Result<(PackOutput, ConsoleReporter)> {
+  let entry_points = args.positional[1..]
+    .iter()
+    .map(|arg| resolve_path(Path::new(arg)))
+    .collect::<Result<Vec<_>>>()?;
+  let Some(entry_point) = entry_points.first() else {
+    bail!(
+      concat!(
+        "Expected an entry point path to be specified as the first ",
+        "argument to the `{}` command."
+      ),
+      args.positional[0]
+    );
+  };
+  let import_map = if args.no_deno_json {
+    None
+  } else {
+    let dir = entry_point.parent().unwrap();
+    let Some(deno_json) = ["deno.json", "deno.jsonc"]
+      .into_iter()
+      .map(|file_name| dir.join(file_name))
+      .find(|path| path.exists())
+    else {
+      bail!(
+        "Expected a deno.json file at {}. Run with --no-deno-json to skip.",
+        dir.join("deno.json").display()
+      );
+    };
+    Some(path_to_specifier(&deno_json)?.to_string())
+  };
+  let options = PackOptions {
+    entry_points: entry_points
+      .iter()
+      .map(|path| path_to_specifier(path).map(|s| s.to_string()))
+      .collect::<Result<Vec<_>>>()?,
+    import_map,
+    source_map: args.source_map.then_some(SourceMapOptions {
+      sources_content: true,
+      url_comment: true,
+    }),
+    declaration_map: args.declaration_map.then_some(SourceMapOptions {
+      sources_content: false,
+      url_comment: true,
+    }),
+    include_remote: args.include_remote_options(),
+    test_import_map: None,
+    minimal_import_map: if args.minimal_import_map {
+      Some(MinimalImportMapOptions {
+        test_files: get_test_files(&entry_points)?,
+      })
+    } else {
+      None
+    },
+    diagnostics: if args.diagnostic_severities.is_empty() {
+      None
+    } else {
+      Some(DiagnosticsOptions {
+        severities: args.diagnostic_severities.clone(),
+      })
+    },
+    diagnostics_report: match args.diagnostics_format {
+      Some(format) => Some(DiagnosticsReportOptions {
+        format,
+        base_url: Some(
+          ModuleSpecifier::from_directory_path(std::env::current_dir()?)
+            .map_err(|_| anyhow::anyhow!("Invalid current directory."))?
+            .to_string(),
+        ),
+      }),
+      None => None,
+    },
+    fix,
+    // read from the deno.json
+    jsx: None,
+    scope_hoist: args.scope_hoist,
+    timings: args.timings,
+    output_folder: output_folder.map(|url| url.to_string()),
+  };
+  let reporter = ConsoleReporter {
+    error_count: Default::default(),
+    print: args.diagnostics_format.is_none(),
+  };
+  let output = futures::executor::block_on(rs_pack(
+    &options,
+    &mut FileSystemLoader::from_env()?
+      .with_include_remote(args.include_remote_options()),
+    &reporter,
+  ))?;
+  for entry in &output.unused_import_map_entries {
+    let scope_text = match &entry.scope {
+      Some(scope) => format!(" in scope \"{}\"", scope),
+      None => String::new(),
+    };
+    eprintln!(
+      "WARNING: Import map entry \"{}\"{} is {}. -- {}",
+      entry.key,
+      scope_text,
+      if entry.used_by_tests {
+        "only used by tests"
+      } else {
+        "unused"
+      },
+      entry.specifier,
+    );
+  }
+  for entry_point_output in &output.entry_points {
+    if let Some(timings) = &entry_point_output.timings {
+      print_timings(&entry_point_output.specifier, timings);
+    }
+  }
+  if let Some(report) = &output.diagnostics_report {
+    let mut stdout = std::io::stdout().lock();
+    stdout.write_all(report.as_bytes())?;
+    if !report.is_empty() && !report.ends_with('\n') {
+      stdout.write_all(b"\n")?;
+    }
+  }
+  Ok((output, reporter))
+}
+
+fn print_timings(specifier: &ModuleSpecifier, timings: &PackTimings) {
+  const MAX_MODULES: usize = 10;
+
+  let module_ms = |timing: &ModuleTiming| timing.analyze_ms + timing.emit_ms;
+  let mut modules = timings.modules.iter().collect::<Vec<_>>();
+  modules.sort_by(|a, b| module_ms(b).total_cmp(&module_ms(a)));
+  eprintln!("Timings for {}", specifier);
+  eprintln!(
+    "  {:>9.2}ms modules ({})",
+    modules.iter().map(|timing| module_ms(timing)).sum::<f64>(),
+    modules.len()
+  );
+  eprintln!("  {:>9.2}ms transpile", timings.transpile_ms);
+  eprintln!("  {:>9.2}ms print", timings.print_ms);
+  eprintln!("Slowest modules:");
+  for timing in modules.into_iter().take(MAX_MODULES) {
+    eprintln!("  {:>9.2}ms {}", module_ms(timing), timing.specifier);
+  }
+}
+
+/// Gets the `x.test.ts` files of the entry points that exist.
+fn get_test_files(entry_points: &[PathBuf]) -> Result<Vec<String>> {
+  let mut test_files = Vec::new();
+  for entry_point in entry_points {
+    let stem = entry_point.file_stem().unwrap().to_string_lossy();
+    let test_file = entry_point.with_file_name(format!("{}.test.ts", stem));
+    if test_file.exists() {
+      test_files.push(path_to_specifier(&test_file)?.to_string());
+    }
+  }
+  Ok(test_files)
+}
+
+struct ConsoleReporter {
+  error_count: Cell<usize>,
+  /// Whether to print the diagnostics to stderr, which is done
+  /// when they're not output in a machine-readable format.
+  print: bool,
+}
+
+impl ConsoleReporter {
+  pub fn result(&self) -> Result<()> {
+    let count = self.error_count.get();
+    if count > 0 {
+      bail!(
+        "Failed. Had {} error{}.",
+        count,
+        if count != 1 { "s" } else { "" }
+      );
+    }
+    Ok(())
+  }
+}
+
+impl Reporter for ConsoleReporter {
+  fn diagnostic(&self, diagnostic: Diagnostic) {
+    if diagnostic.severity == DiagnosticSeverity::Error {
+      self.error_count.set(self.error_count.get() + 1);
+    }
+    if self.print {
+      eprintln!("{}", diagnostic);
+    }
+  }
+}
+
+fn write_file(path: &Path, text: &str) -> Result<()> {
+  std::fs::write(path, text)
+    .with_context(|| format!("Failed writing {}.", path.display()))
+}
+
+fn path_to_specifier(path: &Path) -> Result<ModuleSpecifier> {
+  ModuleSpecifier::from_file_path(path)
+    .map_err(|_| anyhow::anyhow!("Invalid path {}", path.display()))
+}
+
+/// Resolves the path against the current working directory and
+/// removes any `.` and `..` components.
+fn resolve_path(path: &Path) -> Result<PathBuf> {
+  let path = std::env::current_dir()?.join(path);
+  let mut result = PathBuf::new();
+  for component in path.components() {
+    match component {
+      Component::CurDir => {}
+      Component::ParentDir => {
+        result.pop();
+      }
+      _ => result.push(component),
+    }
+  }
+  Ok(result)
+}
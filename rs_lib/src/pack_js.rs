@@ -3,34 +3,64 @@ use std::collections::HashSet;
 use std::collections::VecDeque;
 use std::rc::Rc;
 
+use anyhow::bail;
 use deno_ast::swc::ast::Id;
 use deno_ast::swc::ast::*;
+use deno_ast::swc::common::comments::Comment;
 use deno_ast::swc::common::comments::CommentKind;
+use deno_ast::swc::common::comments::Comments;
 use deno_ast::swc::common::comments::SingleThreadedComments;
 use deno_ast::swc::common::util::take::Take;
+use deno_ast::swc::common::BytePos;
 use deno_ast::swc::common::FileName;
 use deno_ast::swc::common::Mark;
 use deno_ast::swc::common::SourceMap;
+use deno_ast::swc::common::Span;
+use deno_ast::swc::common::Spanned;
+use deno_ast::swc::common::SyntaxContext;
 use deno_ast::swc::common::DUMMY_SP;
 use deno_ast::swc::visit::*;
-use deno_ast::Diagnostic;
 use deno_ast::EmitOptions;
+use deno_ast::MediaType;
 use deno_ast::ModuleSpecifier;
-use deno_graph::CapturingModuleParser;
+use deno_ast::SourceRange;
+use deno_ast::SourceRangedForSpanned;
 use deno_graph::EsmModule;
+use deno_graph::JsonModule;
 use deno_graph::ModuleGraph;
-use deno_graph::ModuleParser;
+use deno_graph::ParsedSourceStore;
 use deno_graph::WalkOptions;
+use serde::Serialize;
 
+use crate::externals::external_specifier_text;
+use crate::externals::is_opaque_external;
 use crate::helpers::adjust_spans;
 use crate::helpers::const_var_decl;
 use crate::helpers::export_x_as_y;
 use crate::helpers::fill_leading_comments;
 use crate::helpers::fill_trailing_comments;
+use crate::helpers::getter_prop;
 use crate::helpers::ident;
+use crate::helpers::is_packed_specifier;
+use crate::helpers::is_valid_ident;
+use crate::helpers::member_prop;
 use crate::helpers::member_x_y;
+use crate::helpers::module_export_name_text;
 use crate::helpers::object_define_property;
+use crate::helpers::parse_json_module_expr;
 use crate::helpers::print_program;
+use crate::helpers::prop_name;
+use crate::helpers::remove_syntax_contexts;
+use crate::helpers::Stopwatch;
+use crate::parsed_sources::into_module;
+use crate::parsed_sources::ParsedSources;
+use crate::Diagnostic;
+use crate::DiagnosticCode;
+use crate::IncludeRemoteOptions;
+use crate::JsxOptions;
+use crate::JsxTransform;
+use crate::Reporter;
+use crate::SourceMapOptions;
 
 #[derive(Default)]
 struct ModuleDataCollection {
@@ -52,8 +82,15 @@ impl ModuleDataCollection {
         id: ModuleId(next_id),
         module: None,
         has_tla: false,
+        is_dynamically_imported: false,
+        dynamic_imports: Default::default(),
+        imports: Default::default(),
         exports: Default::default(),
         re_exports: Default::default(),
+        jsx_runtime: None,
+        jsx_factories: None,
+        names: None,
+        analyze_ms: 0.0,
       })
   }
 
@@ -65,7 +102,7 @@ impl ModuleDataCollection {
       result: &mut HashSet<&'a String>,
     ) {
       if seen.insert(specifier) {
-        if let Some(module_data) = collection.module_data.get(&specifier) {
+        if let Some(module_data) = collection.module_data.get(specifier) {
           result.extend(module_data.exports.iter().map(|e| e.export_name()));
           for re_export in &module_data.re_exports {
             match &re_export.name {
@@ -93,15 +130,59 @@ impl ModuleDataCollection {
     result.sort_unstable();
     result
   }
+
+  /// Gets the packages the module re-exports everything from directly
+  /// or through other modules. Their export names aren't known, so
+  /// they're re-exported with `export * from "..."` instead.
+  pub fn get_package_star_re_exports(
+    &self,
+    graph: &ModuleGraph,
+    specifier: &ModuleSpecifier,
+  ) -> Vec<ModuleSpecifier> {
+    fn inner<'a>(
+      collection: &'a ModuleDataCollection,
+      graph: &ModuleGraph,
+      specifier: &'a ModuleSpecifier,
+      seen: &mut HashSet<&'a ModuleSpecifier>,
+      result: &mut Vec<&'a ModuleSpecifier>,
+    ) {
+      if !seen.insert(specifier) {
+        return;
+      }
+      if is_opaque_external(graph, specifier) {
+        result.push(specifier);
+      } else if let Some(module_data) = collection.module_data.get(specifier) {
+        for re_export in &module_data.re_exports {
+          if matches!(re_export.name, ReExportName::All) {
+            inner(collection, graph, &re_export.specifier, seen, result);
+          }
+        }
+      }
+    }
+
+    let mut result = Vec::new();
+    inner(self, graph, specifier, &mut HashSet::new(), &mut result);
+    result.into_iter().cloned().collect()
+  }
 }
 
 #[derive(Debug, Clone, Copy)]
 struct ModuleId(usize);
 
 impl ModuleId {
-  pub fn to_code_string(&self) -> String {
+  pub fn to_code_string(self) -> String {
     format!("pack{}", self.0)
   }
+
+  /// The promise of an async or lazily evaluated module's evaluation.
+  pub fn to_evaluation_code_string(self) -> String {
+    format!("pack{}Eval", self.0)
+  }
+
+  /// The function that starts a lazily evaluated module's evaluation.
+  pub fn to_load_code_string(self) -> String {
+    format!("pack{}Load", self.0)
+  }
 }
 
 struct ExportName {
@@ -126,14 +207,49 @@ struct ReExport {
   name: ReExportName,
   specifier: ModuleSpecifier,
   module_id: ModuleId,
+  /// For an exported import, the number of the module's exports before
+  /// it so that it's output in the order of the exports.
+  export_index: Option<usize>,
+}
+
+/// A binding imported from a local or external module.
+struct ImportedName {
+  specifier: ModuleSpecifier,
+  /// The imported export or `None` for the namespace.
+  name: Option<String>,
+}
+
+/// The names a module uses, which are collected when scope hoisting.
+struct ModuleNames {
+  top_level_context: SyntaxContext,
+  /// Names of the top level declarations in the order they appear.
+  top_level: Vec<String>,
+  /// Names declared or referenced other than at the top level, which
+  /// includes the globals the module uses.
+  nested: HashSet<String>,
+  /// Imports that are referenced outside of types.
+  used_imports: HashSet<Id>,
 }
 
 struct ModuleData {
   id: ModuleId,
   has_tla: bool,
+  /// If the module is the target of a dynamic import, in which case
+  /// its namespace object is always created.
+  is_dynamically_imported: bool,
+  /// Local modules imported by this module via a dynamic import
+  /// keyed by the import's specifier text.
+  dynamic_imports: HashMap<String, ModuleSpecifier>,
+  imports: HashMap<Id, ImportedName>,
   exports: Vec<ExportName>,
   re_exports: Vec<ReExport>,
+  /// The module the automatic JSX transforms import the runtime from.
+  jsx_runtime: Option<ModuleSpecifier>,
+  /// The classic JSX factories of a module with JSX.
+  jsx_factories: Option<JsxFactories>,
+  names: Option<ModuleNames>,
   module: Option<Module>,
+  analyze_ms: f64,
 }
 
 impl ModuleData {
@@ -145,42 +261,131 @@ impl ModuleData {
   }
 }
 
-struct Context<'a> {
+/// A module with JSX, which is located in the transpiled output by the
+/// position of its source file in the source map.
+struct JsxModule {
+  start_pos: BytePos,
+  end_pos: BytePos,
+  /// The classic JSX factories with the imports they refer to replaced.
+  factory: Vec<String>,
+  fragment_factory: Vec<String>,
+  /// The module the automatic transforms import the runtime from.
+  runtime: Option<ModuleSpecifier>,
+  /// Declares the runtime functions it uses at its start like the
+  /// imports of the transforms, which isn't possible for the modules at
+  /// the top level when scope hoisting.
+  declares_runtime: bool,
+}
+
+struct Context<'a, TReporter: Reporter> {
   graph: &'a ModuleGraph,
-  parser: &'a CapturingModuleParser<'a>,
+  root: &'a ModuleSpecifier,
+  parsed_sources: &'a ParsedSources,
+  move_asts: bool,
+  scope_hoist: bool,
+  reporter: &'a TReporter,
+  include_remote: Option<&'a IncludeRemoteOptions>,
+  jsx: &'a JsxOptions,
+  emit_options: &'a EmitOptions,
+  source_map: &'a SourceMap,
+  comments: &'a SingleThreadedComments,
+  /// Parse diagnostics of the packed modules, which fail the transpile
+  /// when fatal.
+  parse_diagnostics: Vec<deno_ast::Diagnostic>,
+  jsx_modules: Vec<JsxModule>,
   module_data: ModuleDataCollection,
 }
 
-pub struct PackOptions {
-  /// If the packing should include remote modules or leave
-  /// them as external.
-  pub include_remote: bool,
+impl<'a, TReporter: Reporter> Context<'a, TReporter> {
+  pub fn is_packed(&self, specifier: &ModuleSpecifier) -> bool {
+    is_packed_specifier(specifier, self.include_remote)
+  }
+}
+
+pub struct PackOptions<'a> {
+  /// Remote modules to pack into the output. Remote modules are left
+  /// as external imports when not provided.
+  pub include_remote: Option<IncludeRemoteOptions>,
+  /// Builds a source map for the output when provided.
+  pub source_map: Option<SourceMapOptions>,
+  pub jsx: &'a JsxOptions,
+  /// Moves the parsed modules out of the parsed sources instead of
+  /// cloning them, which is done when nothing needs them afterwards.
+  pub move_asts: bool,
+  /// Outputs the declarations of the modules in the top level scope
+  /// instead of wrapping each module in a function.
+  pub scope_hoist: bool,
+}
+
+pub struct PackOutput {
+  pub text: String,
+  pub source_map: Option<String>,
+  pub timings: PackTimings,
+}
+
+/// How long packing the JavaScript output took in milliseconds.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PackTimings {
+  /// Time spent on each packed module in the order they are evaluated.
+  pub modules: Vec<ModuleTiming>,
+  /// Time spent transpiling all the modules, which is done in a single
+  /// pass over the output.
+  pub transpile_ms: f64,
+  pub print_ms: f64,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ModuleTiming {
+  pub specifier: ModuleSpecifier,
+  /// Time spent analyzing the module.
+  pub analyze_ms: f64,
+  /// Time spent replacing the module's imports and adding it to the
+  /// output before transpiling.
+  pub emit_ms: f64,
 }
 
+/// Packs the modules reachable from the provided root into a single
+/// JavaScript file.
+///
+/// Modules shared between multiple roots are duplicated in the output
+/// of each root that uses them.
 pub fn pack(
   graph: &ModuleGraph,
-  parser: &CapturingModuleParser,
-  options: PackOptions,
-) -> Result<String, anyhow::Error> {
+  parsed_sources: &ParsedSources,
+  root: &ModuleSpecifier,
+  options: PackOptions<'_>,
+  reporter: &impl Reporter,
+) -> Result<PackOutput, anyhow::Error> {
   // TODO
-  // - dynamic imports
-  // - tla
-  // - order modules properly (https://v8.dev/features/top-level-await#module-execution-order)
   // - keep remote the same
 
-  let roots = &graph.roots;
-  assert_eq!(roots.len(), 1);
+  let root = graph.resolve(root);
+  let roots = std::slice::from_ref(&root);
+  let emit_options = get_emit_options(options.jsx);
+  let global_comments = SingleThreadedComments::default();
+  let source_map = Rc::new(SourceMap::default());
   let mut context = Context {
     graph,
-    parser,
+    root: &root,
+    parsed_sources,
+    move_asts: options.move_asts,
+    scope_hoist: options.scope_hoist,
+    reporter,
+    include_remote: options.include_remote.as_ref(),
+    jsx: options.jsx,
+    emit_options: &emit_options,
+    source_map: &source_map,
+    comments: &global_comments,
+    parse_diagnostics: Vec::new(),
+    jsx_modules: Vec::new(),
     module_data: ModuleDataCollection::default(),
   };
 
-  // todo: this is not correct. It should output by walking the graph
-  // in the order that the loader does
-  let mut remote_specifiers: Vec<(&ModuleSpecifier, &deno_graph::Module)> =
+  let mut external_specifiers: Vec<(&ModuleSpecifier, &deno_graph::Module)> =
     Default::default();
-  let mut local_specifiers: Vec<(&ModuleSpecifier, &deno_graph::Module)> =
+  let mut packed_specifiers: Vec<(&ModuleSpecifier, &deno_graph::Module)> =
     Default::default();
 
   let mut modules = graph.walk(
@@ -191,45 +396,76 @@ pub fn pack(
       follow_type_only: true,
     },
   );
+  let mut seen_specifiers = HashSet::new();
   while let Some((specifier, _)) = modules.next() {
-    let is_file = specifier.scheme() == "file";
-    if !options.include_remote && !is_file {
-      // don't analyze any dependenices of remote modules
+    // a dynamically imported module that failed to load, which is
+    // reported where it's imported
+    let Some(module) = graph.get(specifier) else {
+      continue;
+    };
+    let specifier = module.specifier();
+    let is_packed = context.is_packed(specifier);
+    if !is_packed {
+      // don't analyze any dependenices of external modules
       modules.skip_previous_dependencies();
     }
-    let module = graph.get(specifier).unwrap();
-    let specifier = module.specifier();
-    if is_file {
-      local_specifiers.push((specifier, module));
+    // a redirected specifier is walked in addition to the module it
+    // redirects to
+    if !seen_specifiers.insert(specifier) {
+      continue;
+    }
+    if is_packed {
+      packed_specifiers.push((specifier, module));
     } else {
-      remote_specifiers.push((specifier, module));
+      external_specifiers.push((specifier, module));
     }
     match module {
       deno_graph::Module::Esm(esm) => {
-        if options.include_remote || is_file {
+        if is_packed {
           analyze_esm_module(esm, &mut context)?;
         }
       }
-      deno_graph::Module::Json(_) => {}
-      _ => {
-        todo!("json modules");
-      }
+      deno_graph::Module::Json(_)
+      | deno_graph::Module::Npm(_)
+      | deno_graph::Module::Node(_)
+      | deno_graph::Module::External(_) => {}
     }
   }
 
-  let root_dir = get_root_dir(local_specifiers.iter().map(|(s, _)| *s));
-  let global_comments = SingleThreadedComments::default();
-  let source_map = Rc::new(SourceMap::default());
+  // output the modules in the order they would be evaluated
+  // (https://v8.dev/features/top-level-await#module-execution-order)
+  let evaluation_order =
+    get_evaluation_order(graph, &root, options.include_remote.as_ref());
+  let async_modules =
+    get_async_modules(graph, &evaluation_order.modules, &context.module_data);
+  let hoisted = if context.scope_hoist {
+    // lazily evaluated modules stay in a function
+    let eager_order = evaluation_order
+      .modules
+      .iter()
+      .filter(|module| !evaluation_order.lazy.contains(module.specifier()))
+      .copied()
+      .collect::<Vec<_>>();
+    HoistedModules::new(
+      &context.module_data,
+      &root,
+      &eager_order,
+      &async_modules,
+    )
+  } else {
+    HoistedModules::default()
+  };
+
+  let root_dir = get_root_dir(packed_specifiers.iter().map(|(s, _)| *s));
   let mut final_module = Module {
     span: DUMMY_SP,
     body: vec![],
     shebang: None,
   };
-  let mut final_text = String::new();
   for (specifier, module) in
-    remote_specifiers.iter().chain(local_specifiers.iter())
+    external_specifiers.iter().chain(packed_specifiers.iter())
   {
-    if specifier.scheme() != "file" {
+    if !context.is_packed(specifier) {
       let module_data = context.module_data.get_mut(specifier);
       final_module
         .body
@@ -243,335 +479,749 @@ pub fn pack(
           )]),
           src: Box::new(Str {
             span: DUMMY_SP,
-            value: specifier.to_string().into(),
+            value: external_specifier_text(specifier).into(),
             raw: None,
           }),
           type_only: false,
           with: None,
         })));
-    } else {
-      if let deno_graph::Module::Esm(_) = module {
-        let export_names = context.module_data.get_export_names(specifier);
-        let module_data = context.module_data.get_mut(specifier);
-        if export_names.is_empty() || context.graph.roots[0] == **specifier {
-          continue;
-        }
-        final_module
-          .body
-          .push(ModuleItem::Stmt(Stmt::Decl(Decl::Var(Box::new(
-            const_var_decl(
-              module_data.id.to_code_string(),
-              Expr::Object(ObjectLit {
-                span: DUMMY_SP,
-                props: export_names
-                  .into_iter()
-                  .map(|name| {
-                    PropOrSpread::Prop(Box::new(Prop::KeyValue(KeyValueProp {
-                      key: ident(name).into(),
-                      value: ident("undefined".to_string()).into(),
-                    })))
-                  })
-                  .collect(),
-              }),
-            ),
-          )))));
-      } else if let deno_graph::Module::Json(json) = module {
-        let module_data = context.module_data.get_mut(specifier);
-        let displayed_specifier = match root_dir {
-          Some(prefix) => {
-            if specifier.scheme() == "file" {
-              let specifier = specifier.as_str();
-              specifier.strip_prefix(prefix).unwrap_or(specifier)
-            } else {
-              specifier.as_str()
-            }
-          }
-          None => specifier.as_str(),
-        };
-        // todo: use swc here too
-        final_text.push_str(&format!(
-          "// {}\nconst {} = {{\n  default: {}\n}};\n",
-          displayed_specifier,
-          module_data.id.to_code_string(),
-          json.source.trim()
-        ));
-      }
+    } else if let deno_graph::Module::Esm(_) = module {
+      final_module.body.extend(namespace_decl_items(
+        &context.module_data,
+        graph,
+        &hoisted,
+        context.root,
+        specifier,
+      ));
     }
   }
 
+  let mut evaluated_modules =
+    HashSet::with_capacity(evaluation_order.modules.len());
+  let mut module_timings = Vec::with_capacity(evaluation_order.modules.len());
+  let mut transpile_ms = 0.0;
+  let mut module_fn_decl_names = HashMap::new();
   let globals = deno_ast::swc::common::Globals::new();
   deno_ast::swc::common::GLOBALS.set(&globals, || {
-    for (specifier, module) in remote_specifiers
-      .iter()
-      .rev()
-      .chain(local_specifiers.iter().rev())
-    {
-      if !options.include_remote && specifier.scheme() != "file" {
-        continue;
-      }
+    for module in evaluation_order.modules.iter().copied() {
+      let specifier = module.specifier();
+      // async dependencies that need to finish evaluating before this
+      // module, excluding the ones that haven't been evaluated due to
+      // a cycle and the lazily evaluated ones, which are evaluated
+      // before this module when imported
+      let awaited_evaluations = get_module_deps(graph, module, false)
+        .iter()
+        .filter(|dep| {
+          evaluated_modules.contains(*dep)
+            && async_modules.contains(*dep)
+            && !evaluation_order.lazy.contains(*dep)
+        })
+        .map(|dep| {
+          let dep_id = context.module_data.get(dep).unwrap().id;
+          Expr::Ident(ident(dep_id.to_evaluation_code_string()))
+        })
+        .collect::<Vec<_>>();
+      let is_lazy = evaluation_order.lazy.contains(specifier);
+      let is_async = is_lazy || async_modules.contains(specifier);
+      evaluated_modules.insert(specifier);
 
-      if let deno_graph::Module::Esm(esm) = module {
-        let source = &esm.source;
-        // eprintln!("PACKING: {}", specifier);
-        let module = {
-          let parsed_source = context.parser.parse_module(
-            &esm.specifier,
-            esm.source.clone(),
-            esm.media_type,
-          )?;
-          // todo: do a single transpile for everything
-          let module_data = context.module_data.get_mut(specifier);
-          let mut module = module_data.module.take().unwrap();
-          let top_level_mark = Mark::fresh(Mark::root());
-          let source_file = source_map.new_source_file(
-            FileName::Url(esm.specifier.clone()),
-            source.to_string(),
-          );
-          adjust_spans(source_file.start_pos, &mut module);
-          fill_leading_comments(
-            source_file.start_pos,
-            &parsed_source,
-            &global_comments,
-            // remove any jsdoc comments from the js output as they will
-            // appear in the dts output
-            |c| c.kind != CommentKind::Block || !c.text.starts_with("*"),
-          );
-          fill_trailing_comments(
-            source_file.start_pos,
-            &parsed_source,
-            &global_comments,
-          );
-          let program = deno_ast::fold_program(
-            Program::Module(module),
-            &EmitOptions::default(),
-            source_map.clone(),
-            &global_comments,
-            top_level_mark,
-            parsed_source.diagnostics(),
-          )?;
-          match program {
-            Program::Module(module) => module,
-            Program::Script(_) => unreachable!(),
-          }
-        };
+      if let deno_graph::Module::Esm(_) = module {
+        let stopwatch = Stopwatch::start();
+        let module_data = context.module_data.get_mut(specifier);
+        let mut module = module_data.module.take().unwrap();
+        transform_module(
+          &mut module,
+          specifier,
+          &mut context,
+          &hoisted,
+          &evaluation_order,
+          &async_modules,
+        );
         let module_data = context.module_data.get(specifier).unwrap();
         if !module.body.is_empty()
           || !module_data.exports.is_empty()
           || !module_data.re_exports.is_empty()
+          || is_async
         {
-          let displayed_specifier = match root_dir {
-            Some(prefix) => {
-              if specifier.scheme() == "file" {
-                let specifier = specifier.as_str();
-                specifier.strip_prefix(prefix).unwrap_or(specifier)
-              } else {
-                specifier.as_str()
-              }
-            }
-            None => specifier.as_str(),
-          };
+          let displayed_specifier =
+            get_displayed_specifier(root_dir, specifier);
           let specifier_id = displayed_specifier
             .chars()
             .map(|c| if c.is_alphanumeric() { c } else { '_' })
             .collect::<String>();
-          if *specifier == &roots[0] {
+          if specifier == context.root {
+            if !awaited_evaluations.is_empty() {
+              final_module
+                .body
+                .push(await_module_evaluations(awaited_evaluations).into());
+            }
             final_module.body.extend(module.body);
-
-            // re-exports
-            let mut export_names = HashSet::with_capacity(
-              module_data.exports.len() + module_data.re_exports.len(),
+            final_module.body.extend(root_export_items(
+              &context.module_data,
+              graph,
+              &hoisted,
+              specifier,
+              context.scope_hoist,
+            ));
+          } else if hoisted.is_hoisted(specifier) {
+            label_hoisted_module(
+              &module,
+              &global_comments,
+              displayed_specifier,
             );
-            for export in &module_data.exports {
-              export_names.insert(export.export_name());
-            }
-            // todo: lots of code duplication below
-            let mut re_export_index = 0;
-            for re_export in &module_data.re_exports {
-              match &re_export.name {
-                ReExportName::Named(name) => {
-                  re_export_index += 1;
-                  let temp_name = format!("_packReExport{}", re_export_index);
-                  final_module.body.push(ModuleItem::Stmt(Stmt::Decl(
-                    Decl::Var(Box::new(const_var_decl(
-                      temp_name.clone(),
-                      Expr::Member(MemberExpr {
-                        span: DUMMY_SP,
-                        obj: ident(re_export.module_id.to_code_string()).into(),
-                        prop: ident(name.local_name.clone()).into(),
-                      }),
-                    ))),
-                  )));
-                  final_module.body.push(export_x_as_y(
-                    temp_name,
-                    name.export_name().to_string(),
-                  ));
-                  export_names.insert(name.export_name());
-                }
-                ReExportName::Namespace(name) => {
-                  re_export_index += 1;
-                  let temp_name = format!("_packReExport{}", re_export_index);
-                  final_module.body.push(
-                    const_var_decl(
-                      temp_name.clone(),
-                      Expr::Ident(ident(re_export.module_id.to_code_string())),
-                    )
-                    .into(),
-                  );
-                  final_module
-                    .body
-                    .push(export_x_as_y(temp_name, name.to_string()));
-                  export_names.insert(name);
-                }
-                ReExportName::All => {
-                  // handle these when all done
-                }
-              }
-            }
-            for re_export in &module_data.re_exports {
-              if matches!(re_export.name, ReExportName::All) {
-                let re_export_names =
-                  context.module_data.get_export_names(&re_export.specifier);
-                for name in &re_export_names {
-                  if !export_names.contains(&name) {
-                    re_export_index += 1;
-                    let temp_name = format!("_packReExport{}", re_export_index);
-                    final_module.body.push(
-                      const_var_decl(
-                        temp_name.clone(),
-                        Expr::Member(member_x_y(
-                          re_export.module_id.to_code_string(),
-                          name.to_string(),
-                        )),
-                      )
-                      .into(),
-                    );
-                    final_module
-                      .body
-                      .push(export_x_as_y(temp_name, name.to_string()));
-                  }
-                }
-              }
-            }
+            final_module.body.extend(module.body);
           } else {
-            let mut stmts = module
-              .body
-              .into_iter()
-              .map(|item| match item {
-                ModuleItem::ModuleDecl(_) => unreachable!(),
-                ModuleItem::Stmt(stmt) => stmt,
-              })
-              .collect::<Vec<_>>();
-            let code_string = module_data.id.to_code_string();
-            let mut export_names = HashSet::with_capacity(
-              module_data.exports.len() + module_data.re_exports.len(),
+            let stmts = module_fn_stmts(
+              &context.module_data,
+              &hoisted,
+              module_data,
+              module.body,
+              awaited_evaluations,
             );
-            for export in &module_data.exports {
-              stmts.push(object_define_property(
-                code_string.clone(),
-                export.export_name().to_string(),
-                ident(export.local_name.clone()).into(),
-              ));
-              export_names.insert(export.export_name());
-            }
-            for re_export in &module_data.re_exports {
-              match &re_export.name {
-                ReExportName::Named(name) => {
-                  stmts.push(object_define_property(
-                    code_string.clone(),
-                    name.export_name().to_string(),
-                    member_x_y(
-                      re_export.module_id.to_code_string(),
-                      name.local_name.to_string(),
-                    )
-                    .into(),
-                  ));
-                  export_names.insert(name.export_name());
-                }
-                ReExportName::Namespace(name) => {
-                  stmts.push(object_define_property(
-                    code_string.clone(),
-                    name.to_string(),
-                    ident(re_export.module_id.to_code_string()).into(),
-                  ));
-                  export_names.insert(name);
-                }
-                ReExportName::All => {
-                  // handle these when all done
-                }
-              }
-            }
-            for re_export in &module_data.re_exports {
-              if matches!(re_export.name, ReExportName::All) {
-                let re_export_names =
-                  context.module_data.get_export_names(&re_export.specifier);
-                for name in &re_export_names {
-                  if !export_names.contains(&name) {
-                    stmts.push(object_define_property(
-                      code_string.clone(),
-                      name.clone(),
-                      member_x_y(
-                        re_export.module_id.to_code_string(),
-                        name.clone(),
-                      )
-                      .into(),
-                    ));
-                  }
-                }
-              }
-            }
-            let fn_expr = FnExpr {
-              ident: Some(ident(specifier_id)),
-              function: Box::new(Function {
-                params: Vec::new(),
-                decorators: Vec::new(),
-                span: DUMMY_SP,
-                body: Some(BlockStmt {
-                  span: DUMMY_SP,
-                  stmts,
-                }),
-                is_generator: false,
-                is_async: module_data.has_tla,
-                type_params: None,
-                return_type: None,
-              }),
-            };
-            let iife = Expr::Call(CallExpr {
-              span: DUMMY_SP,
-              callee: Callee::Expr(Box::new(Expr::Paren(ParenExpr {
-                span: DUMMY_SP,
-                expr: Box::new(Expr::Fn(fn_expr)),
-              }))),
-              args: vec![],
-              type_args: None,
-            });
-            let expr = if module_data.has_tla {
-              Expr::Await(AwaitExpr {
-                span: DUMMY_SP,
-                arg: Box::new(iife),
-              })
-            } else {
-              iife
-            };
-            final_module
-              .body
-              .push(ModuleItem::Stmt(Stmt::Expr(ExprStmt {
-                span: DUMMY_SP,
-                expr: expr.into(),
-              })));
+            module_fn_decl_names.insert(
+              specifier_id.clone(),
+              stmts
+                .iter()
+                .filter_map(|stmt| match stmt {
+                  Stmt::Decl(decl) => enum_or_namespace_name(decl),
+                  _ => None,
+                })
+                .collect(),
+            );
+            final_module.body.extend(module_fn_items(
+              module_data.id,
+              specifier_id,
+              stmts,
+              is_lazy,
+              is_async,
+            ));
           }
         }
+        module_timings.push(ModuleTiming {
+          specifier: specifier.clone(),
+          analyze_ms: module_data.analyze_ms,
+          emit_ms: stopwatch.elapsed_ms(),
+        });
+      } else if let deno_graph::Module::Json(json) = module {
+        final_module.body.push(json_module_item(
+          json,
+          &context,
+          get_displayed_specifier(root_dir, specifier),
+        ));
       }
     }
+
+    let stopwatch = Stopwatch::start();
+    final_module = transpile_output(
+      final_module.take(),
+      external_specifiers.len(),
+      &mut context,
+      &source_map,
+      &hoisted,
+      &module_fn_decl_names,
+    )?;
+    transpile_ms = stopwatch.elapsed_ms();
     Result::<(), anyhow::Error>::Ok(())
   })?;
 
-  final_text.push_str(&print_program(
-    &final_module,
-    &source_map,
-    &global_comments,
-  )?);
+  let stopwatch = Stopwatch::start();
+  let printed = print_program(&final_module, &source_map, &global_comments)?;
+  let maybe_source_map = match &options.source_map {
+    Some(source_map_options) => {
+      Some(printed.build_source_map(&source_map, source_map_options)?)
+    }
+    None => None,
+  };
+  let print_ms = stopwatch.elapsed_ms();
+
+  Ok(PackOutput {
+    text: printed.text,
+    source_map: maybe_source_map,
+    timings: PackTimings {
+      modules: module_timings,
+      transpile_ms,
+      print_ms,
+    },
+  })
+}
+
+/// Gets the exports of the root module, which are its local exports with
+/// its exported imports among them in the order they're exported,
+/// followed by its re-exports and then the names of its star re-exports
+/// that aren't otherwise exported.
+fn root_export_items(
+  collection: &ModuleDataCollection,
+  graph: &ModuleGraph,
+  hoisted: &HoistedModules,
+  specifier: &ModuleSpecifier,
+  scope_hoist: bool,
+) -> Vec<ModuleItem> {
+  let module_data = collection.get(specifier).unwrap();
+  let mut export_names = HashSet::with_capacity(
+    module_data.exports.len() + module_data.re_exports.len(),
+  );
+  let mut items = Vec::new();
+  let mut re_export_index = 0;
+  let mut export_ref =
+    |items: &mut Vec<ModuleItem>, export_ref: ExportRef, export_name: &str| {
+      let parts = export_ref.into_parts(collection);
+      if scope_hoist && parts.len() == 1 {
+        // export the hoisted declaration or namespace directly
+        items.push(export_x_as_y(parts[0].clone(), export_name.to_string()));
+      } else {
+        re_export_index += 1;
+        let temp_name = format!("_packReExport{}", re_export_index);
+        items.push(
+          const_var_decl(temp_name.clone(), replace_id_to_expr(&parts)).into(),
+        );
+        items.push(export_x_as_y(temp_name, export_name.to_string()));
+      }
+    };
+  let (exported_imports, named_re_exports): (Vec<_>, Vec<_>) = module_data
+    .re_exports
+    .iter()
+    .filter_map(|re_export| {
+      let (export_ref, export_name) = match &re_export.name {
+        ReExportName::Named(name) => (
+          hoisted.resolve_export(
+            collection,
+            &re_export.specifier,
+            &name.local_name,
+          ),
+          name.export_name(),
+        ),
+        ReExportName::Namespace(name) => {
+          (ExportRef::Namespace(re_export.specifier.clone()), name)
+        }
+        // handle these when all done
+        ReExportName::All => return None,
+      };
+      Some((re_export.export_index, export_ref, export_name))
+    })
+    .partition(|(export_index, _, _)| export_index.is_some());
+  // the exported imports are output in the order of the exports
+  let mut exported_imports = exported_imports.into_iter().peekable();
+  for index in 0..=module_data.exports.len() {
+    while let Some((_, resolved, export_name)) = exported_imports
+      .next_if(|(export_index, _, _)| *export_index == Some(index))
+    {
+      export_ref(&mut items, resolved, export_name);
+      export_names.insert(export_name);
+    }
+    let Some(export) = module_data.exports.get(index) else {
+      continue;
+    };
+    // the root module's default export expression stays in its body
+    if export.local_name != "__pack_default__" {
+      items.push(export_x_as_y(
+        export.local_name.clone(),
+        export.export_name().to_string(),
+      ));
+    }
+    export_names.insert(export.export_name());
+  }
+  for (_, resolved, export_name) in named_re_exports {
+    export_ref(&mut items, resolved, export_name);
+    export_names.insert(export_name);
+  }
+  for re_export in &module_data.re_exports {
+    if matches!(re_export.name, ReExportName::All) {
+      let re_export_names = collection.get_export_names(&re_export.specifier);
+      for name in &re_export_names {
+        if !export_names.contains(&name) {
+          export_ref(
+            &mut items,
+            hoisted.resolve_export(collection, &re_export.specifier, name),
+            name,
+          );
+        }
+      }
+    }
+  }
+  for package_specifier in
+    collection.get_package_star_re_exports(graph, specifier)
+  {
+    items.push(ModuleItem::ModuleDecl(ModuleDecl::ExportAll(ExportAll {
+      span: DUMMY_SP,
+      src: Box::new(Str {
+        span: DUMMY_SP,
+        value: external_specifier_text(&package_specifier).into(),
+        raw: None,
+      }),
+      type_only: false,
+      with: None,
+    })));
+  }
+  items
+}
+
+/// Gets the declaration of a packed module's namespace object, which
+/// its exports are defined on when the module is evaluated.
+fn namespace_decl_items(
+  collection: &ModuleDataCollection,
+  graph: &ModuleGraph,
+  hoisted: &HoistedModules,
+  root: &ModuleSpecifier,
+  specifier: &ModuleSpecifier,
+) -> Vec<ModuleItem> {
+  let export_names = collection.get_export_names(specifier);
+  let module_data = collection.get(specifier).unwrap();
+  if export_names.is_empty() && !module_data.is_dynamically_imported
+    || root == specifier
+    || !hoisted.has_namespace(specifier)
+  {
+    return Vec::new();
+  }
+  let is_hoisted = hoisted.is_hoisted(specifier);
+  let props = export_names
+    .into_iter()
+    .map(|name| {
+      if is_hoisted {
+        // the declarations are already in scope
+        let export_ref = hoisted.resolve_export(collection, specifier, &name);
+        getter_prop(
+          name,
+          replace_id_to_expr(&export_ref.into_parts(collection)),
+        )
+      } else {
+        PropOrSpread::Prop(Box::new(Prop::KeyValue(KeyValueProp {
+          key: prop_name(name),
+          value: ident("undefined".to_string()).into(),
+        })))
+      }
+    })
+    .collect();
+  let mut items = Vec::from([ModuleItem::Stmt(Stmt::Decl(Decl::Var(
+    Box::new(const_var_decl(
+      module_data.id.to_code_string(),
+      Expr::Object(ObjectLit {
+        span: DUMMY_SP,
+        props,
+      }),
+    )),
+  )))]);
+  for package_specifier in
+    collection.get_package_star_re_exports(graph, specifier)
+  {
+    let package_id = collection.get(&package_specifier).unwrap().id;
+    items.push(ModuleItem::Stmt(package_star_re_export_stmt(
+      module_data.id.to_code_string(),
+      package_id.to_code_string(),
+    )));
+  }
+  items
+}
+
+/// Replaces the imports, the renamed declarations and the dynamic
+/// imports of a module with what they refer to in the output.
+fn transform_module(
+  module: &mut Module,
+  specifier: &ModuleSpecifier,
+  context: &mut Context<impl Reporter>,
+  hoisted: &HoistedModules,
+  evaluation_order: &EvaluationOrder,
+  async_modules: &HashSet<&ModuleSpecifier>,
+) {
+  let module_data = context.module_data.get(specifier).unwrap();
+  let is_root_module = specifier == context.root;
+  if let Some(renames) = hoisted.renames.get(specifier) {
+    module.visit_mut_with(&mut TopLevelRenamer {
+      top_level_context: module_data.names.as_ref().unwrap().top_level_context,
+      renames,
+    });
+  }
+  // replace all the identifiers
+  let replace_ids = module_data
+    .imports
+    .iter()
+    .map(|(id, imported)| {
+      let export_ref = hoisted.resolve_import(&context.module_data, imported);
+      (id.clone(), export_ref.into_parts(&context.module_data))
+    })
+    .collect::<HashMap<_, _>>();
+  module.visit_mut_with(&mut Transformer {
+    replace_ids: &replace_ids,
+    is_root_module,
+    default_name: hoisted.local_name(specifier, "__pack_default__"),
+  });
+  // the modules are transpiled together and their analysis
+  // would conflict
+  remove_syntax_contexts(module);
+  if let Some(factories) = &module_data.jsx_factories {
+    let declares_runtime = module_data.jsx_runtime.is_some()
+      && if is_root_module {
+        !context.scope_hoist
+      } else {
+        !hoisted.is_hoisted(specifier)
+      };
+    if declares_runtime {
+      module.body.insert(
+        0,
+        ModuleItem::Stmt(Stmt::Expr(ExprStmt {
+          span: Span::new(module.span.lo, module.span.lo, module.span.ctxt),
+          expr: Box::new(Expr::Ident(ident(
+            JSX_RUNTIME_PLACEHOLDER.to_string(),
+          ))),
+        })),
+      );
+    }
+    context.jsx_modules.push(JsxModule {
+      start_pos: module.span.lo,
+      end_pos: module.span.hi,
+      factory: factories.factory.replaced_parts(&replace_ids),
+      fragment_factory: factories.fragment_factory.replaced_parts(&replace_ids),
+      runtime: module_data.jsx_runtime.clone(),
+      declares_runtime,
+    });
+  }
+  if !module_data.dynamic_imports.is_empty() {
+    let replacements = get_dynamic_import_replacements(
+      context.graph,
+      &context.module_data,
+      context.root,
+      &module_data.dynamic_imports,
+      evaluation_order,
+      async_modules,
+    );
+    module.visit_mut_with(&mut DynamicImportTransformer {
+      replacements: &replacements,
+    });
+  }
+}
+
+/// Gets the declaration of a JSON module's namespace, which has the
+/// parsed JSON as its default export.
+fn json_module_item(
+  json: &JsonModule,
+  context: &Context<impl Reporter>,
+  displayed_specifier: &str,
+) -> ModuleItem {
+  let module_data = context.module_data.get(&json.specifier).unwrap();
+  let source_file = context.source_map.new_source_file(
+    FileName::Url(json.specifier.clone()),
+    json.source.to_string(),
+  );
+  let json_expr = match parse_json_module_expr(&source_file, &json.specifier) {
+    Ok(expr) => expr,
+    Err(diagnostic) => {
+      context.reporter.diagnostic(*diagnostic);
+      Expr::Ident(ident("undefined".to_string()))
+    }
+  };
+  // label the json module because there's no function name
+  context.comments.add_leading(
+    source_file.start_pos,
+    Comment {
+      kind: CommentKind::Line,
+      span: DUMMY_SP,
+      text: format!(" {}", displayed_specifier).into(),
+    },
+  );
+  let mut var_decl = const_var_decl(
+    module_data.id.to_code_string(),
+    Expr::Object(ObjectLit {
+      span: DUMMY_SP,
+      props: Vec::from([PropOrSpread::Prop(Box::new(Prop::KeyValue(
+        KeyValueProp {
+          key: ident("default".to_string()).into(),
+          value: Box::new(json_expr),
+        },
+      )))]),
+    }),
+  );
+  var_decl.span = Span::new(
+    source_file.start_pos,
+    source_file.start_pos,
+    Default::default(),
+  );
+  var_decl.into()
+}
+
+/// Transpiles all the modules at once now that they're in the output,
+/// but without the imports of the external modules so they're not
+/// removed when only imported for their side effects. The transforms
+/// take one JSX configuration, so the factories and runtime of each
+/// module (ex. from its pragmas) are placeholders replaced afterwards.
+fn transpile_output(
+  mut module: Module,
+  external_imports_len: usize,
+  context: &mut Context<impl Reporter>,
+  source_map: &Rc<SourceMap>,
+  hoisted: &HoistedModules,
+  module_fn_decl_names: &HashMap<String, HashSet<String>>,
+) -> Result<Module, anyhow::Error> {
+  let external_imports = module
+    .body
+    .drain(..external_imports_len)
+    .collect::<Vec<_>>();
+  let top_level_decl_names = module
+    .body
+    .iter()
+    .filter_map(|item| match item {
+      ModuleItem::Stmt(Stmt::Decl(decl))
+      | ModuleItem::ModuleDecl(ModuleDecl::ExportDecl(ExportDecl {
+        decl,
+        ..
+      })) => enum_or_namespace_name(decl),
+      _ => None,
+    })
+    .collect::<HashSet<_>>();
+  let program = deno_ast::fold_program(
+    Program::Module(module),
+    &get_transpile_options(context.emit_options),
+    source_map.clone(),
+    context.comments,
+    Mark::fresh(Mark::root()),
+    &context.parse_diagnostics,
+  )?;
+  let mut module = match program {
+    Program::Module(module) => module,
+    Program::Script(_) => unreachable!(),
+  };
+  restore_separately_transpiled_output(
+    &mut module,
+    &top_level_decl_names,
+    module_fn_decl_names,
+  );
+  if !context.jsx_modules.is_empty() {
+    context
+      .jsx_modules
+      .sort_by_key(|jsx_module| jsx_module.start_pos);
+    replace_jsx_placeholders(
+      &mut module,
+      &context.jsx_modules,
+      hoisted,
+      &context.module_data,
+    );
+  }
+  // remove the modules that only had types
+  module.body.retain(|item| !is_empty_module_fn(item));
+  module.body.splice(0..0, external_imports);
+  Ok(module)
+}
+
+/// Gets the statements of a module's function, which are the module's
+/// body followed by the definitions of its exports on its namespace.
+fn module_fn_stmts(
+  collection: &ModuleDataCollection,
+  hoisted: &HoistedModules,
+  module_data: &ModuleData,
+  body: Vec<ModuleItem>,
+  awaited_evaluations: Vec<Expr>,
+) -> Vec<Stmt> {
+  let mut stmts = body
+    .into_iter()
+    .map(|item| match item {
+      ModuleItem::ModuleDecl(_) => unreachable!(),
+      ModuleItem::Stmt(stmt) => stmt,
+    })
+    .collect::<Vec<_>>();
+  if !awaited_evaluations.is_empty() {
+    stmts.insert(0, await_module_evaluations(awaited_evaluations));
+  }
+  let code_string = module_data.id.to_code_string();
+  let mut export_names = HashSet::with_capacity(
+    module_data.exports.len() + module_data.re_exports.len(),
+  );
+  for export in &module_data.exports {
+    stmts.push(object_define_property(
+      code_string.clone(),
+      export.export_name().to_string(),
+      ident(export.local_name.clone()).into(),
+    ));
+    export_names.insert(export.export_name());
+  }
+  for re_export in &module_data.re_exports {
+    match &re_export.name {
+      ReExportName::Named(name) => {
+        let export_ref = hoisted.resolve_export(
+          collection,
+          &re_export.specifier,
+          &name.local_name,
+        );
+        stmts.push(object_define_property(
+          code_string.clone(),
+          name.export_name().to_string(),
+          replace_id_to_expr(&export_ref.into_parts(collection)),
+        ));
+        export_names.insert(name.export_name());
+      }
+      ReExportName::Namespace(name) => {
+        stmts.push(object_define_property(
+          code_string.clone(),
+          name.to_string(),
+          ident(re_export.module_id.to_code_string()).into(),
+        ));
+        export_names.insert(name);
+      }
+      ReExportName::All => {
+        // handle these when all done
+      }
+    }
+  }
+  for re_export in &module_data.re_exports {
+    if matches!(re_export.name, ReExportName::All) {
+      let re_export_names = collection.get_export_names(&re_export.specifier);
+      for name in &re_export_names {
+        if !export_names.contains(&name) {
+          let export_ref =
+            hoisted.resolve_export(collection, &re_export.specifier, name);
+          stmts.push(object_define_property(
+            code_string.clone(),
+            name.clone(),
+            replace_id_to_expr(&export_ref.into_parts(collection)),
+          ));
+        }
+      }
+    }
+  }
+  stmts
+}
+
+/// Gets the items that call a module's function where the module is
+/// evaluated, or when it's first loaded if the module is lazy.
+fn module_fn_items(
+  id: ModuleId,
+  name: String,
+  stmts: Vec<Stmt>,
+  is_lazy: bool,
+  is_async: bool,
+) -> Vec<ModuleItem> {
+  let fn_expr = FnExpr {
+    ident: Some(ident(name)),
+    function: Box::new(Function {
+      params: Vec::new(),
+      decorators: Vec::new(),
+      span: DUMMY_SP,
+      body: Some(BlockStmt {
+        span: DUMMY_SP,
+        stmts,
+      }),
+      is_generator: false,
+      is_async,
+      type_params: None,
+      return_type: None,
+    }),
+  };
+  let iife = Expr::Call(CallExpr {
+    span: DUMMY_SP,
+    callee: Callee::Expr(Box::new(Expr::Paren(ParenExpr {
+      span: DUMMY_SP,
+      expr: Box::new(Expr::Fn(fn_expr)),
+    }))),
+    args: vec![],
+    type_args: None,
+  });
+  if is_lazy {
+    lazy_module_load_items(id, iife)
+  } else if is_async {
+    // other modules await this promise instead of it being awaited
+    // here so that sibling modules may still evaluate
+    Vec::from([const_var_decl(id.to_evaluation_code_string(), iife).into()])
+  } else {
+    Vec::from([ModuleItem::Stmt(Stmt::Expr(ExprStmt {
+      span: DUMMY_SP,
+      expr: iife.into(),
+    }))])
+  }
+}
+
+/// Labels where a hoisted module starts with a comment since there's no
+/// function named after it.
+fn label_hoisted_module(
+  module: &Module,
+  global_comments: &SingleThreadedComments,
+  displayed_specifier: &str,
+) {
+  // label where the module starts since there's no function
+  let maybe_first_item =
+    module.body.iter().find(|item| !is_type_declaration(item));
+  if let Some(first_item) = maybe_first_item {
+    let pos = first_item.span_lo();
+    if pos != BytePos::DUMMY {
+      let mut comments = global_comments.take_leading(pos).unwrap_or_default();
+      comments.insert(
+        0,
+        Comment {
+          kind: CommentKind::Line,
+          span: DUMMY_SP,
+          text: format!(" {}", displayed_specifier.trim_start_matches('/'))
+            .into(),
+        },
+      );
+      global_comments.add_leading_comments(pos, comments);
+    }
+  }
+}
 
-  Ok(final_text)
+/// Gets what replaces each dynamic import of a local module, which is a
+/// promise of the module's namespace once the module is evaluated.
+fn get_dynamic_import_replacements(
+  graph: &ModuleGraph,
+  collection: &ModuleDataCollection,
+  root: &ModuleSpecifier,
+  dynamic_imports: &HashMap<String, ModuleSpecifier>,
+  evaluation_order: &EvaluationOrder,
+  async_modules: &HashSet<&ModuleSpecifier>,
+) -> HashMap<String, Expr> {
+  dynamic_imports
+    .iter()
+    .map(|(text, dep_specifier)| {
+      if dep_specifier == root {
+        // the output is the root module
+        return (text.clone(), import_meta_url_import());
+      }
+      let dep_id = collection.get(dep_specifier).unwrap().id;
+      let namespace = Expr::Ident(ident(dep_id.to_code_string()));
+      let expr = if evaluation_order.lazy.contains(dep_specifier) {
+        // import("./mod.ts") ->
+        //   pack1Load().then(pack0Load).then(() => pack0)
+        let mut load_names = get_lazy_evaluation_order(
+          graph,
+          dep_specifier,
+          &evaluation_order.lazy,
+        )
+        .into_iter()
+        .map(|specifier| {
+          let id = collection.get(&specifier).unwrap().id;
+          id.to_load_code_string()
+        });
+        let first_load = Expr::Call(CallExpr {
+          span: DUMMY_SP,
+          callee: Callee::Expr(Box::new(Expr::Ident(ident(
+            load_names.next().unwrap(),
+          )))),
+          args: Vec::new(),
+          type_args: None,
+        });
+        let loaded = load_names.fold(first_load, |expr, load_name| {
+          promise_then(expr, Expr::Ident(ident(load_name)))
+        });
+        promise_then(loaded, arrow_fn_returning(namespace))
+      } else if async_modules.contains(dep_specifier) {
+        // import("./mod.ts") -> pack0Eval.then(() => pack0)
+        promise_then(
+          Expr::Ident(ident(dep_id.to_evaluation_code_string())),
+          arrow_fn_returning(namespace),
+        )
+      } else {
+        // import("./mod.ts") -> Promise.resolve(pack0)
+        Expr::Call(CallExpr {
+          span: DUMMY_SP,
+          callee: Callee::Expr(Box::new(Expr::Member(member_x_y(
+            "Promise".to_string(),
+            "resolve".to_string(),
+          )))),
+          args: Vec::from([ExprOrSpread {
+            spread: None,
+            expr: Box::new(namespace),
+          }]),
+          type_args: None,
+        })
+      };
+      (text.clone(), expr)
+    })
+    .collect()
 }
 
 struct HasAwaitKeywordVisitor {
@@ -606,18 +1256,65 @@ impl Visit for HasAwaitKeywordVisitor {
 
 fn analyze_esm_module(
   esm: &EsmModule,
-  context: &mut Context,
-) -> Result<(), Diagnostic> {
+  context: &mut Context<impl Reporter>,
+) -> Result<(), anyhow::Error> {
+  let stopwatch = Stopwatch::start();
   let module_specifier = &esm.specifier;
-  let parsed_source = context.parser.parse_module(
-    module_specifier,
-    esm.source.clone(),
-    esm.media_type,
-  )?;
-  let is_root_module = context.graph.roots[0] == *module_specifier;
-  let mut module = (*parsed_source.module()).clone();
+  let maybe_parsed_source = if context.move_asts {
+    context.parsed_sources.take(module_specifier)
+  } else {
+    context.parsed_sources.get_parsed_source(module_specifier)
+  };
+  let Some(parsed_source) = maybe_parsed_source else {
+    bail!("Could not find the parsed source of {}", module_specifier);
+  };
+  let source_file = context.source_map.new_source_file(
+    FileName::Url(module_specifier.clone()),
+    esm.source.to_string(),
+  );
+  fill_leading_comments(
+    source_file.start_pos,
+    &parsed_source,
+    context.comments,
+    // remove any jsdoc comments from the js output as they will
+    // appear in the dts output
+    |c| c.kind != CommentKind::Block || !c.text.starts_with('*'),
+  );
+  fill_trailing_comments(
+    source_file.start_pos,
+    &parsed_source,
+    context.comments,
+  );
+  context
+    .parse_diagnostics
+    .extend(parsed_source.diagnostics().iter().cloned());
+  let text_info = parsed_source.text_info().clone();
+  let top_level_context = parsed_source.top_level_context();
+  let is_jsx = matches!(esm.media_type, MediaType::Jsx | MediaType::Tsx);
+  let jsx_pragmas =
+    is_jsx.then(|| JsxPragmas::parse(&parsed_source.get_leading_comments()));
+  let mut module = into_module(parsed_source);
+
+  let reporter = context.reporter;
+  let report = |code: DiagnosticCode, range: SourceRange, message: String| {
+    reporter.diagnostic(Diagnostic {
+      code,
+      severity: code.default_severity(),
+      message,
+      specifier: module_specifier.clone(),
+      line_and_column: Some(
+        text_info.line_and_column_display(range.start).into(),
+      ),
+      end_line_and_column: Some(
+        text_info.line_and_column_display(range.end).into(),
+      ),
+      related: Vec::new(),
+      hint: None,
+    });
+  };
+  let is_root_module = context.root == module_specifier;
 
-  let mut replace_ids = HashMap::new();
+  let mut imports = HashMap::new();
   let mut found_tla = false;
   // analyze the top level declarations
   for module_item in &module.body {
@@ -639,52 +1336,38 @@ fn analyze_esm_module(
             .resolve_dependency(value, module_specifier, false)
           {
             Some(dep_specifier) => {
-              let dep_module_id =
-                context.module_data.get_mut(&dep_specifier).id;
+              // assign the dependency its id in the order it is imported
+              context.module_data.get_mut(&dep_specifier);
               for import_specifier in &import.specifiers {
-                match import_specifier {
+                let (local, name) = match import_specifier {
                   ImportSpecifier::Default(default_specifier) => {
-                    replace_ids.insert(
-                      default_specifier.local.to_id(),
-                      vec![
-                        dep_module_id.to_code_string(),
-                        "default".to_string(),
-                      ],
-                    );
+                    (&default_specifier.local, Some("default".to_string()))
                   }
                   ImportSpecifier::Namespace(namespace_specifier) => {
-                    replace_ids.insert(
-                      namespace_specifier.local.to_id(),
-                      vec![dep_module_id.to_code_string()],
-                    );
+                    (&namespace_specifier.local, None)
                   }
                   ImportSpecifier::Named(named_specifier) => {
-                    if !named_specifier.is_type_only {
-                      replace_ids.insert(
-                        named_specifier.local.to_id(),
-                        vec![
-                          dep_module_id.to_code_string(),
-                          named_specifier
-                            .imported
-                            .as_ref()
-                            .map(|i| match i {
-                              ModuleExportName::Str(_) => todo!(),
-                              ModuleExportName::Ident(ident) => {
-                                ident.sym.to_string()
-                              }
-                            })
-                            .unwrap_or_else(|| {
-                              named_specifier.local.sym.to_string()
-                            }),
-                        ],
-                      );
+                    if named_specifier.is_type_only {
+                      continue;
                     }
+                    let imported_name = match &named_specifier.imported {
+                      Some(name) => module_export_name_text(name),
+                      None => named_specifier.local.sym.to_string(),
+                    };
+                    (&named_specifier.local, Some(imported_name))
                   }
-                }
+                };
+                imports.insert(
+                  local.to_id(),
+                  ImportedName {
+                    specifier: dep_specifier.clone(),
+                    name,
+                  },
+                );
               }
             }
             None => {
-              todo!();
+              report_unresolved(&report, &import.src);
             }
           }
         }
@@ -732,11 +1415,7 @@ fn analyze_esm_module(
               context.module_data.get_mut(module_specifier).exports.push(
                 ExportName {
                   export_name: Some("default".to_string()),
-                  local_name: replace_ids
-                    .get(&ident.to_id())
-                    .map(ToOwned::to_owned)
-                    .unwrap_or_else(|| vec![ident.sym.to_string()])
-                    .join("."),
+                  local_name: ident.sym.to_string(),
                 },
               );
             }
@@ -792,51 +1471,8 @@ fn analyze_esm_module(
               }
               let module_data = context.module_data.get_mut(module_specifier);
               for decl in &decl.decls {
-                match &decl.name {
-                  Pat::Array(_) => todo!("array destructuring"),
-                  Pat::Assign(_) => todo!("var assignment"),
-                  Pat::Ident(ident) => {
-                    module_data.add_export_name(ident.id.sym.to_string());
-                  }
-                  Pat::Rest(_) => todo!("spread in var decl"),
-                  Pat::Object(obj) => {
-                    for prop in &obj.props {
-                      match prop {
-                        ObjectPatProp::KeyValue(kv) => {
-                          match &kv.key {
-                            PropName::Ident(ident) => {
-                              module_data
-                                .add_export_name(ident.sym.to_string());
-                            }
-                            PropName::Str(_) => todo!(),
-                            PropName::Computed(_)
-                            | PropName::BigInt(_)
-                            | PropName::Num(_) => {
-                              // ignore
-                            }
-                          }
-                        }
-                        ObjectPatProp::Assign(assign_prop) => {
-                          module_data
-                            .add_export_name(assign_prop.key.sym.to_string());
-                        }
-                        ObjectPatProp::Rest(rest) => match &*rest.arg {
-                          Pat::Ident(ident) => {
-                            module_data
-                              .add_export_name(ident.id.sym.to_string());
-                          }
-                          Pat::Array(_) => todo!(),
-                          Pat::Rest(_) => todo!(),
-                          Pat::Object(_) => todo!(),
-                          Pat::Assign(_) => todo!(),
-                          Pat::Invalid(_) => todo!(),
-                          Pat::Expr(_) => todo!(),
-                        },
-                      }
-                    }
-                  }
-                  Pat::Invalid(_) => todo!(),
-                  Pat::Expr(_) => todo!(),
+                for name in get_pat_binding_names(&decl.name) {
+                  module_data.add_export_name(name);
                 }
               }
             }
@@ -883,8 +1519,17 @@ fn analyze_esm_module(
                 let module_data = context.module_data.get_mut(module_specifier);
                 for export_specifier in &decl.specifiers {
                   match export_specifier {
-                    ExportSpecifier::Default(_) => {
-                      todo!(); // what even is this? Maybe some babel thing or I'm not thinking atm
+                    // export a from "./mod.ts";
+                    ExportSpecifier::Default(default) => {
+                      module_data.re_exports.push(ReExport {
+                        name: ReExportName::Named(ExportName {
+                          export_name: Some(default.exported.sym.to_string()),
+                          local_name: "default".to_string(),
+                        }),
+                        specifier: dep_specifier.clone(),
+                        module_id: dep_id,
+                        export_index: None,
+                      })
                     }
                     ExportSpecifier::Named(named) => {
                       if named.is_type_only {
@@ -892,83 +1537,87 @@ fn analyze_esm_module(
                       }
                       module_data.re_exports.push(ReExport {
                         name: ReExportName::Named(ExportName {
-                          export_name: named.exported.as_ref().map(|name| {
-                            match name {
-                              ModuleExportName::Ident(ident) => {
-                                ident.sym.to_string()
-                              }
-                              ModuleExportName::Str(_) => todo!(),
-                            }
-                          }),
-                          local_name: match &named.orig {
-                            ModuleExportName::Ident(ident) => {
-                              ident.sym.to_string()
-                            }
-                            ModuleExportName::Str(_) => todo!(),
-                          },
+                          export_name: named
+                            .exported
+                            .as_ref()
+                            .map(module_export_name_text),
+                          local_name: module_export_name_text(&named.orig),
                         }),
                         specifier: dep_specifier.clone(),
                         module_id: dep_id,
+                        export_index: None,
                       })
                     }
                     ExportSpecifier::Namespace(namespace) => {
                       module_data.re_exports.push(ReExport {
-                        name: ReExportName::Namespace(match &namespace.name {
-                          ModuleExportName::Ident(ident) => {
-                            ident.sym.to_string()
-                          }
-                          ModuleExportName::Str(_) => todo!(),
-                        }),
+                        name: ReExportName::Namespace(module_export_name_text(
+                          &namespace.name,
+                        )),
                         specifier: dep_specifier.clone(),
                         module_id: dep_id,
+                        export_index: None,
                       })
                     }
                   }
                 }
               }
               None => {
-                todo!();
+                report_unresolved(&report, src);
               }
             }
           } else {
             // no specifier
-            let module_data = context.module_data.get_mut(module_specifier);
             for export_specifier in &decl.specifiers {
               match export_specifier {
                 ExportSpecifier::Named(named) => {
-                  let (local_name, local_name_as_export) = {
-                    match &named.orig {
-                      ModuleExportName::Ident(ident) => {
-                        let ident_text = ident.sym.to_string();
-                        let local_name = replace_ids
-                          .get(&ident.to_id())
-                          .map(ToOwned::to_owned)
-                          .unwrap_or_else(|| vec![ident_text.clone()])
-                          .join(".");
-                        let local_name_as_export = if ident_text != local_name {
-                          Some(ident_text)
-                        } else {
-                          None
-                        };
-                        (local_name, local_name_as_export)
-                      }
-                      ModuleExportName::Str(_) => todo!(),
-                    }
+                  let ident = match &named.orig {
+                    ModuleExportName::Ident(ident) => ident,
+                    // not valid syntax without a module specifier
+                    ModuleExportName::Str(_) => unreachable!(),
                   };
-                  module_data.exports.push(ExportName {
-                    export_name: named
-                      .exported
-                      .as_ref()
-                      .map(|name| match name {
-                        ModuleExportName::Ident(ident) => ident.sym.to_string(),
-                        ModuleExportName::Str(_) => todo!(),
-                      })
-                      .or(local_name_as_export),
-                    local_name,
-                  });
-                }
-                ExportSpecifier::Namespace(_) | ExportSpecifier::Default(_) => {
-                  unreachable!()
+                  let export_name = named
+                    .exported
+                    .as_ref()
+                    .map(module_export_name_text)
+                    .unwrap_or_else(|| ident.sym.to_string());
+                  match imports.get(&ident.to_id()) {
+                    // exporting an import is the same as re-exporting it
+                    Some(imported) => {
+                      let dep_id =
+                        context.module_data.get_mut(&imported.specifier).id;
+                      let name = match &imported.name {
+                        Some(name) => ReExportName::Named(ExportName {
+                          export_name: Some(export_name),
+                          local_name: name.clone(),
+                        }),
+                        None => ReExportName::Namespace(export_name),
+                      };
+                      let module_data =
+                        context.module_data.get_mut(module_specifier);
+                      module_data.re_exports.push(ReExport {
+                        name,
+                        specifier: imported.specifier.clone(),
+                        module_id: dep_id,
+                        export_index: Some(module_data.exports.len()),
+                      });
+                    }
+                    None => {
+                      let local_name = ident.sym.to_string();
+                      context
+                        .module_data
+                        .get_mut(module_specifier)
+                        .exports
+                        .push(ExportName {
+                          export_name: (export_name != local_name)
+                            .then_some(export_name),
+                          local_name,
+                        });
+                    }
+                  }
+                }
+                // not valid syntax without a module specifier
+                ExportSpecifier::Namespace(_) | ExportSpecifier::Default(_) => {
+                  unreachable!()
                 }
               }
             }
@@ -990,35 +1639,305 @@ fn analyze_esm_module(
                 name: ReExportName::All,
                 specifier: dep_specifier,
                 module_id: dep_id,
+                export_index: None,
               });
             }
             None => {
-              todo!();
+              report_unresolved(&report, &export_all.src);
             }
           }
         }
-        ModuleDecl::TsImportEquals(_)
-        | ModuleDecl::TsExportAssignment(_)
-        | ModuleDecl::TsNamespaceExport(_) => {}
+        ModuleDecl::TsExportAssignment(assignment) => {
+          // a CommonJS style `export =` becomes the default export, which
+          // is what TypeScript's default imports of it resolve to
+          if has_value_exports(&module) {
+            report(
+              DiagnosticCode::UnsupportedExportAssignment,
+              assignment.range(),
+              concat!(
+                "Export assignment cannot be represented in the ESM output ",
+                "because the module has other exports.",
+              )
+              .to_string(),
+            );
+            continue;
+          }
+          if is_root_module {
+            continue;
+          }
+          context.module_data.get_mut(module_specifier).exports.push(
+            ExportName {
+              export_name: Some("default".to_string()),
+              local_name: "__pack_default__".to_string(),
+            },
+          );
+        }
+        ModuleDecl::TsImportEquals(_) | ModuleDecl::TsNamespaceExport(_) => {}
       },
     }
   }
 
-  // replace all the identifiers
-  let mut transformer = Transformer {
-    replace_ids: &replace_ids,
-    is_root_module,
+  if has_value_exports(&module) {
+    // the unsupported export assignments were reported above
+    module.body.retain(|item| {
+      !matches!(
+        item,
+        ModuleItem::ModuleDecl(ModuleDecl::TsExportAssignment(_))
+      )
+    });
+  }
+
+  // resolve the dynamic imports of local modules, which are rewritten
+  // to their namespace objects once the evaluation order is known
+  let mut dynamic_imports = HashMap::new();
+  for specifier in &DynamicImportCollector::collect(&module) {
+    match context.graph.resolve_dependency(
+      &specifier.value,
+      module_specifier,
+      false,
+    ) {
+      Some(dep_specifier) if context.graph.get(&dep_specifier).is_some() => {
+        if !context.is_packed(&dep_specifier) {
+          // leave these as-is
+          continue;
+        }
+        if *context.root != dep_specifier {
+          context
+            .module_data
+            .get_mut(&dep_specifier)
+            .is_dynamically_imported = true;
+        }
+        dynamic_imports.insert(specifier.value.to_string(), dep_specifier);
+      }
+      _ => report_unresolved(&report, specifier),
+    }
+  }
+
+  let mut jsx_runtime = None;
+  let mut jsx_factories = None;
+  if let Some(pragmas) = &jsx_pragmas {
+    let report_unsupported_pragma = |pragma: &JsxPragma| {
+      report(
+        DiagnosticCode::UnsupportedJsxPragma,
+        pragma.range,
+        format!(
+          "The {} pragma is not supported with the configured JSX transform.",
+          pragma.name
+        ),
+      )
+    };
+    match context.jsx.automatic_import_source() {
+      Some(import_source) => {
+        for pragma in [&pragmas.factory, &pragmas.fragment_factory]
+          .into_iter()
+          .flatten()
+        {
+          report_unsupported_pragma(pragma);
+        }
+        if let Some(pragma) =
+          pragmas.runtime.as_ref().filter(|p| p.value != "automatic")
+        {
+          report_unsupported_pragma(pragma);
+        }
+        // deno_graph adds the runtime as a dependency of the module
+        // (ex. `react/jsx-runtime`) based on the config or the pragma
+        let runtime_text = format!(
+          "{}/{}",
+          pragmas
+            .import_source
+            .as_ref()
+            .map(|p| p.value.as_str())
+            .unwrap_or(import_source),
+          context.jsx.import_source_module()
+        );
+        let Some(runtime_specifier) = context.graph.resolve_dependency(
+          &runtime_text,
+          module_specifier,
+          false,
+        ) else {
+          bail!("Could not resolve the JSX runtime of {}", module_specifier);
+        };
+        context.module_data.get_mut(&runtime_specifier);
+        jsx_runtime = Some(runtime_specifier);
+      }
+      None => {
+        if let Some(pragma) = &pragmas.import_source {
+          report_unsupported_pragma(pragma);
+        }
+        if let Some(pragma) =
+          pragmas.runtime.as_ref().filter(|p| p.value != "classic")
+        {
+          report_unsupported_pragma(pragma);
+        }
+      }
+    }
+    // the classic factories refer to an import or a global
+    let factory = |pragma: &Option<JsxPragma>, default: &str| {
+      JsxFactory::new(
+        pragma.as_ref().map(|p| p.value.as_str()).unwrap_or(default),
+        &imports,
+        top_level_context,
+      )
+    };
+    jsx_factories = Some(JsxFactories {
+      factory: factory(&pragmas.factory, &context.emit_options.jsx_factory),
+      fragment_factory: factory(
+        &pragmas.fragment_factory,
+        &context.emit_options.jsx_fragment_factory,
+      ),
+    });
+  }
+
+  let names = if context.scope_hoist {
+    let mut names =
+      NameCollector::collect(&module, top_level_context, &imports);
+    if let Some(factories) = &jsx_factories {
+      for factory in [&factories.factory, &factories.fragment_factory] {
+        match &factory.import_id {
+          Some(id) => {
+            names.used_imports.insert(id.clone());
+          }
+          None => {
+            names.nested.insert(factory.parts[0].clone());
+          }
+        }
+      }
+    }
+    Some(names)
+  } else {
+    None
   };
-  transformer.visit_mut_module(&mut module);
+  adjust_spans(source_file.start_pos, &mut module);
   let module_data = context.module_data.get_mut(module_specifier);
   module_data.module = Some(module);
+  module_data.imports = imports;
+  module_data.dynamic_imports = dynamic_imports;
+  module_data.jsx_runtime = jsx_runtime;
+  module_data.jsx_factories = jsx_factories;
+  module_data.names = names;
+  module_data.analyze_ms = stopwatch.elapsed_ms();
 
   Ok(())
 }
 
+/// Gets the names of all the identifiers bound by a pattern.
+fn get_pat_binding_names(pat: &Pat) -> Vec<String> {
+  fn inner(pat: &Pat, names: &mut Vec<String>) {
+    match pat {
+      Pat::Ident(ident) => {
+        names.push(ident.id.sym.to_string());
+      }
+      Pat::Array(array) => {
+        for elem in array.elems.iter().flatten() {
+          inner(elem, names);
+        }
+      }
+      Pat::Rest(rest) => inner(&rest.arg, names),
+      Pat::Assign(assign) => inner(&assign.left, names),
+      Pat::Object(obj) => {
+        for prop in &obj.props {
+          match prop {
+            ObjectPatProp::KeyValue(kv) => inner(&kv.value, names),
+            ObjectPatProp::Assign(assign_prop) => {
+              names.push(assign_prop.key.sym.to_string());
+            }
+            ObjectPatProp::Rest(rest) => inner(&rest.arg, names),
+          }
+        }
+      }
+      // not valid syntax in a variable declaration
+      Pat::Invalid(_) | Pat::Expr(_) => unreachable!(),
+    }
+  }
+
+  let mut names = Vec::new();
+  inner(pat, &mut names);
+  names
+}
+
+/// Gets if the module exports any values other than by an export
+/// assignment (`export = x`).
+fn has_value_exports(module: &Module) -> bool {
+  module.body.iter().any(|item| match item {
+    ModuleItem::ModuleDecl(decl) => match decl {
+      ModuleDecl::ExportDecl(decl) => match &decl.decl {
+        Decl::Class(decl) => !decl.declare,
+        Decl::Fn(decl) => !decl.declare,
+        Decl::Var(decl) => !decl.declare,
+        Decl::TsEnum(decl) => !decl.declare,
+        Decl::TsModule(decl) => !decl.declare,
+        Decl::Using(_) => true,
+        Decl::TsInterface(_) | Decl::TsTypeAlias(_) => false,
+      },
+      ModuleDecl::ExportNamed(decl) => !decl.type_only,
+      ModuleDecl::ExportAll(decl) => !decl.type_only,
+      ModuleDecl::ExportDefaultDecl(decl) => {
+        !matches!(decl.decl, DefaultDecl::TsInterfaceDecl(_))
+      }
+      ModuleDecl::ExportDefaultExpr(_) => true,
+      ModuleDecl::Import(_)
+      | ModuleDecl::TsImportEquals(_)
+      | ModuleDecl::TsExportAssignment(_)
+      | ModuleDecl::TsNamespaceExport(_) => false,
+    },
+    ModuleItem::Stmt(_) => false,
+  })
+}
+
+fn report_unresolved(
+  report: &impl Fn(DiagnosticCode, SourceRange, String),
+  src: &Str,
+) {
+  report(
+    DiagnosticCode::UnresolvedSpecifier,
+    src.range(),
+    format!("Could not resolve '{}'.", src.value),
+  );
+}
+
+/// Collects the string literal specifiers of dynamic imports.
+struct DynamicImportCollector {
+  specifiers: Vec<Str>,
+}
+
+impl DynamicImportCollector {
+  fn collect(module: &Module) -> Vec<Str> {
+    let mut collector = DynamicImportCollector {
+      specifiers: Vec::new(),
+    };
+    module.visit_with(&mut collector);
+    collector.specifiers
+  }
+}
+
+impl Visit for DynamicImportCollector {
+  fn visit_call_expr(&mut self, n: &CallExpr) {
+    if let Some(specifier) = get_dynamic_import_specifier(n) {
+      self.specifiers.push(specifier.clone());
+    }
+    visit_call_expr(self, n);
+  }
+}
+
+fn get_dynamic_import_specifier(call_expr: &CallExpr) -> Option<&Str> {
+  if !matches!(call_expr.callee, Callee::Import(_)) {
+    return None;
+  }
+  match call_expr.args.first() {
+    Some(ExprOrSpread { spread: None, expr }) => match &**expr {
+      Expr::Lit(Lit::Str(str)) => Some(str),
+      _ => None,
+    },
+    _ => None,
+  }
+}
+
 struct Transformer<'a> {
   replace_ids: &'a HashMap<Id, Vec<String>>,
   is_root_module: bool,
+  /// The name of the declaration a default export expression is
+  /// assigned to.
+  default_name: &'a str,
 }
 
 impl<'a> VisitMut for Transformer<'a> {
@@ -1042,6 +1961,17 @@ impl<'a> VisitMut for Transformer<'a> {
   }
 
   fn visit_mut_module_item(&mut self, n: &mut ModuleItem) {
+    if let ModuleItem::ModuleDecl(ModuleDecl::TsExportAssignment(assignment)) =
+      n
+    {
+      // export = a; -> export default a;
+      *n = ModuleItem::ModuleDecl(ModuleDecl::ExportDefaultExpr(
+        ExportDefaultExpr {
+          span: assignment.span,
+          expr: assignment.expr.take(),
+        },
+      ));
+    }
     if !self.is_root_module {
       if let ModuleItem::ModuleDecl(ModuleDecl::ExportDefaultExpr(
         export_default_expr,
@@ -1055,7 +1985,7 @@ impl<'a> VisitMut for Transformer<'a> {
           decls: Vec::from([VarDeclarator {
             span: DUMMY_SP,
             name: Pat::Ident(BindingIdent {
-              id: ident("__pack_default__".to_string()),
+              id: ident(self.default_name.to_string()),
               type_ann: None,
             }),
             init: Some(export_default_expr.expr.clone()),
@@ -1087,7 +2017,7 @@ impl<'a> VisitMut for Transformer<'a> {
               name: Pat::Ident(BindingIdent {
                 id: maybe_ident
                   .cloned()
-                  .unwrap_or_else(|| ident("__pack_default__".to_string())),
+                  .unwrap_or_else(|| ident(self.default_name.to_string())),
                 type_ann: None,
               }),
               init: Some(Box::new(expr)),
@@ -1102,6 +2032,48 @@ impl<'a> VisitMut for Transformer<'a> {
         self.visit_mut_decl(&mut decl);
         // remove the export keyword
         *n = ModuleItem::Stmt(Stmt::Decl(decl));
+      } else if let ModuleItem::ModuleDecl(ModuleDecl::TsImportEquals(
+        import_equals,
+      )) = n
+      {
+        if import_equals.is_type_only {
+          n.take(); // remove it
+          return;
+        }
+        // import a = b.c; -> const a = b.c;
+        let mut expr = match &import_equals.module_ref {
+          TsModuleRef::TsEntityName(entity_name) => {
+            ts_entity_name_to_expr(entity_name)
+          }
+          TsModuleRef::TsExternalModuleRef(module_ref) => {
+            Expr::Call(CallExpr {
+              span: DUMMY_SP,
+              callee: Callee::Expr(Box::new(Expr::Ident(ident(
+                "require".to_string(),
+              )))),
+              args: Vec::from([ExprOrSpread {
+                spread: None,
+                expr: Box::new(Expr::Lit(Lit::Str(module_ref.expr.clone()))),
+              }]),
+              type_args: None,
+            })
+          }
+        };
+        self.visit_mut_expr(&mut expr);
+        *n = ModuleItem::Stmt(Stmt::Decl(Decl::Var(Box::new(VarDecl {
+          span: import_equals.span,
+          kind: VarDeclKind::Const,
+          declare: false,
+          decls: Vec::from([VarDeclarator {
+            span: DUMMY_SP,
+            name: Pat::Ident(BindingIdent {
+              id: import_equals.id.clone(),
+              type_ann: None,
+            }),
+            init: Some(Box::new(expr)),
+            definite: false,
+          }]),
+        }))));
       } else {
         visit_mut_module_item(self, n)
       }
@@ -1153,6 +2125,1437 @@ impl<'a> VisitMut for Transformer<'a> {
       }
     }
   }
+
+  fn visit_mut_jsx_element_name(&mut self, name: &mut JSXElementName) {
+    // <Button /> -> <pack0.Button />
+    if let JSXElementName::Ident(ident) = name {
+      let maybe_parts = self.replace_ids.get(&ident.to_id());
+      if let Some(new_name) = maybe_parts.and_then(|p| replace_id_to_jsx(p)) {
+        *name = new_name;
+      }
+    } else {
+      visit_mut_jsx_element_name(self, name);
+    }
+  }
+
+  fn visit_mut_jsx_object(&mut self, object: &mut JSXObject) {
+    // <ns.Button /> -> <pack0.Button />
+    if let JSXObject::Ident(ident) = object {
+      let maybe_parts = self.replace_ids.get(&ident.to_id());
+      match maybe_parts.and_then(|p| replace_id_to_jsx(p)) {
+        Some(JSXElementName::Ident(ident)) => {
+          *object = JSXObject::Ident(ident);
+        }
+        Some(JSXElementName::JSXMemberExpr(member)) => {
+          *object = JSXObject::JSXMemberExpr(Box::new(member));
+        }
+        Some(JSXElementName::JSXNamespacedName(_)) | None => {}
+      }
+    } else {
+      visit_mut_jsx_object(self, object);
+    }
+  }
+}
+
+/// How the output refers to an export of a packed module.
+enum ExportRef {
+  /// A declaration in the top level scope of the output.
+  Binding(String),
+  /// The namespace object of a module.
+  Namespace(ModuleSpecifier),
+  /// A property of the namespace object of a module.
+  Member(ModuleSpecifier, String),
+}
+
+impl ExportRef {
+  pub fn namespace_specifier(&self) -> Option<&ModuleSpecifier> {
+    match self {
+      ExportRef::Binding(_) => None,
+      ExportRef::Namespace(specifier) | ExportRef::Member(specifier, _) => {
+        Some(specifier)
+      }
+    }
+  }
+
+  pub fn into_parts(self, module_data: &ModuleDataCollection) -> Vec<String> {
+    let id_code_string = |specifier: &ModuleSpecifier| {
+      module_data.get(specifier).unwrap().id.to_code_string()
+    };
+    match self {
+      ExportRef::Binding(name) => vec![name],
+      ExportRef::Namespace(specifier) => vec![id_code_string(&specifier)],
+      ExportRef::Member(specifier, name) => {
+        vec![id_code_string(&specifier), name]
+      }
+    }
+  }
+}
+
+/// The modules whose declarations are output in the top level scope
+/// instead of in a function when scope hoisting.
+#[derive(Default)]
+struct HoistedModules {
+  modules: HashSet<ModuleSpecifier>,
+  /// New names of the top level declarations by module, which are
+  /// renamed when another module uses the same name.
+  renames: HashMap<ModuleSpecifier, HashMap<String, String>>,
+  /// Hoisted modules that still need a namespace object because they're
+  /// imported or re-exported as a namespace or dynamically imported.
+  namespaces: HashSet<ModuleSpecifier>,
+}
+
+impl HoistedModules {
+  pub fn new(
+    module_data: &ModuleDataCollection,
+    root: &ModuleSpecifier,
+    evaluation_order: &[&deno_graph::Module],
+    async_modules: &HashSet<&ModuleSpecifier>,
+  ) -> Self {
+    // async modules stay in a function so that they can be awaited
+    let hoisted_order = evaluation_order
+      .iter()
+      .filter_map(|module| match module {
+        deno_graph::Module::Esm(esm)
+          if esm.specifier != *root
+            && !async_modules.contains(&esm.specifier) =>
+        {
+          Some(&esm.specifier)
+        }
+        _ => None,
+      })
+      .collect::<Vec<_>>();
+
+    // a hoisted declaration is renamed when another module uses its
+    // name outside of the top level scope because it could be shadowed
+    // there or shadow a global
+    let hoisted_specifiers =
+      hoisted_order.iter().copied().collect::<HashSet<_>>();
+    let mut name_counts = HashMap::<&str, usize>::new();
+    let mut taken_names = HashSet::new();
+    for (specifier, data) in &module_data.module_data {
+      if let Some(names) = &data.names {
+        let mut add_name = |name| *name_counts.entry(name).or_default() += 1;
+        names.nested.iter().for_each(|name| add_name(name));
+        if !hoisted_specifiers.contains(specifier) {
+          names
+            .top_level
+            .iter()
+            .filter(|name| !names.nested.contains(*name))
+            .for_each(|name| add_name(name));
+        }
+      }
+      taken_names.insert(data.id.to_code_string());
+      taken_names.insert(data.id.to_evaluation_code_string());
+      taken_names.insert(data.id.to_load_code_string());
+    }
+    // the root module's declarations keep their names
+    if let Some(names) = module_data.get(root).and_then(|d| d.names.as_ref()) {
+      taken_names.extend(names.top_level.iter().cloned());
+    }
+    let mut renames = HashMap::new();
+    for specifier in &hoisted_order {
+      let data = module_data.get(specifier).unwrap();
+      let names = data.names.as_ref().unwrap();
+      let has_default_expr = data
+        .exports
+        .iter()
+        .any(|export| export.local_name == "__pack_default__");
+      let mut module_renames = HashMap::new();
+      for name in names
+        .top_level
+        .iter()
+        .map(String::as_str)
+        .chain(has_default_expr.then_some("__pack_default__"))
+      {
+        let own_count = usize::from(names.nested.contains(name));
+        let count = name_counts.get(name).copied().unwrap_or(0);
+        if taken_names.contains(name) || count > own_count {
+          let new_name = (1..)
+            .map(|index| format!("{}{}", name, index))
+            .find(|new_name| {
+              !taken_names.contains(new_name)
+                && !name_counts.contains_key(new_name.as_str())
+            })
+            .unwrap();
+          taken_names.insert(new_name.clone());
+          module_renames.insert(name.to_string(), new_name);
+        } else {
+          taken_names.insert(name.to_string());
+        }
+      }
+      if !module_renames.is_empty() {
+        renames.insert((*specifier).clone(), module_renames);
+      }
+    }
+
+    let mut hoisted = HoistedModules {
+      modules: hoisted_order.into_iter().cloned().collect(),
+      renames,
+      namespaces: HashSet::new(),
+    };
+
+    // find the namespace objects the output refers to, which includes
+    // the exports the namespace objects themselves refer to
+    let mut refs = Vec::new();
+    for (specifier, data) in &module_data.module_data {
+      let used_imports = data.names.as_ref().map(|names| &names.used_imports);
+      refs.extend(
+        data
+          .imports
+          .iter()
+          .filter(|(id, _)| {
+            used_imports.map(|u| u.contains(*id)).unwrap_or(true)
+          })
+          .map(|(_, imported)| hoisted.resolve_import(module_data, imported)),
+      );
+      if data.is_dynamically_imported {
+        refs.push(ExportRef::Namespace(specifier.clone()));
+      }
+      if let Some(runtime) = &data.jsx_runtime {
+        // the imported runtime functions aren't known until transpiled
+        refs.push(ExportRef::Namespace(runtime.clone()));
+      }
+      if !hoisted.modules.contains(specifier) {
+        refs.extend(hoisted.resolve_re_exports(module_data, data));
+      }
+    }
+    let mut namespaces = HashSet::new();
+    while !refs.is_empty() {
+      let mut pending = Vec::new();
+      for specifier in refs.iter().filter_map(|r| r.namespace_specifier()) {
+        if hoisted.modules.contains(specifier)
+          && namespaces.insert(specifier.clone())
+        {
+          pending.push(specifier);
+        }
+      }
+      refs = pending
+        .into_iter()
+        .flat_map(|specifier| {
+          module_data
+            .get_export_names(specifier)
+            .into_iter()
+            .map(|name| hoisted.resolve_export(module_data, specifier, &name))
+        })
+        .collect();
+    }
+    hoisted.namespaces = namespaces;
+    hoisted
+  }
+
+  pub fn is_hoisted(&self, specifier: &ModuleSpecifier) -> bool {
+    self.modules.contains(specifier)
+  }
+
+  pub fn has_namespace(&self, specifier: &ModuleSpecifier) -> bool {
+    !self.modules.contains(specifier) || self.namespaces.contains(specifier)
+  }
+
+  /// Gets the name of a top level declaration in the output.
+  pub fn local_name<'a>(
+    &'a self,
+    specifier: &ModuleSpecifier,
+    name: &'a str,
+  ) -> &'a str {
+    self
+      .renames
+      .get(specifier)
+      .and_then(|renames| renames.get(name))
+      .map(String::as_str)
+      .unwrap_or(name)
+  }
+
+  pub fn resolve_import(
+    &self,
+    module_data: &ModuleDataCollection,
+    imported: &ImportedName,
+  ) -> ExportRef {
+    match &imported.name {
+      Some(name) => self.resolve_export(module_data, &imported.specifier, name),
+      None => ExportRef::Namespace(imported.specifier.clone()),
+    }
+  }
+
+  /// Resolves an export to the declaration of a hoisted module when
+  /// possible, which falls back to the module's namespace object.
+  pub fn resolve_export(
+    &self,
+    module_data: &ModuleDataCollection,
+    specifier: &ModuleSpecifier,
+    name: &str,
+  ) -> ExportRef {
+    self
+      .resolve_hoisted_export(module_data, specifier, name, &mut HashSet::new())
+      .unwrap_or_else(|| ExportRef::Member(specifier.clone(), name.to_string()))
+  }
+
+  fn resolve_hoisted_export(
+    &self,
+    module_data: &ModuleDataCollection,
+    specifier: &ModuleSpecifier,
+    name: &str,
+    seen: &mut HashSet<ModuleSpecifier>,
+  ) -> Option<ExportRef> {
+    if !self.modules.contains(specifier) || !seen.insert(specifier.clone()) {
+      return None;
+    }
+    let data = module_data.get(specifier)?;
+    if let Some(export) = data.exports.iter().find(|e| e.export_name() == name)
+    {
+      return Some(ExportRef::Binding(
+        self.local_name(specifier, &export.local_name).to_string(),
+      ));
+    }
+    let mut resolve_re_export = |re_export: &ReExport, name: &str| {
+      self
+        .resolve_hoisted_export(module_data, &re_export.specifier, name, seen)
+        .unwrap_or_else(|| {
+          ExportRef::Member(re_export.specifier.clone(), name.to_string())
+        })
+    };
+    for re_export in &data.re_exports {
+      match &re_export.name {
+        ReExportName::Named(export) if export.export_name() == name => {
+          return Some(resolve_re_export(re_export, &export.local_name));
+        }
+        ReExportName::Namespace(export_name) if export_name == name => {
+          return Some(ExportRef::Namespace(re_export.specifier.clone()));
+        }
+        ReExportName::Named(_)
+        | ReExportName::Namespace(_)
+        | ReExportName::All => {}
+      }
+    }
+    if name != "default" {
+      for re_export in &data.re_exports {
+        if matches!(re_export.name, ReExportName::All)
+          && module_data
+            .get_export_names(&re_export.specifier)
+            .iter()
+            .any(|export_name| export_name == name)
+        {
+          return Some(resolve_re_export(re_export, name));
+        }
+      }
+    }
+    None
+  }
+
+  /// Resolves the exports a module re-exports from other modules.
+  fn resolve_re_exports(
+    &self,
+    module_data: &ModuleDataCollection,
+    data: &ModuleData,
+  ) -> Vec<ExportRef> {
+    let mut refs = Vec::new();
+    for re_export in &data.re_exports {
+      match &re_export.name {
+        ReExportName::Named(export) => refs.push(self.resolve_export(
+          module_data,
+          &re_export.specifier,
+          &export.local_name,
+        )),
+        ReExportName::Namespace(_) => {
+          refs.push(ExportRef::Namespace(re_export.specifier.clone()))
+        }
+        ReExportName::All => {
+          for name in module_data.get_export_names(&re_export.specifier) {
+            refs.push(self.resolve_export(
+              module_data,
+              &re_export.specifier,
+              &name,
+            ));
+          }
+        }
+      }
+    }
+    refs
+  }
+}
+
+/// Collects the names a module uses for scope hoisting.
+struct NameCollector<'a> {
+  imports: &'a HashMap<Id, ImportedName>,
+  names: ModuleNames,
+  top_level: HashSet<Id>,
+}
+
+impl<'a> NameCollector<'a> {
+  pub fn collect(
+    module: &Module,
+    top_level_context: SyntaxContext,
+    imports: &'a HashMap<Id, ImportedName>,
+  ) -> ModuleNames {
+    let mut collector = NameCollector {
+      imports,
+      names: ModuleNames {
+        top_level_context,
+        top_level: Vec::new(),
+        nested: HashSet::new(),
+        used_imports: HashSet::new(),
+      },
+      top_level: HashSet::new(),
+    };
+    module.visit_with(&mut collector);
+    collector.names
+  }
+}
+
+impl<'a> Visit for NameCollector<'a> {
+  fn visit_ident(&mut self, ident: &Ident) {
+    // property names and labels aren't resolved by the scope analysis
+    if ident.span.ctxt == SyntaxContext::empty() {
+      return;
+    }
+    let id = ident.to_id();
+    if self.imports.contains_key(&id) {
+      // the imports are replaced
+      self.names.used_imports.insert(id);
+    } else if ident.span.ctxt == self.names.top_level_context {
+      if self.top_level.insert(id) {
+        self.names.top_level.push(ident.sym.to_string());
+      }
+    } else {
+      self.names.nested.insert(ident.sym.to_string());
+    }
+  }
+
+  // types are removed from the output
+
+  fn visit_import_decl(&mut self, _: &ImportDecl) {}
+
+  fn visit_ts_type(&mut self, _: &TsType) {}
+
+  fn visit_ts_interface_decl(&mut self, _: &TsInterfaceDecl) {}
+
+  fn visit_ts_type_alias_decl(&mut self, _: &TsTypeAliasDecl) {}
+}
+
+/// Renames the top level declarations of a hoisted module that
+/// collide with the names of other modules.
+struct TopLevelRenamer<'a> {
+  top_level_context: SyntaxContext,
+  renames: &'a HashMap<String, String>,
+}
+
+impl<'a> TopLevelRenamer<'a> {
+  fn renamed(&self, ident: &Ident) -> Option<Ident> {
+    if ident.span.ctxt != self.top_level_context {
+      return None;
+    }
+    self.renames.get(&*ident.sym).map(|new_name| Ident {
+      sym: new_name.as_str().into(),
+      ..ident.clone()
+    })
+  }
+}
+
+impl<'a> VisitMut for TopLevelRenamer<'a> {
+  fn visit_mut_ident(&mut self, ident: &mut Ident) {
+    if let Some(renamed) = self.renamed(ident) {
+      *ident = renamed;
+    }
+  }
+
+  fn visit_mut_prop(&mut self, prop: &mut Prop) {
+    // { a } -> { a: a1 }
+    if let Prop::Shorthand(ident) = prop {
+      if let Some(renamed) = self.renamed(ident) {
+        *prop = Prop::KeyValue(KeyValueProp {
+          key: PropName::Ident(ident.clone()),
+          value: Box::new(Expr::Ident(renamed)),
+        });
+        return;
+      }
+    }
+    visit_mut_prop(self, prop);
+  }
+
+  fn visit_mut_object_pat_prop(&mut self, prop: &mut ObjectPatProp) {
+    // const { a = 1 } = b; -> const { a: a1 = 1 } = b;
+    if let ObjectPatProp::Assign(assign) = prop {
+      if let Some(renamed) = self.renamed(&assign.key) {
+        let binding = Pat::Ident(BindingIdent {
+          id: renamed,
+          type_ann: None,
+        });
+        let value = match assign.value.take() {
+          Some(mut default) => {
+            default.visit_mut_with(self);
+            Pat::Assign(AssignPat {
+              span: DUMMY_SP,
+              left: Box::new(binding),
+              right: default,
+            })
+          }
+          None => binding,
+        };
+        *prop = ObjectPatProp::KeyValue(KeyValuePatProp {
+          key: PropName::Ident(assign.key.clone()),
+          value: Box::new(value),
+        });
+        return;
+      }
+    }
+    visit_mut_object_pat_prop(self, prop);
+  }
+}
+
+/// Replaces dynamic imports of local modules with an expression
+/// that resolves to the packed module's namespace object.
+struct DynamicImportTransformer<'a> {
+  replacements: &'a HashMap<String, Expr>,
+}
+
+impl<'a> VisitMut for DynamicImportTransformer<'a> {
+  fn visit_mut_expr(&mut self, expr: &mut Expr) {
+    if let Expr::Call(call_expr) = expr {
+      let maybe_replacement = get_dynamic_import_specifier(call_expr)
+        .and_then(|specifier| self.replacements.get(&*specifier.value));
+      if let Some(replacement) = maybe_replacement {
+        *expr = replacement.clone();
+        return;
+      }
+    }
+    visit_mut_expr(self, expr);
+  }
+}
+
+const JSX_FACTORY_PLACEHOLDER: &str = "__pack_jsx_factory__";
+const JSX_FRAGMENT_FACTORY_PLACEHOLDER: &str = "__pack_jsx_fragment_factory__";
+/// Statement at the start of a module that's replaced with the
+/// declaration of the JSX runtime functions it uses.
+const JSX_RUNTIME_PLACEHOLDER: &str = "__pack_jsx_runtime__";
+
+struct JsxPlaceholderReplacer<'a> {
+  jsx_modules: &'a [JsxModule],
+  runtime_imports: &'a [(Id, String)],
+  hoisted: &'a HoistedModules,
+  module_data: &'a ModuleDataCollection,
+  /// The index of the module of the code being visited, which is found
+  /// by the spans of the statements.
+  current: usize,
+  /// The runtime imports used by each module that declares them.
+  used_runtime_imports: Vec<HashSet<Id>>,
+}
+
+impl<'a> JsxPlaceholderReplacer<'a> {
+  fn with_module_at(&mut self, span: Span, visit: impl FnOnce(&mut Self)) {
+    let previous = self.current;
+    if let Some(index) = get_jsx_module_index(self.jsx_modules, span) {
+      self.current = index;
+    }
+    visit(self);
+    self.current = previous;
+  }
+}
+
+impl<'a> VisitMut for JsxPlaceholderReplacer<'a> {
+  fn visit_mut_module_item(&mut self, item: &mut ModuleItem) {
+    self.with_module_at(item.span(), |this| visit_mut_module_item(this, item));
+  }
+
+  fn visit_mut_stmt(&mut self, stmt: &mut Stmt) {
+    self.with_module_at(stmt.span(), |this| visit_mut_stmt(this, stmt));
+  }
+
+  fn visit_mut_expr(&mut self, expr: &mut Expr) {
+    let Expr::Ident(ident) = expr else {
+      visit_mut_expr(self, expr);
+      return;
+    };
+    let current = &self.jsx_modules[self.current];
+    let id = ident.to_id();
+    if &*ident.sym == JSX_FACTORY_PLACEHOLDER {
+      *expr = replace_id_to_expr(&current.factory);
+    } else if &*ident.sym == JSX_FRAGMENT_FACTORY_PLACEHOLDER {
+      *expr = replace_id_to_expr(&current.fragment_factory);
+    } else if let Some((_, imported_name)) = self
+      .runtime_imports
+      .iter()
+      .find(|(runtime_id, _)| *runtime_id == id)
+    {
+      if current.declares_runtime {
+        self.used_runtime_imports[self.current].insert(id);
+      } else {
+        // only modules with JSX reference the runtime
+        let runtime = current.runtime.as_ref().unwrap();
+        let export_ref =
+          self
+            .hoisted
+            .resolve_export(self.module_data, runtime, imported_name);
+        *expr = replace_id_to_expr(&export_ref.into_parts(self.module_data));
+      }
+    }
+  }
+}
+
+/// Replaces the runtime placeholder statement of each module with the
+/// declaration of the runtime functions that the module uses or removes
+/// it when it uses none.
+struct JsxRuntimeDeclarer<'a> {
+  jsx_modules: &'a [JsxModule],
+  runtime_imports: &'a [(Id, String)],
+  hoisted: &'a HoistedModules,
+  module_data: &'a ModuleDataCollection,
+  used_runtime_imports: Vec<HashSet<Id>>,
+}
+
+impl<'a> JsxRuntimeDeclarer<'a> {
+  fn runtime_decl(&self, span: Span) -> Option<Stmt> {
+    let index = get_jsx_module_index(self.jsx_modules, span)?;
+    let used_runtime_imports = &self.used_runtime_imports[index];
+    let runtime = self.jsx_modules[index].runtime.as_ref()?;
+    let decls = self
+      .runtime_imports
+      .iter()
+      .filter(|(id, _)| used_runtime_imports.contains(id))
+      .map(|(id, imported_name)| {
+        let export_ref =
+          self
+            .hoisted
+            .resolve_export(self.module_data, runtime, imported_name);
+        VarDeclarator {
+          span: DUMMY_SP,
+          name: Pat::Ident(BindingIdent {
+            id: Ident::new(id.0.clone(), DUMMY_SP),
+            type_ann: None,
+          }),
+          init: Some(Box::new(replace_id_to_expr(
+            &export_ref.into_parts(self.module_data),
+          ))),
+          definite: false,
+        }
+      })
+      .collect::<Vec<_>>();
+    if decls.is_empty() {
+      return None;
+    }
+    Some(Stmt::Decl(Decl::Var(Box::new(VarDecl {
+      span: DUMMY_SP,
+      kind: VarDeclKind::Const,
+      declare: false,
+      decls,
+    }))))
+  }
+
+  /// Replaces the statement when it's a runtime placeholder and returns
+  /// if it should be kept.
+  fn replace_placeholder(&self, stmt: &mut Stmt) -> bool {
+    let Stmt::Expr(expr_stmt) = stmt else {
+      return true;
+    };
+    let Expr::Ident(ident) = &*expr_stmt.expr else {
+      return true;
+    };
+    if &*ident.sym != JSX_RUNTIME_PLACEHOLDER {
+      return true;
+    }
+    match self.runtime_decl(expr_stmt.span) {
+      Some(decl) => {
+        *stmt = decl;
+        true
+      }
+      None => false,
+    }
+  }
+}
+
+impl<'a> VisitMut for JsxRuntimeDeclarer<'a> {
+  fn visit_mut_module_items(&mut self, items: &mut Vec<ModuleItem>) {
+    visit_mut_module_items(self, items);
+    items.retain_mut(|item| match item {
+      ModuleItem::Stmt(stmt) => self.replace_placeholder(stmt),
+      ModuleItem::ModuleDecl(_) => true,
+    });
+  }
+
+  fn visit_mut_stmts(&mut self, stmts: &mut Vec<Stmt>) {
+    visit_mut_stmts(self, stmts);
+    stmts.retain_mut(|stmt| self.replace_placeholder(stmt));
+  }
+}
+
+/// Gets the index of the module with JSX that contains the span.
+fn get_jsx_module_index(
+  jsx_modules: &[JsxModule],
+  span: Span,
+) -> Option<usize> {
+  if span.is_dummy() {
+    return None;
+  }
+  let index = jsx_modules
+    .partition_point(|jsx_module| jsx_module.start_pos <= span.lo)
+    .checked_sub(1)?;
+  (span.lo <= jsx_modules[index].end_pos).then_some(index)
+}
+
+/// The local modules in the order they are output.
+struct EvaluationOrder<'a> {
+  /// The modules evaluated on startup followed by the lazily evaluated
+  /// ones and then the root.
+  modules: Vec<&'a deno_graph::Module>,
+  /// Modules only reachable via a dynamic import, which are evaluated
+  /// when first imported.
+  lazy: HashSet<&'a ModuleSpecifier>,
+}
+
+/// Gets the local modules in the order they are evaluated.
+///
+/// This is a post-order traversal of the static imports, which matches
+/// how an ES module graph is evaluated. Modules only reachable via a
+/// dynamic import are evaluated lazily, except for JSON modules which
+/// have no side effects and are output first so that any module may
+/// refer to them.
+fn get_evaluation_order<'a>(
+  graph: &'a ModuleGraph,
+  root: &ModuleSpecifier,
+  include_remote: Option<&IncludeRemoteOptions>,
+) -> EvaluationOrder<'a> {
+  fn visit<'a>(
+    graph: &'a ModuleGraph,
+    specifier: &ModuleSpecifier,
+    include_remote: Option<&IncludeRemoteOptions>,
+    visited: &mut HashSet<ModuleSpecifier>,
+    order: &mut Vec<&'a deno_graph::Module>,
+  ) {
+    if !is_packed_specifier(specifier, include_remote) {
+      return;
+    }
+    let Some(module) = graph.get(specifier) else {
+      return;
+    };
+    if !visited.insert(module.specifier().clone()) {
+      return; // already evaluated or in a cycle
+    }
+    for dep in get_module_deps(graph, module, false) {
+      visit(graph, &dep, include_remote, visited, order);
+    }
+    if matches!(
+      module,
+      deno_graph::Module::Esm(_) | deno_graph::Module::Json(_)
+    ) {
+      order.push(module);
+    }
+  }
+
+  let mut visited = HashSet::new();
+  let mut order = Vec::new();
+  let Some(root_module) = graph.get(root) else {
+    return EvaluationOrder {
+      modules: order,
+      lazy: HashSet::new(),
+    };
+  };
+  visited.insert(root_module.specifier().clone());
+  for dep in get_module_deps(graph, root_module, false) {
+    visit(graph, &dep, include_remote, &mut visited, &mut order);
+  }
+  let eager_len = order.len();
+  for dep in get_module_deps(graph, root_module, true) {
+    visit(graph, &dep, include_remote, &mut visited, &mut order);
+  }
+  let mut index = 0;
+  while index < order.len() {
+    for dep in get_module_deps(graph, order[index], true) {
+      visit(graph, &dep, include_remote, &mut visited, &mut order);
+    }
+    index += 1;
+  }
+  let (lazy_json, lazy_esm): (Vec<_>, Vec<_>) = order
+    .drain(eager_len..)
+    .partition(|module| matches!(module, deno_graph::Module::Json(_)));
+  let lazy = lazy_esm.iter().map(|module| module.specifier()).collect();
+  let mut modules = lazy_json;
+  modules.extend(order);
+  modules.extend(lazy_esm);
+  modules.push(root_module);
+  EvaluationOrder { modules, lazy }
+}
+
+/// Gets the lazily evaluated modules to evaluate, in order, when the
+/// provided module is dynamically imported.
+fn get_lazy_evaluation_order(
+  graph: &ModuleGraph,
+  specifier: &ModuleSpecifier,
+  lazy_modules: &HashSet<&ModuleSpecifier>,
+) -> Vec<ModuleSpecifier> {
+  fn visit(
+    graph: &ModuleGraph,
+    specifier: ModuleSpecifier,
+    lazy_modules: &HashSet<&ModuleSpecifier>,
+    visited: &mut HashSet<ModuleSpecifier>,
+    order: &mut Vec<ModuleSpecifier>,
+  ) {
+    // the modules evaluated on startup are already evaluated
+    if !lazy_modules.contains(&specifier) || !visited.insert(specifier.clone())
+    {
+      return;
+    }
+    let module = graph.get(&specifier).unwrap();
+    for dep in get_module_deps(graph, module, false) {
+      visit(graph, dep, lazy_modules, visited, order);
+    }
+    order.push(specifier);
+  }
+
+  let mut order = Vec::new();
+  visit(
+    graph,
+    specifier.clone(),
+    lazy_modules,
+    &mut HashSet::new(),
+    &mut order,
+  );
+  order
+}
+
+/// Gets the resolved code dependencies of a module in source order.
+fn get_module_deps(
+  graph: &ModuleGraph,
+  module: &deno_graph::Module,
+  is_dynamic: bool,
+) -> Vec<ModuleSpecifier> {
+  match module {
+    deno_graph::Module::Esm(esm) => esm
+      .dependencies
+      .values()
+      .filter(|dep| dep.is_dynamic == is_dynamic)
+      .filter_map(|dep| dep.get_code())
+      .map(|specifier| graph.resolve(specifier))
+      .collect(),
+    _ => Vec::new(),
+  }
+}
+
+/// Gets the modules that evaluate asynchronously, which are the modules
+/// with a top level await and any module that depends on one of them.
+fn get_async_modules<'a>(
+  graph: &ModuleGraph,
+  evaluation_order: &[&'a deno_graph::Module],
+  module_data: &ModuleDataCollection,
+) -> HashSet<&'a ModuleSpecifier> {
+  let mut async_modules = HashSet::new();
+  for module in evaluation_order {
+    let specifier = module.specifier();
+    let has_tla = module_data
+      .get(specifier)
+      .map(|data| data.has_tla)
+      .unwrap_or(false);
+    if has_tla
+      || get_module_deps(graph, module, false)
+        .iter()
+        .any(|dep| async_modules.contains(dep))
+    {
+      async_modules.insert(specifier);
+    }
+  }
+  async_modules
+}
+
+/// Creates the items that evaluate a lazily evaluated module on its
+/// first import.
+///
+/// ```js
+/// let pack0Eval;
+/// function pack0Load() {
+///   return pack0Eval ??= (async function mod_ts() { ... })();
+/// }
+/// ```
+fn lazy_module_load_items(id: ModuleId, iife: Expr) -> Vec<ModuleItem> {
+  let evaluation_name = id.to_evaluation_code_string();
+  let eval_decl = VarDecl {
+    span: DUMMY_SP,
+    kind: VarDeclKind::Let,
+    declare: false,
+    decls: Vec::from([VarDeclarator {
+      span: DUMMY_SP,
+      name: Pat::Ident(BindingIdent {
+        id: ident(evaluation_name.clone()),
+        type_ann: None,
+      }),
+      init: None,
+      definite: false,
+    }]),
+  };
+  let load_decl = FnDecl {
+    ident: ident(id.to_load_code_string()),
+    declare: false,
+    function: Box::new(Function {
+      params: Vec::new(),
+      decorators: Vec::new(),
+      span: DUMMY_SP,
+      body: Some(BlockStmt {
+        span: DUMMY_SP,
+        stmts: Vec::from([Stmt::Return(ReturnStmt {
+          span: DUMMY_SP,
+          arg: Some(Box::new(Expr::Assign(AssignExpr {
+            span: DUMMY_SP,
+            op: AssignOp::NullishAssign,
+            left: PatOrExpr::Pat(Box::new(Pat::Ident(BindingIdent {
+              id: ident(evaluation_name),
+              type_ann: None,
+            }))),
+            right: Box::new(iife),
+          }))),
+        })]),
+      }),
+      is_generator: false,
+      is_async: false,
+      type_params: None,
+      return_type: None,
+    }),
+  };
+  Vec::from([
+    ModuleItem::Stmt(Stmt::Decl(Decl::Var(Box::new(eval_decl)))),
+    ModuleItem::Stmt(Stmt::Decl(Decl::Fn(load_decl))),
+  ])
+}
+
+/// Creates the statement that adds the exports of a package that a
+/// packed module re-exports everything from to its namespace object,
+/// which is done at runtime since the package's exports aren't known.
+///
+/// ```js
+/// for (const key in pack1) if (key !== "default" && !(key in pack0))
+///   Object.defineProperty(pack0, key, { get: ()=>pack1[key], enumerable: true });
+/// ```
+fn package_star_re_export_stmt(namespace: String, package: String) -> Stmt {
+  let key = || Box::new(Expr::Ident(ident("key".to_string())));
+  let is_not_default = Expr::Bin(BinExpr {
+    span: DUMMY_SP,
+    op: BinaryOp::NotEqEq,
+    left: key(),
+    right: Box::new(Expr::Lit(Lit::Str(Str {
+      span: DUMMY_SP,
+      value: "default".into(),
+      raw: None,
+    }))),
+  });
+  // explicit exports take precedence
+  let is_not_exported = Expr::Unary(UnaryExpr {
+    span: DUMMY_SP,
+    op: UnaryOp::Bang,
+    arg: Box::new(Expr::Paren(ParenExpr {
+      span: DUMMY_SP,
+      expr: Box::new(Expr::Bin(BinExpr {
+        span: DUMMY_SP,
+        op: BinaryOp::In,
+        left: key(),
+        right: Box::new(Expr::Ident(ident(namespace.clone()))),
+      })),
+    })),
+  });
+  let getter = Expr::Arrow(ArrowExpr {
+    span: DUMMY_SP,
+    params: Vec::new(),
+    body: Box::new(BlockStmtOrExpr::Expr(Box::new(Expr::Member(MemberExpr {
+      span: DUMMY_SP,
+      obj: Box::new(Expr::Ident(ident(package.clone()))),
+      prop: MemberProp::Computed(ComputedPropName {
+        span: DUMMY_SP,
+        expr: key(),
+      }),
+    })))),
+    is_async: false,
+    is_generator: false,
+    type_params: None,
+    return_type: None,
+  });
+  let define_property = Expr::Call(CallExpr {
+    span: DUMMY_SP,
+    callee: Callee::Expr(Box::new(Expr::Member(member_x_y(
+      "Object".to_string(),
+      "defineProperty".to_string(),
+    )))),
+    args: Vec::from([
+      ExprOrSpread {
+        spread: None,
+        expr: Box::new(Expr::Ident(ident(namespace))),
+      },
+      ExprOrSpread {
+        spread: None,
+        expr: key(),
+      },
+      ExprOrSpread {
+        spread: None,
+        expr: Box::new(Expr::Object(ObjectLit {
+          span: DUMMY_SP,
+          props: Vec::from([
+            PropOrSpread::Prop(Box::new(Prop::KeyValue(KeyValueProp {
+              key: prop_name("get".to_string()),
+              value: Box::new(getter),
+            }))),
+            PropOrSpread::Prop(Box::new(Prop::KeyValue(KeyValueProp {
+              key: prop_name("enumerable".to_string()),
+              value: Box::new(Expr::Lit(Lit::Bool(Bool {
+                span: DUMMY_SP,
+                value: true,
+              }))),
+            }))),
+          ]),
+        })),
+      },
+    ]),
+    type_args: None,
+  });
+  Stmt::ForIn(ForInStmt {
+    span: DUMMY_SP,
+    left: ForHead::VarDecl(Box::new(VarDecl {
+      span: DUMMY_SP,
+      kind: VarDeclKind::Const,
+      declare: false,
+      decls: Vec::from([VarDeclarator {
+        span: DUMMY_SP,
+        name: Pat::Ident(BindingIdent {
+          id: ident("key".to_string()),
+          type_ann: None,
+        }),
+        init: None,
+        definite: false,
+      }]),
+    })),
+    right: Box::new(Expr::Ident(ident(package))),
+    body: Box::new(Stmt::If(IfStmt {
+      span: DUMMY_SP,
+      test: Box::new(Expr::Bin(BinExpr {
+        span: DUMMY_SP,
+        op: BinaryOp::LogicalAnd,
+        left: Box::new(is_not_default),
+        right: Box::new(is_not_exported),
+      })),
+      cons: Box::new(Stmt::Expr(ExprStmt {
+        span: DUMMY_SP,
+        expr: Box::new(define_property),
+      })),
+      alt: None,
+    })),
+  })
+}
+
+/// Creates `promise.then(on_fulfilled)`.
+fn promise_then(promise: Expr, on_fulfilled: Expr) -> Expr {
+  Expr::Call(CallExpr {
+    span: DUMMY_SP,
+    callee: Callee::Expr(Box::new(Expr::Member(MemberExpr {
+      span: DUMMY_SP,
+      obj: Box::new(promise),
+      prop: member_prop("then".to_string()),
+    }))),
+    args: Vec::from([ExprOrSpread {
+      spread: None,
+      expr: Box::new(on_fulfilled),
+    }]),
+    type_args: None,
+  })
+}
+
+/// Creates `() => expr`.
+fn arrow_fn_returning(expr: Expr) -> Expr {
+  Expr::Arrow(ArrowExpr {
+    span: DUMMY_SP,
+    params: Vec::new(),
+    body: Box::new(BlockStmtOrExpr::Expr(Box::new(expr))),
+    is_async: false,
+    is_generator: false,
+    type_params: None,
+    return_type: None,
+  })
+}
+
+/// Creates `import(import.meta.url)`, which imports the output itself.
+fn import_meta_url_import() -> Expr {
+  Expr::Call(CallExpr {
+    span: DUMMY_SP,
+    callee: Callee::Import(Import { span: DUMMY_SP }),
+    args: Vec::from([ExprOrSpread {
+      spread: None,
+      expr: Box::new(Expr::Member(MemberExpr {
+        span: DUMMY_SP,
+        obj: Box::new(Expr::MetaProp(MetaPropExpr {
+          span: DUMMY_SP,
+          kind: MetaPropKind::ImportMeta,
+        })),
+        prop: member_prop("url".to_string()),
+      })),
+    }]),
+    type_args: None,
+  })
+}
+
+fn await_module_evaluations(evaluations: Vec<Expr>) -> Stmt {
+  // await pack0Eval; or await Promise.all([pack0Eval, pack1Eval]);
+  let arg = if evaluations.len() == 1 {
+    evaluations.into_iter().next().unwrap()
+  } else {
+    Expr::Call(CallExpr {
+      span: DUMMY_SP,
+      callee: Callee::Expr(Box::new(Expr::Member(member_x_y(
+        "Promise".to_string(),
+        "all".to_string(),
+      )))),
+      args: Vec::from([ExprOrSpread {
+        spread: None,
+        expr: Box::new(Expr::Array(ArrayLit {
+          span: DUMMY_SP,
+          elems: evaluations
+            .into_iter()
+            .map(|expr| {
+              Some(ExprOrSpread {
+                spread: None,
+                expr: Box::new(expr),
+              })
+            })
+            .collect(),
+        })),
+      }]),
+      type_args: None,
+    })
+  };
+  Stmt::Expr(ExprStmt {
+    span: DUMMY_SP,
+    expr: Box::new(Expr::Await(AwaitExpr {
+      span: DUMMY_SP,
+      arg: Box::new(arg),
+    })),
+  })
+}
+
+fn get_emit_options(jsx: &JsxOptions) -> EmitOptions {
+  let defaults = EmitOptions::default();
+  let (transform_jsx, jsx_automatic, precompile_jsx) = match jsx.jsx {
+    JsxTransform::React => (true, false, false),
+    JsxTransform::ReactJsx | JsxTransform::ReactJsxDev => (true, true, false),
+    JsxTransform::Precompile => (false, false, true),
+  };
+  EmitOptions {
+    transform_jsx,
+    jsx_automatic,
+    jsx_development: jsx.jsx == JsxTransform::ReactJsxDev,
+    precompile_jsx,
+    jsx_factory: jsx.jsx_factory.clone().unwrap_or(defaults.jsx_factory),
+    jsx_fragment_factory: jsx
+      .jsx_fragment_factory
+      .clone()
+      .unwrap_or(defaults.jsx_fragment_factory),
+    jsx_import_source: jsx.automatic_import_source().map(ToOwned::to_owned),
+    ..defaults
+  }
+}
+
+/// Options for transpiling the output, which uses placeholders for the
+/// classic JSX factories because they differ between modules.
+fn get_transpile_options(emit_options: &EmitOptions) -> EmitOptions {
+  EmitOptions {
+    jsx_factory: JSX_FACTORY_PLACEHOLDER.to_string(),
+    jsx_fragment_factory: JSX_FRAGMENT_FACTORY_PLACEHOLDER.to_string(),
+    ..emit_options.clone()
+  }
+}
+
+/// The classic JSX factories of a module.
+struct JsxFactories {
+  factory: JsxFactory,
+  fragment_factory: JsxFactory,
+}
+
+/// A classic JSX factory (ex. `React.createElement`).
+struct JsxFactory {
+  parts: Vec<String>,
+  /// The import the first identifier refers to or `None` for a global.
+  import_id: Option<Id>,
+}
+
+impl JsxFactory {
+  pub fn new(
+    text: &str,
+    imports: &HashMap<Id, ImportedName>,
+    top_level_context: SyntaxContext,
+  ) -> Self {
+    let parts = text.split('.').map(ToOwned::to_owned).collect::<Vec<_>>();
+    // the imports are all declared at the top level
+    let id = (parts[0].as_str().into(), top_level_context);
+    Self {
+      import_id: imports.contains_key(&id).then_some(id),
+      parts,
+    }
+  }
+
+  /// Gets the parts with the first identifier replaced when it refers
+  /// to an import (ex. `React.createElement` becomes
+  /// `pack0.default.createElement`).
+  pub fn replaced_parts(
+    &self,
+    replace_ids: &HashMap<Id, Vec<String>>,
+  ) -> Vec<String> {
+    let mut parts = self.parts.clone();
+    if let Some(import_parts) =
+      self.import_id.as_ref().and_then(|id| replace_ids.get(id))
+    {
+      parts.splice(0..1, import_parts.iter().cloned());
+    }
+    parts
+  }
+}
+
+/// The JSX pragmas in the leading comments of a module
+/// (ex. `/** @jsx h */`), which override the JSX options for it.
+#[derive(Default)]
+struct JsxPragmas {
+  runtime: Option<JsxPragma>,
+  import_source: Option<JsxPragma>,
+  factory: Option<JsxPragma>,
+  fragment_factory: Option<JsxPragma>,
+}
+
+struct JsxPragma {
+  name: &'static str,
+  value: String,
+  /// Range of the comment the pragma is in.
+  range: SourceRange,
+}
+
+impl JsxPragmas {
+  pub fn parse(comments: &[Comment]) -> Self {
+    let mut pragmas = Self::default();
+    for comment in comments {
+      if comment.kind != CommentKind::Block {
+        continue;
+      }
+      for line in comment.text.lines() {
+        let mut words =
+          line.trim_start().trim_start_matches('*').split_whitespace();
+        while let (Some(name), Some(value)) = (words.next(), words.next()) {
+          let (name, pragma) = match name {
+            "@jsxRuntime" => ("@jsxRuntime", &mut pragmas.runtime),
+            "@jsxImportSource" => {
+              ("@jsxImportSource", &mut pragmas.import_source)
+            }
+            "@jsx" => ("@jsx", &mut pragmas.factory),
+            "@jsxFrag" => ("@jsxFrag", &mut pragmas.fragment_factory),
+            _ => break,
+          };
+          *pragma = Some(JsxPragma {
+            name,
+            value: value.to_string(),
+            range: comment.range(),
+          });
+        }
+      }
+    }
+    pragmas
+  }
+}
+
+/// Replaces the JSX factory placeholders and the references to the
+/// JSX runtime imports that the transforms added to the output with
+/// the ones of the module the JSX is in.
+///
+/// The runtime is a dependency of each module in the graph, so it's
+/// either packed or imported externally like any other module.
+fn replace_jsx_placeholders(
+  module: &mut Module,
+  jsx_modules: &[JsxModule],
+  hoisted: &HoistedModules,
+  module_data: &ModuleDataCollection,
+) {
+  let mut runtime_imports = Vec::new();
+  // the transforms add the only imports in the transpiled output
+  module.body.retain(|item| {
+    let ModuleItem::ModuleDecl(ModuleDecl::Import(import)) = item else {
+      return true;
+    };
+    for import_specifier in &import.specifiers {
+      // the transforms only add named imports
+      let ImportSpecifier::Named(named) = import_specifier else {
+        unreachable!();
+      };
+      let imported_name = match &named.imported {
+        Some(name) => module_export_name_text(name),
+        None => named.local.sym.to_string(),
+      };
+      runtime_imports.push((named.local.to_id(), imported_name));
+    }
+    false
+  });
+  let mut replacer = JsxPlaceholderReplacer {
+    jsx_modules,
+    runtime_imports: &runtime_imports,
+    hoisted,
+    module_data,
+    current: 0,
+    used_runtime_imports: vec![HashSet::new(); jsx_modules.len()],
+  };
+  module.visit_mut_with(&mut replacer);
+  module.visit_mut_with(&mut JsxRuntimeDeclarer {
+    jsx_modules,
+    runtime_imports: &runtime_imports,
+    hoisted,
+    module_data,
+    used_runtime_imports: replacer.used_runtime_imports,
+  });
+}
+
+/// Gets if the item is the function of a module that has no
+/// statements after being transpiled.
+fn is_empty_module_fn(item: &ModuleItem) -> bool {
+  let ModuleItem::Stmt(Stmt::Expr(expr_stmt)) = item else {
+    return false;
+  };
+  let Expr::Call(call_expr) = &*expr_stmt.expr else {
+    return false;
+  };
+  let Callee::Expr(callee) = &call_expr.callee else {
+    return false;
+  };
+  match callee.unwrap_parens() {
+    Expr::Fn(fn_expr) => fn_expr
+      .function
+      .body
+      .as_ref()
+      .map(|body| body.stmts.is_empty())
+      .unwrap_or(false),
+    _ => false,
+  }
+}
+
+/// Gets the name of the variable that an enum or namespace declaration
+/// is transpiled to.
+fn enum_or_namespace_name(decl: &Decl) -> Option<String> {
+  match decl {
+    Decl::TsEnum(decl) if !decl.declare => Some(decl.id.sym.to_string()),
+    Decl::TsModule(decl) if !decl.declare => match &decl.id {
+      TsModuleName::Ident(ident) => Some(ident.sym.to_string()),
+      TsModuleName::Str(_) => None,
+    },
+    _ => None,
+  }
+}
+
+/// Makes the transpiled output the same as when each module was
+/// transpiled on its own, which the transforms output differently now
+/// that the modules are transpiled in one module.
+///
+/// Enums and namespaces are declared with `let` at the top level and
+/// with `var` at the top level of a module's function, and the function
+/// of an async module is in parentheses when called.
+fn restore_separately_transpiled_output(
+  module: &mut Module,
+  top_level_decl_names: &HashSet<String>,
+  module_fn_decl_names: &HashMap<String, HashSet<String>>,
+) {
+  for item in &mut module.body {
+    if let ModuleItem::Stmt(Stmt::Decl(Decl::Var(var_decl)))
+    | ModuleItem::ModuleDecl(ModuleDecl::ExportDecl(ExportDecl {
+      decl: Decl::Var(var_decl),
+      ..
+    })) = item
+    {
+      set_decl_var_kind(var_decl, top_level_decl_names, VarDeclKind::Let);
+    }
+    let Some(call_expr) = module_fn_call_mut(item) else {
+      continue;
+    };
+    let Callee::Expr(callee) = &mut call_expr.callee else {
+      continue;
+    };
+    let Expr::Fn(fn_expr) = callee.unwrap_parens_mut() else {
+      continue;
+    };
+    let Some(decl_names) = fn_expr
+      .ident
+      .as_ref()
+      .and_then(|ident| module_fn_decl_names.get(&*ident.sym))
+    else {
+      continue;
+    };
+    if let Some(body) = &mut fn_expr.function.body {
+      for stmt in &mut body.stmts {
+        if let Stmt::Decl(Decl::Var(var_decl)) = stmt {
+          set_decl_var_kind(var_decl, decl_names, VarDeclKind::Var);
+        }
+      }
+    }
+    if !matches!(**callee, Expr::Paren(_)) {
+      *callee = Box::new(Expr::Paren(ParenExpr {
+        span: DUMMY_SP,
+        expr: callee.take(),
+      }));
+    }
+  }
+
+  fn set_decl_var_kind(
+    var_decl: &mut VarDecl,
+    decl_names: &HashSet<String>,
+    kind: VarDeclKind,
+  ) {
+    if let [VarDeclarator {
+      name: Pat::Ident(binding_ident),
+      init: None,
+      ..
+    }] = var_decl.decls.as_slice()
+    {
+      if decl_names.contains(&*binding_ident.id.sym) {
+        var_decl.kind = kind;
+      }
+    }
+  }
+}
+
+/// Gets the call of a module's function in the output, which is either
+/// a statement, the evaluation promise of an async module or what the
+/// load function of a lazily evaluated module assigns.
+fn module_fn_call_mut(item: &mut ModuleItem) -> Option<&mut CallExpr> {
+  let ModuleItem::Stmt(stmt) = item else {
+    return None;
+  };
+  let expr = match stmt {
+    Stmt::Expr(expr_stmt) => &mut expr_stmt.expr,
+    Stmt::Decl(Decl::Var(var_decl)) if var_decl.decls.len() == 1 => {
+      var_decl.decls[0].init.as_mut()?
+    }
+    Stmt::Decl(Decl::Fn(fn_decl)) => {
+      match fn_decl.function.body.as_mut()?.stmts.first_mut()? {
+        Stmt::Return(ReturnStmt { arg: Some(arg), .. }) => match &mut **arg {
+          Expr::Assign(assign_expr) => &mut assign_expr.right,
+          _ => return None,
+        },
+        _ => return None,
+      }
+    }
+    _ => return None,
+  };
+  match &mut **expr {
+    Expr::Call(call_expr) => Some(call_expr),
+    _ => None,
+  }
+}
+
+/// Gets if the item is a declaration that's removed when transpiled.
+fn is_type_declaration(item: &ModuleItem) -> bool {
+  match item {
+    ModuleItem::Stmt(Stmt::Decl(decl)) => match decl {
+      Decl::TsInterface(_) | Decl::TsTypeAlias(_) => true,
+      Decl::Class(decl) => decl.declare,
+      Decl::Fn(decl) => decl.declare,
+      Decl::Var(decl) => decl.declare,
+      Decl::TsEnum(decl) => decl.declare,
+      Decl::TsModule(decl) => decl.declare,
+      Decl::Using(_) => false,
+    },
+    _ => false,
+  }
+}
+
+fn ts_entity_name_to_expr(entity_name: &TsEntityName) -> Expr {
+  match entity_name {
+    TsEntityName::Ident(ident) => Expr::Ident(ident.clone()),
+    TsEntityName::TsQualifiedName(qualified_name) => Expr::Member(MemberExpr {
+      span: DUMMY_SP,
+      obj: Box::new(ts_entity_name_to_expr(&qualified_name.left)),
+      prop: MemberProp::Ident(qualified_name.right.clone()),
+    }),
+  }
 }
 
 fn replace_id_to_expr(parts: &[String]) -> Expr {
@@ -1162,12 +3565,52 @@ fn replace_id_to_expr(parts: &[String]) -> Expr {
     final_expr = Expr::Member(MemberExpr {
       span: DUMMY_SP,
       obj: Box::new(final_expr),
-      prop: MemberProp::Ident(ident(parts.pop_front().unwrap().clone())),
+      prop: member_prop(parts.pop_front().unwrap().clone()),
     });
   }
   final_expr
 }
 
+/// Gets the JSX element name for the provided replace id parts or
+/// `None` when a part isn't a valid identifier (ex. `pack0["a-b"]`).
+fn replace_id_to_jsx(parts: &[String]) -> Option<JSXElementName> {
+  if !parts.iter().all(|part| is_valid_ident(part)) {
+    return None;
+  }
+  let mut name = JSXElementName::Ident(ident(parts[0].clone()));
+  for part in &parts[1..] {
+    let obj = match name {
+      JSXElementName::Ident(ident) => JSXObject::Ident(ident),
+      JSXElementName::JSXMemberExpr(member) => {
+        JSXObject::JSXMemberExpr(Box::new(member))
+      }
+      JSXElementName::JSXNamespacedName(_) => unreachable!(),
+    };
+    name = JSXElementName::JSXMemberExpr(JSXMemberExpr {
+      obj,
+      prop: ident(part.clone()),
+    });
+  }
+  Some(name)
+}
+
+fn get_displayed_specifier<'a>(
+  root_dir: Option<&str>,
+  specifier: &'a ModuleSpecifier,
+) -> &'a str {
+  match root_dir {
+    Some(prefix) => {
+      if specifier.scheme() == "file" {
+        let specifier = specifier.as_str();
+        specifier.strip_prefix(prefix).unwrap_or(specifier)
+      } else {
+        specifier.as_str()
+      }
+    }
+    None => specifier.as_str(),
+  }
+}
+
 fn get_root_dir<'a>(
   specifiers: impl Iterator<Item = &'a ModuleSpecifier>,
 ) -> Option<&'a str> {
@@ -0,0 +1,53 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use deno_ast::swc::ast::Module;
+use deno_ast::swc::ast::Program;
+use deno_ast::ModuleSpecifier;
+use deno_ast::ParsedSource;
+use deno_graph::ParsedSourceStore;
+
+/// Stores the sources parsed while building the graph so that every
+/// output uses the same parse of each module.
+#[derive(Default)]
+pub struct ParsedSources {
+  store: RefCell<HashMap<ModuleSpecifier, ParsedSource>>,
+}
+
+impl ParsedSources {
+  /// Removes the parsed source from the store, which allows the last
+  /// output that uses it to take its AST without cloning it.
+  pub fn take(&self, specifier: &ModuleSpecifier) -> Option<ParsedSource> {
+    self.store.borrow_mut().remove(specifier)
+  }
+}
+
+impl ParsedSourceStore for ParsedSources {
+  fn set_parsed_source(
+    &self,
+    specifier: ModuleSpecifier,
+    parsed_source: ParsedSource,
+  ) -> Option<ParsedSource> {
+    self.store.borrow_mut().insert(specifier, parsed_source)
+  }
+
+  fn get_parsed_source(
+    &self,
+    specifier: &ModuleSpecifier,
+  ) -> Option<ParsedSource> {
+    self.store.borrow().get(specifier).cloned()
+  }
+}
+
+/// Gets the module of the parsed source, which is moved out when
+/// nothing else references the parsed source and cloned otherwise.
+pub fn into_module(parsed_source: ParsedSource) -> Module {
+  let program = parsed_source.program();
+  drop(parsed_source);
+  let program = Arc::try_unwrap(program).unwrap_or_else(|p| (*p).clone());
+  match program {
+    Program::Module(module) => module,
+    Program::Script(_) => unreachable!(),
+  }
+}
@@ -0,0 +1,255 @@
+use std::cell::RefCell;
+use std::io::Write;
+
+use deno_ast::ModuleSpecifier;
+use serde::Deserialize;
+use serde_json::json;
+
+use crate::Diagnostic;
+use crate::DiagnosticCode;
+use crate::DiagnosticSeverity;
+use crate::LineAndColumnDisplay;
+use crate::Reporter;
+
+const SARIF_SCHEMA: &str = "https://json.schemastore.org/sarif-2.1.0.json";
+const SARIF_SRC_ROOT: &str = "SRCROOT";
+
+#[derive(Debug, Clone, Copy, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum DiagnosticsFormat {
+  /// One JSON serialized `Diagnostic` per line.
+  JsonLines,
+  /// A SARIF 2.1.0 log with a result per diagnostic.
+  Sarif,
+}
+
+/// Writes each diagnostic as a line of JSON.
+pub struct JsonLinesReporter<W: Write> {
+  writer: RefCell<W>,
+}
+
+impl<W: Write> JsonLinesReporter<W> {
+  pub fn new(writer: W) -> Self {
+    Self {
+      writer: RefCell::new(writer),
+    }
+  }
+
+  pub fn into_inner(self) -> W {
+    self.writer.into_inner()
+  }
+}
+
+impl<W: Write> Reporter for JsonLinesReporter<W> {
+  fn diagnostic(&self, diagnostic: Diagnostic) {
+    let mut writer = self.writer.borrow_mut();
+    // the reporter can't surface errors, so a closed output (ex. a
+    // broken pipe) only drops the remaining lines
+    let _ = serde_json::to_writer(&mut *writer, &diagnostic)
+      .map_err(std::io::Error::from)
+      .and_then(|_| writeln!(writer));
+  }
+}
+
+/// Collects the diagnostics into a SARIF 2.1.0 log.
+#[derive(Default)]
+pub struct SarifReporter {
+  base_url: Option<ModuleSpecifier>,
+  diagnostics: RefCell<Vec<Diagnostic>>,
+}
+
+impl SarifReporter {
+  /// Creates a reporter that outputs the locations relative to the
+  /// base URL (ex. the repository root) when provided.
+  pub fn new(base_url: Option<ModuleSpecifier>) -> Self {
+    Self {
+      base_url,
+      diagnostics: Default::default(),
+    }
+  }
+
+  pub fn to_json(&self) -> serde_json::Value {
+    let rules = DiagnosticCode::ALL
+      .iter()
+      .map(|code| {
+        json!({
+          "id": code.as_str(),
+          "name": code.name(),
+          "shortDescription": { "text": code.description() },
+          "defaultConfiguration": {
+            "level": sarif_level(code.default_severity()),
+          },
+        })
+      })
+      .collect::<Vec<_>>();
+    let results = self
+      .diagnostics
+      .borrow()
+      .iter()
+      .map(|diagnostic| {
+        let mut result = json!({
+          "ruleId": diagnostic.code.as_str(),
+          "ruleIndex": DiagnosticCode::ALL
+            .iter()
+            .position(|code| *code == diagnostic.code)
+            .unwrap(),
+          "level": sarif_level(diagnostic.severity),
+          "message": { "text": diagnostic.message },
+          "locations": [self.location(
+            &diagnostic.specifier,
+            diagnostic.line_and_column.as_ref(),
+            diagnostic.end_line_and_column.as_ref(),
+          )],
+        });
+        if !diagnostic.related.is_empty() {
+          result["relatedLocations"] = diagnostic
+            .related
+            .iter()
+            .enumerate()
+            .map(|(index, related)| {
+              let mut location = self.location(
+                &related.specifier,
+                related.line_and_column.as_ref(),
+                related.end_line_and_column.as_ref(),
+              );
+              location["id"] = json!(index);
+              location["message"] = json!({ "text": related.message });
+              location
+            })
+            .collect();
+        }
+        if let Some(hint) = &diagnostic.hint {
+          result["properties"] = json!({ "hint": hint });
+        }
+        result
+      })
+      .collect::<Vec<_>>();
+    let mut run = json!({
+      "tool": {
+        "driver": {
+          "name": "libpack",
+          "informationUri": "https://github.com/dsherret/libpack",
+          "rules": rules,
+        },
+      },
+      "results": results,
+    });
+    if let Some(base_url) = &self.base_url {
+      run["originalUriBaseIds"] = json!({
+        SARIF_SRC_ROOT: { "uri": base_url.as_str() },
+      });
+    }
+    json!({
+      "$schema": SARIF_SCHEMA,
+      "version": "2.1.0",
+      "runs": [run],
+    })
+  }
+
+  pub fn to_json_string(&self) -> String {
+    serde_json::to_string_pretty(&self.to_json()).unwrap()
+  }
+
+  fn location(
+    &self,
+    specifier: &ModuleSpecifier,
+    start: Option<&LineAndColumnDisplay>,
+    end: Option<&LineAndColumnDisplay>,
+  ) -> serde_json::Value {
+    let relative_path = self.base_url.as_ref().and_then(|base_url| {
+      specifier
+        .as_str()
+        .strip_prefix(base_url.as_str())
+        .filter(|_| base_url.as_str().ends_with('/'))
+    });
+    let artifact_location = match relative_path {
+      Some(path) => json!({ "uri": path, "uriBaseId": SARIF_SRC_ROOT }),
+      None => json!({ "uri": specifier.as_str() }),
+    };
+    let mut physical_location =
+      json!({ "artifactLocation": artifact_location });
+    if let Some(start) = start {
+      let mut region = json!({
+        "startLine": start.line_number,
+        "startColumn": start.column_number,
+      });
+      if let Some(end) = end {
+        region["endLine"] = json!(end.line_number);
+        region["endColumn"] = json!(end.column_number);
+      }
+      physical_location["region"] = region;
+    }
+    json!({ "physicalLocation": physical_location })
+  }
+}
+
+impl Reporter for SarifReporter {
+  fn diagnostic(&self, diagnostic: Diagnostic) {
+    self.diagnostics.borrow_mut().push(diagnostic);
+  }
+}
+
+fn sarif_level(severity: DiagnosticSeverity) -> &'static str {
+  match severity {
+    DiagnosticSeverity::Error => "error",
+    DiagnosticSeverity::Warning => "warning",
+    DiagnosticSeverity::Info => "note",
+  }
+}
+
+/// Collects the diagnostics in the requested format for
+/// `PackOutput::diagnostics_report`.
+pub(crate) enum FormattedReport {
+  JsonLines(JsonLinesReporter<Vec<u8>>),
+  Sarif(SarifReporter),
+}
+
+impl FormattedReport {
+  pub fn new(
+    format: DiagnosticsFormat,
+    base_url: Option<ModuleSpecifier>,
+  ) -> Self {
+    match format {
+      DiagnosticsFormat::JsonLines => {
+        FormattedReport::JsonLines(JsonLinesReporter::new(Vec::new()))
+      }
+      DiagnosticsFormat::Sarif => {
+        FormattedReport::Sarif(SarifReporter::new(base_url))
+      }
+    }
+  }
+
+  pub fn into_string(self) -> String {
+    match self {
+      FormattedReport::JsonLines(reporter) => {
+        String::from_utf8(reporter.into_inner()).unwrap()
+      }
+      FormattedReport::Sarif(reporter) => reporter.to_json_string(),
+    }
+  }
+}
+
+impl Reporter for FormattedReport {
+  fn diagnostic(&self, diagnostic: Diagnostic) {
+    match self {
+      FormattedReport::JsonLines(reporter) => reporter.diagnostic(diagnostic),
+      FormattedReport::Sarif(reporter) => reporter.diagnostic(diagnostic),
+    }
+  }
+}
+
+/// Forwards diagnostics to the provided reporter and to the
+/// formatted report when requested.
+pub(crate) struct TeeReporter<'a, TReporter: Reporter> {
+  pub inner: &'a TReporter,
+  pub report: Option<&'a FormattedReport>,
+}
+
+impl<'a, TReporter: Reporter> Reporter for TeeReporter<'a, TReporter> {
+  fn diagnostic(&self, diagnostic: Diagnostic) {
+    if let Some(report) = self.report {
+      report.diagnostic(diagnostic.clone());
+    }
+    self.inner.diagnostic(diagnostic);
+  }
+}
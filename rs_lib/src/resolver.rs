@@ -0,0 +1,355 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::collections::HashSet;
+
+use anyhow::bail;
+use anyhow::Context;
+use deno_ast::ModuleSpecifier;
+use deno_graph::source::CacheSetting;
+use deno_graph::source::LoadResponse;
+use deno_graph::source::Loader;
+use deno_graph::source::ResolutionMode;
+use deno_graph::source::ResolveError;
+use deno_graph::ModuleGraphError;
+use import_map::ImportMap;
+use import_map::SpecifierMap;
+
+use crate::JsxOptions;
+
+/// An entry in the top level `imports` or in a scope of an import map,
+/// identified by the keys as written in the file.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ImportMapEntryRef {
+  pub scope: Option<String>,
+  pub key: String,
+}
+
+/// A specifier the import map resolved along with the entry used.
+#[derive(Debug, Clone)]
+pub struct ImportMapResolution {
+  pub referrer: ModuleSpecifier,
+  pub resolved: ModuleSpecifier,
+  pub entry: ImportMapEntryRef,
+}
+
+#[derive(Debug)]
+pub struct ImportMapResolver {
+  pub import_map: ImportMap,
+  /// The file each entry came from, which differs from the loaded file
+  /// for a deno.json that points to an import map with `"importMap"`.
+  entry_sources: HashMap<ImportMapEntryRef, ModuleSpecifier>,
+  resolutions: RefCell<Vec<ImportMapResolution>>,
+  /// JSX settings from the config file's `compilerOptions`.
+  pub jsx: Option<JsxOptions>,
+}
+
+impl ImportMapResolver {
+  /// Loads an import map file or a deno.json/deno.jsonc config file.
+  ///
+  /// When the config file has an `"importMap"` field, the referenced
+  /// import map is loaded and merged with the config's own `"imports"`
+  /// and `"scopes"`, which take precedence.
+  pub async fn load(
+    import_map_url: &ModuleSpecifier,
+    loader: &mut dyn Loader,
+  ) -> anyhow::Result<Self> {
+    let (specifier, value) = load_jsonc(import_map_url, loader).await?;
+    let external_url = match value.get("importMap") {
+      Some(serde_json::Value::String(url)) => {
+        Some(specifier.join(url).with_context(|| {
+          format!("Invalid \"importMap\" value in {}", specifier)
+        })?)
+      }
+      Some(_) => {
+        bail!("Expected \"importMap\" to be a string in {}", specifier)
+      }
+      None => None,
+    };
+    let jsx = match value.get("compilerOptions") {
+      Some(compiler_options) => Some(
+        serde_json::from_value::<JsxOptions>(compiler_options.clone())
+          .with_context(|| {
+            format!("Invalid JSX \"compilerOptions\" in {}", specifier)
+          })?,
+      ),
+      None => None,
+    };
+    let mut import_map =
+      import_map::parse_from_value(&specifier, value)?.import_map;
+    let mut entry_sources = get_entry_refs(&import_map)
+      .into_iter()
+      .map(|entry| (entry, specifier.clone()))
+      .collect::<HashMap<_, _>>();
+    if let Some(external_url) = external_url {
+      let (external_specifier, external_value) =
+        load_jsonc(&external_url, loader).await.with_context(|| {
+          format!(
+            "Error loading import map {} referenced in {}.",
+            external_url, specifier
+          )
+        })?;
+      let external_map =
+        import_map::parse_from_value(&external_specifier, external_value)?
+          .import_map;
+      merge_import_map(&mut import_map, &external_map)?;
+      for entry in get_entry_refs(&import_map) {
+        entry_sources
+          .entry(entry)
+          .or_insert_with(|| external_specifier.clone());
+      }
+    }
+    Ok(ImportMapResolver {
+      import_map,
+      entry_sources,
+      resolutions: Default::default(),
+      jsx,
+    })
+  }
+
+  /// Takes the specifiers resolved with an import map entry so far.
+  pub fn take_resolutions(&self) -> Vec<ImportMapResolution> {
+    self.resolutions.take()
+  }
+
+  /// Gets the file the entry came from.
+  pub fn entry_source(&self, entry: &ImportMapEntryRef) -> &ModuleSpecifier {
+    self
+      .entry_sources
+      .get(entry)
+      .unwrap_or_else(|| self.import_map.base_url())
+  }
+
+  /// Adds which import map entry resolved the specifier that failed
+  /// to load to a module graph error.
+  pub fn add_graph_error_context(
+    &self,
+    err: ModuleGraphError,
+  ) -> anyhow::Error {
+    let ModuleGraphError::ModuleError(module_err) = &err else {
+      return err.into();
+    };
+    let resolutions = self.resolutions.borrow();
+    let maybe_resolution = resolutions.iter().find(|resolution| {
+      resolution.resolved == *module_err.specifier()
+        && module_err
+          .maybe_referrer()
+          .map(|range| range.specifier == resolution.referrer)
+          .unwrap_or(true)
+    });
+    match maybe_resolution {
+      Some(resolution) => {
+        let entry = &resolution.entry;
+        let scope_text = match &entry.scope {
+          Some(scope) => format!(" in scope \"{}\"", scope),
+          None => String::new(),
+        };
+        let message = format!(
+          "Import map entry \"{}\"{} from {} mapped to {}",
+          entry.key,
+          scope_text,
+          self.entry_source(entry),
+          resolution.resolved,
+        );
+        // the graph error's source has the same message, so don't
+        // include it in the chain
+        anyhow::anyhow!("{}", err).context(message)
+      }
+      None => err.into(),
+    }
+  }
+
+  /// Creates an import map with only the provided entries that map
+  /// to a remote address. Local addresses are packed into the output,
+  /// so they're not necessary.
+  pub fn to_minimal_json(
+    &self,
+    entries: &HashSet<ImportMapEntryRef>,
+  ) -> String {
+    let mut minimal = ImportMap::new(self.import_map.base_url().clone());
+    let mut append = |scope: Option<&str>, map: &SpecifierMap| {
+      for entry in map.entries() {
+        let is_used = entries.contains(&ImportMapEntryRef {
+          scope: scope.map(ToOwned::to_owned),
+          key: entry.raw_key.to_string(),
+        });
+        let (Some(value), Some(raw_value)) = (entry.value, entry.raw_value)
+        else {
+          continue;
+        };
+        if !is_used || value.scheme() == "file" {
+          continue;
+        }
+        let target = match scope {
+          Some(scope) => minimal.get_or_append_scope_mut(scope).unwrap(),
+          None => minimal.imports_mut(),
+        };
+        // the base url is the same, so these were already validated
+        target
+          .append(entry.raw_key.to_string(), raw_value.to_string())
+          .unwrap();
+      }
+    };
+    append(None, self.import_map.imports());
+    for scope in self.import_map.scopes() {
+      append(Some(scope.raw_key), scope.imports);
+    }
+    minimal.to_json()
+  }
+
+  /// Gets all the entries in the import map.
+  pub fn entries(&self) -> Vec<ImportMapEntryRef> {
+    get_entry_refs(&self.import_map)
+  }
+
+  /// Finds the entry that the import map uses to resolve the specifier,
+  /// which is the most specific key of the most specific scope.
+  fn find_entry(
+    &self,
+    specifier: &str,
+    referrer: &ModuleSpecifier,
+  ) -> Option<ImportMapEntryRef> {
+    let normalized_specifier = if is_url_like(specifier) {
+      referrer.join(specifier).ok()?.to_string()
+    } else {
+      specifier.to_string()
+    };
+    let mut scopes = self
+      .import_map
+      .scopes()
+      .filter(|scope| key_matches(scope.key, referrer.as_str()))
+      .collect::<Vec<_>>();
+    scopes.sort_by_key(|scope| std::cmp::Reverse(scope.key.len()));
+    for scope in scopes {
+      if let Some(key) = find_key(scope.imports, &normalized_specifier) {
+        return Some(ImportMapEntryRef {
+          scope: Some(scope.raw_key.to_string()),
+          key,
+        });
+      }
+    }
+    find_key(self.import_map.imports(), &normalized_specifier)
+      .map(|key| ImportMapEntryRef { scope: None, key })
+  }
+}
+
+impl deno_graph::source::Resolver for ImportMapResolver {
+  fn resolve(
+    &self,
+    specifier: &str,
+    referrer: &ModuleSpecifier,
+    _mode: ResolutionMode,
+  ) -> Result<ModuleSpecifier, ResolveError> {
+    let resolved = self
+      .import_map
+      .resolve(specifier, referrer)
+      .map_err(|err| ResolveError::Other(err.into()))?;
+    if let Some(entry) = self.find_entry(specifier, referrer) {
+      self.resolutions.borrow_mut().push(ImportMapResolution {
+        referrer: referrer.clone(),
+        resolved: resolved.clone(),
+        entry,
+      });
+    }
+    Ok(resolved)
+  }
+}
+
+async fn load_jsonc(
+  url: &ModuleSpecifier,
+  loader: &mut dyn Loader,
+) -> anyhow::Result<(ModuleSpecifier, serde_json::Value)> {
+  let response = loader
+    .load(url, false, CacheSetting::Use)
+    .await?
+    .ok_or_else(|| anyhow::anyhow!("Could not find {}", url))?;
+  match response {
+    LoadResponse::External { specifier } => {
+      bail!("Did not expect external import map {}", specifier)
+    }
+    LoadResponse::Module {
+      content, specifier, ..
+    } => {
+      let value = jsonc_parser::parse_to_serde_value(
+        &content,
+        &jsonc_parser::ParseOptions {
+          allow_comments: true,
+          allow_loose_object_property_names: true,
+          allow_trailing_commas: true,
+        },
+      )?
+      .unwrap_or_else(|| serde_json::Value::Object(Default::default()));
+      Ok((specifier, value))
+    }
+  }
+}
+
+/// Adds the entries of the other import map that aren't already in
+/// the import map. The keys and addresses are added resolved because
+/// the import maps may have different base URLs.
+fn merge_import_map(
+  import_map: &mut ImportMap,
+  other: &ImportMap,
+) -> anyhow::Result<()> {
+  fn merge_specifier_map(
+    map: &mut SpecifierMap,
+    other: &SpecifierMap,
+  ) -> anyhow::Result<()> {
+    for entry in other.entries() {
+      let Some(value) = entry.value else {
+        continue;
+      };
+      if !map.contains(entry.key) {
+        map
+          .append(entry.key.to_string(), value.to_string())
+          .map_err(|err| anyhow::anyhow!("{}", err))?;
+      }
+    }
+    Ok(())
+  }
+
+  merge_specifier_map(import_map.imports_mut(), other.imports())?;
+  for scope in other.scopes() {
+    let scope_map = import_map
+      .get_or_append_scope_mut(scope.key)
+      .map_err(|err| anyhow::anyhow!("{}", err))?;
+    merge_specifier_map(scope_map, scope.imports)?;
+  }
+  Ok(())
+}
+
+fn get_entry_refs(import_map: &ImportMap) -> Vec<ImportMapEntryRef> {
+  let mut entries = import_map
+    .imports()
+    .entries()
+    .map(|entry| ImportMapEntryRef {
+      scope: None,
+      key: entry.raw_key.to_string(),
+    })
+    .collect::<Vec<_>>();
+  for scope in import_map.scopes() {
+    entries.extend(scope.imports.entries().map(|entry| ImportMapEntryRef {
+      scope: Some(scope.raw_key.to_string()),
+      key: entry.raw_key.to_string(),
+    }));
+  }
+  entries
+}
+
+fn find_key(map: &SpecifierMap, specifier: &str) -> Option<String> {
+  map
+    .entries()
+    .filter(|entry| key_matches(entry.key, specifier))
+    .max_by_key(|entry| entry.key.len())
+    .map(|entry| entry.raw_key.to_string())
+}
+
+fn key_matches(key: &str, specifier: &str) -> bool {
+  key == specifier || key.ends_with('/') && specifier.starts_with(key)
+}
+
+fn is_url_like(specifier: &str) -> bool {
+  specifier.starts_with("./")
+    || specifier.starts_with("../")
+    || specifier.starts_with('/')
+    || ModuleSpecifier::parse(specifier).is_ok()
+}
@@ -19,6 +19,7 @@ type RemoteFileText = Arc<str>;
 type RemoteFileHeaders = Option<HashMap<String, String>>;
 type RemoteFileResult = Result<(RemoteFileText, RemoteFileHeaders), String>;
 
+#[allow(dead_code)]
 fn url_to_file_path(module_specifier: &ModuleSpecifier) -> Result<PathBuf> {
   // module_specifier.to_file_path() does not work in a cross platform way
   // and it does not work in Wasm
@@ -40,6 +41,7 @@ fn url_to_file_path(module_specifier: &ModuleSpecifier) -> Result<PathBuf> {
   Ok(PathBuf::from(final_text))
 }
 
+#[allow(dead_code)]
 fn is_windows_path_segment(specifier: &str) -> bool {
   let mut chars = specifier.chars();
 
@@ -97,6 +99,7 @@ impl InMemoryLoader {
     self
   }
 
+  #[allow(dead_code)]
   pub fn add_file_with_error(
     &mut self,
     specifier: impl AsRef<str>,
@@ -114,7 +117,7 @@ impl Loader for InMemoryLoader {
   fn load(
     &mut self,
     specifier: &ModuleSpecifier,
-    is_dynamic: bool,
+    _is_dynamic: bool,
     _cache_setting: CacheSetting,
   ) -> Pin<Box<dyn Future<Output = Result<Option<LoadResponse>>> + 'static>> {
     let specifier = specifier.clone();
@@ -3,9 +3,16 @@ use std::cell::RefCell;
 use anyhow::Result;
 use rs_lib::rs_pack;
 use rs_lib::Diagnostic;
+use rs_lib::DiagnosticsOptions;
+use rs_lib::DiagnosticsReportOptions;
+use rs_lib::IncludeRemoteOptions;
+use rs_lib::JsxOptions;
+use rs_lib::MinimalImportMapOptions;
 use rs_lib::PackOptions;
 use rs_lib::PackOutput;
 use rs_lib::Reporter;
+use rs_lib::SourceMapOptions;
+use rs_lib::TestImportMapOptions;
 
 use super::InMemoryLoader;
 
@@ -33,7 +40,20 @@ pub struct PackResult {
 
 pub struct TestBuilder {
   loader: InMemoryLoader,
-  entry_point: String,
+  entry_points: Vec<String>,
+  import_map: Option<String>,
+  source_map: Option<SourceMapOptions>,
+  declaration_map: Option<SourceMapOptions>,
+  include_remote: Option<IncludeRemoteOptions>,
+  test_import_map: Option<TestImportMapOptions>,
+  minimal_import_map: Option<MinimalImportMapOptions>,
+  diagnostics: Option<DiagnosticsOptions>,
+  diagnostics_report: Option<DiagnosticsReportOptions>,
+  fix: bool,
+  jsx: Option<JsxOptions>,
+  scope_hoist: bool,
+  timings: bool,
+  output_folder: Option<String>,
 }
 
 impl TestBuilder {
@@ -41,7 +61,20 @@ impl TestBuilder {
     let loader = InMemoryLoader::default();
     Self {
       loader,
-      entry_point: "file:///mod.ts".to_string(),
+      entry_points: vec!["file:///mod.ts".to_string()],
+      import_map: None,
+      source_map: None,
+      declaration_map: None,
+      include_remote: None,
+      test_import_map: None,
+      minimal_import_map: None,
+      diagnostics: None,
+      diagnostics_report: None,
+      fix: false,
+      jsx: None,
+      scope_hoist: false,
+      timings: false,
+      output_folder: None,
     }
   }
 
@@ -54,7 +87,86 @@ impl TestBuilder {
   }
 
   pub fn entry_point(&mut self, value: impl AsRef<str>) -> &mut Self {
-    self.entry_point = value.as_ref().to_string();
+    self.entry_points = vec![value.as_ref().to_string()];
+    self
+  }
+
+  pub fn add_entry_point(&mut self, value: impl AsRef<str>) -> &mut Self {
+    self.entry_points.push(value.as_ref().to_string());
+    self
+  }
+
+  pub fn import_map(&mut self, value: impl AsRef<str>) -> &mut Self {
+    self.import_map = Some(value.as_ref().to_string());
+    self
+  }
+
+  pub fn source_map(&mut self, options: SourceMapOptions) -> &mut Self {
+    self.source_map = Some(options);
+    self
+  }
+
+  pub fn declaration_map(&mut self, options: SourceMapOptions) -> &mut Self {
+    self.declaration_map = Some(options);
+    self
+  }
+
+  pub fn include_remote(&mut self, options: IncludeRemoteOptions) -> &mut Self {
+    self.include_remote = Some(options);
+    self
+  }
+
+  pub fn test_import_map(
+    &mut self,
+    options: TestImportMapOptions,
+  ) -> &mut Self {
+    self.test_import_map = Some(options);
+    self
+  }
+
+  pub fn minimal_import_map(
+    &mut self,
+    options: MinimalImportMapOptions,
+  ) -> &mut Self {
+    self.minimal_import_map = Some(options);
+    self
+  }
+
+  pub fn diagnostics(&mut self, options: DiagnosticsOptions) -> &mut Self {
+    self.diagnostics = Some(options);
+    self
+  }
+
+  pub fn diagnostics_report(
+    &mut self,
+    options: DiagnosticsReportOptions,
+  ) -> &mut Self {
+    self.diagnostics_report = Some(options);
+    self
+  }
+
+  pub fn fix(&mut self) -> &mut Self {
+    self.fix = true;
+    self
+  }
+
+  pub fn jsx(&mut self, options: JsxOptions) -> &mut Self {
+    self.jsx = Some(options);
+    self
+  }
+
+  pub fn scope_hoist(&mut self) -> &mut Self {
+    self.scope_hoist = true;
+    self
+  }
+
+  pub fn timings(&mut self) -> &mut Self {
+    self.timings = true;
+    self
+  }
+
+  pub fn output_folder(&mut self, value: impl AsRef<str>) -> &mut Self {
+    self.output_folder = Some(value.as_ref().to_string());
     self
   }
 
@@ -62,8 +174,20 @@ impl TestBuilder {
     let reporter = TestReporter::default();
     let output = rs_pack(
       &PackOptions {
-        entry_points: vec![self.entry_point.clone()],
-        import_map: None,
+        entry_points: self.entry_points.clone(),
+        import_map: self.import_map.clone(),
+        source_map: self.source_map.clone(),
+        declaration_map: self.declaration_map.clone(),
+        include_remote: self.include_remote.clone(),
+        test_import_map: self.test_import_map.clone(),
+        minimal_import_map: self.minimal_import_map.clone(),
+        diagnostics: self.diagnostics.clone(),
+        diagnostics_report: self.diagnostics_report.clone(),
+        fix: self.fix,
+        jsx: self.jsx.clone(),
+        scope_hoist: self.scope_hoist,
+        timings: self.timings,
+        output_folder: self.output_folder.clone(),
       },
       &mut self.loader.clone(),
       &reporter,
@@ -1,17 +1,48 @@
+use std::collections::HashMap;
 use std::path::Path;
 use std::path::PathBuf;
 
 use pretty_assertions::assert_eq;
 
+use deno_ast::ModuleSpecifier;
 use integration::TestBuilder;
+use rs_lib::apply_text_edits;
+use rs_lib::rs_pack;
 use rs_lib::Diagnostic;
+use rs_lib::DiagnosticCode;
+use rs_lib::DiagnosticSeverity;
+use rs_lib::DiagnosticSeverityConfig;
+use rs_lib::DiagnosticsFormat;
+use rs_lib::DiagnosticsOptions;
+use rs_lib::DiagnosticsReportOptions;
+use rs_lib::FileSystemLoader;
+use rs_lib::IncludeRemoteOptions;
+use rs_lib::JsxOptions;
+use rs_lib::JsxTransform;
+use rs_lib::MinimalImportMapOptions;
+use rs_lib::PackOptions;
+use rs_lib::Reporter;
+use rs_lib::SourceMapOptions;
+use rs_lib::TestImportMapOptions;
+use rs_lib::UnusedImportMapEntry;
 
 mod integration;
 
 #[tokio::test]
 async fn test_specs() {
-  for (test_file_path, spec) in get_specs_in_dir(&PathBuf::from("./tests/pack"))
-  {
+  run_pack_specs(&PathBuf::from("./tests/pack"), |_| {}).await;
+}
+
+#[tokio::test]
+async fn test_scope_hoist_specs() {
+  run_pack_specs(&PathBuf::from("./tests/pack_scope_hoist"), |builder| {
+    builder.scope_hoist();
+  })
+  .await;
+}
+
+async fn run_pack_specs(path: &Path, configure: impl Fn(&mut TestBuilder)) {
+  for (test_file_path, spec) in get_specs_in_dir(path) {
     eprintln!("Running {}", test_file_path.display());
     let mut builder = TestBuilder::new();
     builder.with_loader(|loader| {
@@ -19,12 +50,13 @@ async fn test_specs() {
         loader.add_file(&file.specifier, &file.text);
       }
     });
+    configure(&mut builder);
 
     let result = builder.pack().await.unwrap();
     let update_var = std::env::var("UPDATE");
     let spec = if update_var.as_ref().map(|v| v.as_str()) == Ok("1") {
       let mut spec = spec;
-      spec.output_file.text = result.output.js.clone();
+      spec.output_file.text = result.output.entry_points[0].js.clone();
       spec.diagnostics = result.diagnostics.clone();
       std::fs::write(&test_file_path, spec.emit()).unwrap();
       spec
@@ -32,7 +64,7 @@ async fn test_specs() {
       spec
     };
     assert_eq!(
-      result.output.js,
+      result.output.entry_points[0].js,
       spec.output_file.text,
       "Should be same for {}",
       test_file_path.display()
@@ -63,7 +95,7 @@ async fn test_dts_specs() {
     let update_var = std::env::var("UPDATE");
     let spec = if update_var.as_ref().map(|v| v.as_str()) == Ok("1") {
       let mut spec = spec;
-      spec.output_file.text = result.output.dts.clone();
+      spec.output_file.text = result.output.entry_points[0].dts.clone();
       spec.diagnostics = result.diagnostics.clone();
       std::fs::write(&test_file_path, spec.emit()).unwrap();
       spec
@@ -71,7 +103,7 @@ async fn test_dts_specs() {
       spec
     };
     assert_eq!(
-      result.output.dts,
+      result.output.entry_points[0].dts,
       spec.output_file.text,
       "Should be same for {}",
       test_file_path.display()
@@ -85,6 +117,1278 @@ async fn test_dts_specs() {
   }
 }
 
+#[tokio::test]
+async fn test_multiple_entry_points() {
+  let mut builder = TestBuilder::new();
+  builder
+    .with_loader(|loader| {
+      loader.add_file("mod.ts", "export { add } from './shared.ts';");
+      loader.add_file(
+        "testing.ts",
+        concat!(
+          "import { add } from './shared.ts';\n",
+          "export function addOne(value: number): number {\n",
+          "  return add(value, 1);\n",
+          "}\n",
+        ),
+      );
+      loader.add_file(
+        "shared.ts",
+        concat!(
+          "export function add(a: number, b: number): number {\n",
+          "  return a + b;\n",
+          "}\n",
+        ),
+      );
+    })
+    .add_entry_point("file:///testing.ts");
+
+  let result = builder.pack().await.unwrap();
+  let entry_points = &result.output.entry_points;
+  assert_eq!(entry_points.len(), 2);
+  assert_eq!(entry_points[0].specifier.as_str(), "file:///mod.ts");
+  assert_eq!(
+    entry_points[0].js,
+    r#"const pack1 = {
+  add: undefined
+};
+(function shared_ts() {
+  function add(a, b) {
+    return a + b;
+  }
+  Object.defineProperty(pack1, "add", {
+    get: ()=>add
+  });
+})();
+const _packReExport1 = pack1.add;
+export { _packReExport1 as add };
+"#
+  );
+  assert_eq!(
+    entry_points[0].dts,
+    r#"import __export1 = pack1.add;
+export { __export1 as add };
+declare module pack1 {
+  export function add(a: number, b: number): number;
+}
+"#
+  );
+  assert_eq!(entry_points[1].specifier.as_str(), "file:///testing.ts");
+  assert_eq!(
+    entry_points[1].js,
+    r#"const pack0 = {
+  add: undefined
+};
+(function shared_ts() {
+  function add(a, b) {
+    return a + b;
+  }
+  Object.defineProperty(pack0, "add", {
+    get: ()=>add
+  });
+})();
+export function addOne(value) {
+  return pack0.add(value, 1);
+}
+"#
+  );
+  assert_eq!(
+    entry_points[1].dts,
+    r#"export function addOne(value: number): number;
+"#
+  );
+  assert!(result.diagnostics.is_empty());
+}
+
+#[tokio::test]
+async fn test_entry_points_same_file_name() {
+  let mut builder = TestBuilder::new();
+  builder
+    .with_loader(|loader| {
+      loader.add_file("a/mod.ts", "export const a = 1;");
+      loader.add_file("b/mod.ts", "export const b = 2;");
+    })
+    .entry_point("file:///a/mod.ts")
+    .add_entry_point("file:///b/mod.ts");
+
+  let err = builder.pack().await.err().unwrap();
+  assert_eq!(
+    err.to_string(),
+    concat!(
+      "Entry points file:///a/mod.ts and file:///b/mod.ts would both output ",
+      "files named 'mod'. Rename one of them.",
+    ),
+  );
+}
+
+#[tokio::test]
+async fn test_timings() {
+  let mut builder = TestBuilder::new();
+  builder.with_loader(|loader| {
+    loader.add_file("mod.ts", "export { add } from './add.ts';");
+    loader.add_file(
+      "add.ts",
+      "export function add(a: number, b: number) { return a + b; }",
+    );
+  });
+
+  let result = builder.pack().await.unwrap();
+  assert!(result.output.entry_points[0].timings.is_none());
+
+  builder.timings();
+  let result = builder.pack().await.unwrap();
+  let timings = result.output.entry_points[0].timings.as_ref().unwrap();
+  // in the order the modules are evaluated
+  assert_eq!(
+    timings
+      .modules
+      .iter()
+      .map(|timing| timing.specifier.as_str())
+      .collect::<Vec<_>>(),
+    vec!["file:///add.ts", "file:///mod.ts"],
+  );
+  assert!(timings.transpile_ms >= 0.0);
+  assert!(timings.print_ms >= 0.0);
+}
+
+#[tokio::test]
+async fn test_js_source_map() {
+  let mut builder = TestBuilder::new();
+  builder
+    .with_loader(|loader| {
+      loader.add_file(
+        "mod.ts",
+        concat!(
+          "import { add } from './add.ts';\n",
+          "\n",
+          "console.log(add(1, 2));\n",
+        ),
+      );
+      loader.add_file(
+        "add.ts",
+        concat!(
+          "// adds two numbers\n",
+          "export function add(a: number, b: number): number {\n",
+          "  return a + b;\n",
+          "}\n",
+        ),
+      );
+    })
+    .source_map(SourceMapOptions {
+      sources_content: true,
+      url_comment: true,
+    });
+
+  let result = builder.pack().await.unwrap();
+  let output = &result.output.entry_points[0];
+  assert!(output.js.ends_with("//# sourceMappingURL=mod.js.map\n"));
+  let source_map = sourcemap::SourceMap::from_slice(
+    output.js_source_map.as_ref().unwrap().as_bytes(),
+  )
+  .unwrap();
+  let find_token = |text: &str| {
+    let (line, col) = find_line_and_col(&output.js, text);
+    source_map.lookup_token(line, col).unwrap()
+  };
+
+  let token = find_token("return a + b;");
+  assert_eq!(token.get_source(), Some("file:///add.ts"));
+  assert_eq!((token.get_src_line(), token.get_src_col()), (2, 2));
+  let token = find_token("console.log");
+  assert_eq!(token.get_source(), Some("file:///mod.ts"));
+  assert_eq!((token.get_src_line(), token.get_src_col()), (2, 0));
+  assert_eq!(
+    source_map.get_source_contents(token.get_src_id()),
+    Some("import { add } from './add.ts';\n\nconsole.log(add(1, 2));\n")
+  );
+}
+
+#[tokio::test]
+async fn test_output_files() {
+  let mut builder = TestBuilder::new();
+  builder
+    .with_loader(|loader| {
+      loader.add_file("src/mod.ts", "export default function add() {}\n");
+    })
+    .entry_point("file:///src/mod.ts")
+    .source_map(SourceMapOptions {
+      sources_content: false,
+      url_comment: true,
+    })
+    .output_folder("file:///dist");
+
+  let result = builder.pack().await.unwrap();
+  let output = &result.output.entry_points[0];
+  let files = output
+    .files
+    .iter()
+    .map(|file| (file.relative_url.as_str(), file.text.as_str()))
+    .collect::<Vec<_>>();
+  assert_eq!(
+    files.iter().map(|(url, _)| *url).collect::<Vec<_>>(),
+    vec!["mod.js", "mod.js.map", "mod.ts", "mod.d.ts"],
+  );
+  assert_eq!(
+    files[0].1,
+    format!("/// <reference types=\"./mod.d.ts\" />\n{}", output.js)
+  );
+  let source_map: serde_json::Value = serde_json::from_str(files[1].1).unwrap();
+  assert_eq!(source_map["sources"], serde_json::json!(["../src/mod.ts"]));
+  // shifted down a line for the types reference comment
+  assert!(source_map["mappings"].as_str().unwrap().starts_with(';'));
+  assert_eq!(
+    files[2].1,
+    concat!(
+      "// @deno-types=\"./mod.d.ts\"\n",
+      "export * from \"./mod.js\";\n",
+      "// @deno-types=\"./mod.d.ts\"\n",
+      "import defaultExport from \"./mod.js\";\n",
+      "export default defaultExport;",
+    )
+  );
+  assert_eq!(files[3].1, output.dts);
+}
+
+#[tokio::test]
+async fn test_declaration_map() {
+  let mut builder = TestBuilder::new();
+  builder
+    .with_loader(|loader| {
+      loader.add_file("mod.ts", "export * from './classes.ts';\n");
+      loader.add_file(
+        "classes.ts",
+        concat!(
+          "/** A person. */\n",
+          "export class Person {\n",
+          "  /**\n",
+          "   * Their name.\n",
+          "   */\n",
+          "  name: string = 'Bob';\n",
+          "}\n",
+        ),
+      );
+    })
+    .declaration_map(SourceMapOptions {
+      sources_content: false,
+      url_comment: true,
+    });
+
+  let result = builder.pack().await.unwrap();
+  let output = &result.output.entry_points[0];
+  assert!(output.dts.ends_with("//# sourceMappingURL=mod.d.ts.map\n"));
+  let declaration_map = sourcemap::SourceMap::from_slice(
+    output.declaration_map.as_ref().unwrap().as_bytes(),
+  )
+  .unwrap();
+  let find_token = |text: &str| {
+    let (line, col) = find_line_and_col(&output.dts, text);
+    declaration_map.lookup_token(line, col).unwrap()
+  };
+
+  let token = find_token("export class Person");
+  assert_eq!(token.get_source(), Some("file:///classes.ts"));
+  assert_eq!((token.get_src_line(), token.get_src_col()), (1, 0));
+  let token = find_token("name: string");
+  assert_eq!(token.get_source(), Some("file:///classes.ts"));
+  assert_eq!((token.get_src_line(), token.get_src_col()), (5, 2));
+  assert_eq!(
+    declaration_map.get_source_contents(token.get_src_id()),
+    None
+  );
+}
+
+#[tokio::test]
+async fn test_include_remote() {
+  let mut builder = TestBuilder::new();
+  builder
+    .with_loader(|loader| {
+      loader.add_file(
+        "mod.ts",
+        concat!(
+          "import { bold } from 'https://deno.land/std@0.200.0/fmt/colors.ts';\n",
+          "import { red } from 'https://deno.land/std@0.200.0/fmt/denied.ts';\n",
+          "export { add } from 'https://deno.land/x/math/mod.ts';\n",
+          "\n",
+          "export function format(text: string): string {\n",
+          "  return bold(red(text));\n",
+          "}\n",
+          "export type Text = ReturnType<typeof bold>;\n",
+        ),
+      );
+      loader.add_file(
+        "https://deno.land/std@0.200.0/fmt/colors.ts",
+        concat!(
+          "import { wrap } from './wrap.ts';\n",
+          "export function bold(text: string): string {\n",
+          "  return wrap(text, 1);\n",
+          "}\n",
+        ),
+      );
+      loader.add_file(
+        "https://deno.land/std@0.200.0/fmt/wrap.ts",
+        concat!(
+          "export function wrap(text: string, code: number): string {\n",
+          "  return `\\x1b[${code}m${text}`;\n",
+          "}\n",
+        ),
+      );
+      loader.add_file(
+        "https://deno.land/std@0.200.0/fmt/denied.ts",
+        "export function red(text: string): string { return text; }\n",
+      );
+      loader.add_file(
+        "https://deno.land/x/math/mod.ts",
+        "export function add(a: number, b: number): number { return a + b; }\n",
+      );
+    })
+    .include_remote(IncludeRemoteOptions {
+      allow: vec!["https://deno.land/std@*/fmt/*".to_string()],
+      deny: vec!["*/denied.ts".to_string()],
+    });
+
+  let result = builder.pack().await.unwrap();
+  let output = &result.output.entry_points[0];
+  assert_eq!(
+    output.js,
+    r#"import * as pack1 from "https://deno.land/std@0.200.0/fmt/denied.ts";
+import * as pack3 from "https://deno.land/x/math/mod.ts";
+const pack0 = {
+  bold: undefined
+};
+const pack4 = {
+  wrap: undefined
+};
+(function https___deno_land_std_0_200_0_fmt_wrap_ts() {
+  function wrap(text, code) {
+    return `\x1b[${code}m${text}`;
+  }
+  Object.defineProperty(pack4, "wrap", {
+    get: ()=>wrap
+  });
+})();
+(function https___deno_land_std_0_200_0_fmt_colors_ts() {
+  function bold(text) {
+    return pack4.wrap(text, 1);
+  }
+  Object.defineProperty(pack0, "bold", {
+    get: ()=>bold
+  });
+})();
+export function format(text) {
+  return pack0.bold(pack1.red(text));
+}
+const _packReExport1 = pack3.add;
+export { _packReExport1 as add };
+"#
+  );
+  assert_eq!(
+    output.dts,
+    r#"import * as pack1 from "https://deno.land/x/math/mod.ts";
+import bold = pack2.bold;
+export function format(text: string): string;
+export type Text = ReturnType<typeof bold>;
+import __export1 = pack1.add;
+export { __export1 as add };
+declare module pack2 {
+  export function bold(text: string): string;
+}
+"#
+  );
+  assert!(result.diagnostics.is_empty());
+}
+
+#[tokio::test]
+async fn test_file_system_loader() {
+  let temp_dir = std::env::temp_dir().join("libpack_test_file_system_loader");
+  let _ = std::fs::remove_dir_all(&temp_dir);
+  let project_dir = temp_dir.join("project");
+  let cache_dir = temp_dir.join("deno_dir/deps/https/deno.land");
+  std::fs::create_dir_all(&project_dir).unwrap();
+  std::fs::create_dir_all(&cache_dir).unwrap();
+  std::fs::write(
+    project_dir.join("mod.ts"),
+    concat!(
+      "import { bold } from 'https://deno.land/std/fmt/colors.ts';\n",
+      "export function format(text: string): string {\n",
+      "  return bold(text);\n",
+      "}\n",
+      "export type Text = ReturnType<typeof bold>;\n",
+    ),
+  )
+  .unwrap();
+  // sha256 of "/std/fmt/colors.ts", which redirects to a versioned url
+  let redirect_hash =
+    "3574883d8acbaf00e28990ec8e83d71084c4c668c1dc7794be25208c60cfc935";
+  std::fs::write(cache_dir.join(redirect_hash), "").unwrap();
+  std::fs::write(
+    cache_dir.join(format!("{}.metadata.json", redirect_hash)),
+    r#"{"headers":{"Location":"/std@0.200.0/fmt/colors.ts"},"url":"https://deno.land/std/fmt/colors.ts"}"#,
+  )
+  .unwrap();
+  // sha256 of "/std@0.200.0/fmt/colors.ts"
+  let module_hash =
+    "c661d721cc3a8c74943a503757c75036840819b89265dd4b35a7b47569cbf6cb";
+  std::fs::write(
+    cache_dir.join(module_hash),
+    "export function bold(text: string): string { return text; }\n",
+  )
+  .unwrap();
+  std::fs::write(
+    cache_dir.join(format!("{}.metadata.json", module_hash)),
+    r#"{"headers":{"content-type":"application/typescript"},"url":"https://deno.land/std@0.200.0/fmt/colors.ts"}"#,
+  )
+  .unwrap();
+
+  let entry_point = ModuleSpecifier::from_file_path(project_dir.join("mod.ts"))
+    .unwrap()
+    .to_string();
+  let options = PackOptions {
+    entry_points: vec![entry_point.clone()],
+    import_map: None,
+    source_map: None,
+    declaration_map: None,
+    include_remote: Some(IncludeRemoteOptions::default()),
+    test_import_map: None,
+    minimal_import_map: None,
+    diagnostics: None,
+    diagnostics_report: None,
+    fix: false,
+    jsx: None,
+    scope_hoist: false,
+    timings: false,
+    output_folder: None,
+  };
+  let mut loader = FileSystemLoader::new(temp_dir.join("deno_dir"))
+    .with_include_remote(options.include_remote.clone());
+  let output = rs_pack(&options, &mut loader, &NullReporter).await.unwrap();
+  assert_eq!(
+    output.entry_points[0].js,
+    r#"const pack0 = {
+  bold: undefined
+};
+(function https___deno_land_std_0_200_0_fmt_colors_ts() {
+  function bold(text) {
+    return text;
+  }
+  Object.defineProperty(pack0, "bold", {
+    get: ()=>bold
+  });
+})();
+export function format(text) {
+  return pack0.bold(text);
+}
+"#
+  );
+  assert_eq!(
+    output.entry_points[0].dts,
+    r#"import bold = pack1.bold;
+export function format(text: string): string;
+export type Text = ReturnType<typeof bold>;
+declare module pack1 {
+  export function bold(text: string): string;
+}
+"#
+  );
+
+  // remote modules missing from the cache error
+  std::fs::write(
+    project_dir.join("mod.ts"),
+    "export * from 'https://deno.land/x/missing/mod.ts';\n",
+  )
+  .unwrap();
+  let err = rs_pack(&options, &mut loader, &NullReporter)
+    .await
+    .err()
+    .unwrap();
+  assert!(
+    err
+      .to_string()
+      .contains("Remote module https://deno.land/x/missing/mod.ts was not found in the Deno cache"),
+    "{:#}",
+    err
+  );
+
+  // while remote modules that aren't packed are left external
+  let options = PackOptions {
+    include_remote: None,
+    ..options
+  };
+  let mut loader = FileSystemLoader::new(temp_dir.join("deno_dir"));
+  let output = rs_pack(&options, &mut loader, &NullReporter).await.unwrap();
+  assert_eq!(
+    output.entry_points[0].js,
+    concat!(
+      "import * as pack1 from \"https://deno.land/x/missing/mod.ts\";\n",
+      "export * from \"https://deno.land/x/missing/mod.ts\";\n",
+    )
+  );
+  assert_eq!(
+    output.entry_points[0].dts,
+    "export * from \"https://deno.land/x/missing/mod.ts\";\n"
+  );
+
+  std::fs::remove_dir_all(&temp_dir).unwrap();
+}
+
+#[tokio::test]
+async fn test_diagnostic_severities() {
+  let mut builder = TestBuilder::new();
+  builder.with_loader(|loader| {
+    loader.add_file(
+      "mod.ts",
+      concat!(
+        "export function a() {\n",
+        "  return Math.random();\n",
+        "}\n",
+        "export default [1, 2];\n",
+      ),
+    );
+  });
+
+  builder.diagnostics(DiagnosticsOptions {
+    severities: HashMap::from([(
+      "missing-return-type".to_string(),
+      DiagnosticSeverityConfig::Warning,
+    )]),
+  });
+  let result = builder.pack().await.unwrap();
+  let diagnostics = result
+    .diagnostics
+    .iter()
+    .map(|d| (d.code, d.severity))
+    .collect::<Vec<_>>();
+  assert_eq!(
+    diagnostics,
+    vec![
+      (
+        DiagnosticCode::UnsupportedDefaultExpr,
+        DiagnosticSeverity::Error
+      ),
+      (
+        DiagnosticCode::MissingReturnType,
+        DiagnosticSeverity::Warning
+      ),
+    ]
+  );
+
+  builder.diagnostics(DiagnosticsOptions {
+    severities: HashMap::from([
+      ("LP1001".to_string(), DiagnosticSeverityConfig::Off),
+      ("LP1002".to_string(), DiagnosticSeverityConfig::Info),
+    ]),
+  });
+  let result = builder.pack().await.unwrap();
+  let diagnostics = result
+    .diagnostics
+    .iter()
+    .map(|d| (d.code, d.severity))
+    .collect::<Vec<_>>();
+  assert_eq!(
+    diagnostics,
+    vec![(
+      DiagnosticCode::UnsupportedDefaultExpr,
+      DiagnosticSeverity::Info
+    )]
+  );
+
+  builder.diagnostics(DiagnosticsOptions {
+    severities: HashMap::from([(
+      "LP9999".to_string(),
+      DiagnosticSeverityConfig::Off,
+    )]),
+  });
+  let err = builder.pack().await.err().unwrap();
+  assert_eq!(err.to_string(), "Unknown diagnostic code 'LP9999'.");
+}
+
+#[tokio::test]
+async fn test_diagnostics_report() {
+  let mut builder = TestBuilder::new();
+  builder
+    .with_loader(|loader| {
+      loader.add_file(
+        "file:///project/mod.ts",
+        concat!(
+          "export function a() {\n",
+          "  return Math.random();\n",
+          "}\n",
+          "export default [1, 2];\n",
+        ),
+      );
+    })
+    .entry_point("file:///project/mod.ts")
+    .diagnostics(DiagnosticsOptions {
+      severities: HashMap::from([(
+        "LP1002".to_string(),
+        DiagnosticSeverityConfig::Warning,
+      )]),
+    });
+
+  builder.diagnostics_report(DiagnosticsReportOptions {
+    format: DiagnosticsFormat::JsonLines,
+    base_url: None,
+  });
+  let result = builder.pack().await.unwrap();
+  let report = result.output.diagnostics_report.unwrap();
+  let lines = report
+    .lines()
+    .map(|line| serde_json::from_str::<Diagnostic>(line).unwrap())
+    .collect::<Vec<_>>();
+  assert_eq!(lines, result.diagnostics);
+  assert_eq!(lines.len(), 2);
+
+  builder.diagnostics_report(DiagnosticsReportOptions {
+    format: DiagnosticsFormat::Sarif,
+    base_url: Some("file:///project/".to_string()),
+  });
+  let result = builder.pack().await.unwrap();
+  let sarif: serde_json::Value =
+    serde_json::from_str(&result.output.diagnostics_report.unwrap()).unwrap();
+  assert_eq!(sarif["version"], "2.1.0");
+  let run = &sarif["runs"][0];
+  assert_eq!(run["tool"]["driver"]["name"], "libpack");
+  assert_eq!(
+    run["originalUriBaseIds"]["SRCROOT"]["uri"],
+    "file:///project/"
+  );
+  let rules = run["tool"]["driver"]["rules"].as_array().unwrap();
+  assert_eq!(rules.len(), DiagnosticCode::ALL.len());
+  assert_eq!(
+    run["results"],
+    serde_json::json!([{
+      "ruleId": "LP1002",
+      "ruleIndex": 1,
+      "level": "warning",
+      "message": {
+        "text": "Default expressions that are not identifiers are not supported.",
+      },
+      "locations": [{
+        "physicalLocation": {
+          "artifactLocation": { "uri": "mod.ts", "uriBaseId": "SRCROOT" },
+          "region": {
+            "startLine": 4,
+            "startColumn": 1,
+          },
+        },
+      }],
+      "properties": {
+        "hint": concat!(
+          "Extract out the expression to a variable, type the variable, ",
+          "and then default export the variable declaration.",
+        ),
+      },
+    }, {
+      "ruleId": "LP1001",
+      "ruleIndex": 0,
+      "level": "error",
+      "message": {
+        "text": "Missing return type for function with return statement.",
+      },
+      "locations": [{
+        "physicalLocation": {
+          "artifactLocation": { "uri": "mod.ts", "uriBaseId": "SRCROOT" },
+          "region": {
+            "startLine": 1,
+            "startColumn": 8,
+            "endLine": 1,
+            "endColumn": 21,
+          },
+        },
+      }],
+      "relatedLocations": [{
+        "id": 0,
+        "message": { "text": "Value returned here." },
+        "physicalLocation": {
+          "artifactLocation": { "uri": "mod.ts", "uriBaseId": "SRCROOT" },
+          "region": {
+            "startLine": 2,
+            "startColumn": 3,
+            "endLine": 2,
+            "endColumn": 24,
+          },
+        },
+      }],
+      "properties": { "hint": "Add an explicit return type to the function." },
+    }])
+  );
+}
+
+#[tokio::test]
+async fn test_fix() {
+  let source = concat!(
+    "export function add(a: number, b: number) {\n",
+    "  if (a > b) {\n",
+    "    return a as number;\n",
+    "  } else {\n",
+    "    return 1;\n",
+    "  }\n",
+    "}\n",
+    "export async function name() {\n",
+    "  return new Map<string, number>();\n",
+    "}\n",
+    "export function mixed() {\n",
+    "  if (Math.random() > 0.5) {\n",
+    "    return 1;\n",
+    "  }\n",
+    "  return \"a\";\n",
+    "}\n",
+    "export class Service {\n",
+    "  cache? = new Intl.Collator();\n",
+    "  count = 0;\n",
+    "  other = createOther();\n",
+    "}\n",
+    "function createOther() {}\n",
+    "export function createMap() {\n",
+    "  return new Map();\n",
+    "}\n",
+  );
+  let mut builder = TestBuilder::new();
+  builder.with_loader(|loader| {
+    loader.add_file("mod.ts", source);
+  });
+
+  // the inferred return types aren't reported
+  let result = builder.pack().await.unwrap();
+  assert!(result.output.fixes.is_empty());
+  let codes = result
+    .diagnostics
+    .iter()
+    .map(|d| (d.code, d.severity))
+    .collect::<Vec<_>>();
+  assert_eq!(
+    codes,
+    vec![
+      (DiagnosticCode::MissingReturnType, DiagnosticSeverity::Error),
+      (DiagnosticCode::MissingReturnType, DiagnosticSeverity::Error),
+      (
+        DiagnosticCode::MissingPropertyType,
+        DiagnosticSeverity::Warning
+      ),
+      (
+        DiagnosticCode::MissingPropertyType,
+        DiagnosticSeverity::Warning
+      ),
+      (DiagnosticCode::MissingReturnType, DiagnosticSeverity::Error),
+    ]
+  );
+
+  builder.fix();
+  let result = builder.pack().await.unwrap();
+  // only what can't be inferred is left for manual work
+  let diagnostics = result
+    .diagnostics
+    .iter()
+    .map(|d| (d.code, d.line_and_column.as_ref().unwrap().line_number))
+    .collect::<Vec<_>>();
+  assert_eq!(
+    diagnostics,
+    vec![
+      (DiagnosticCode::MissingReturnType, 11),
+      (DiagnosticCode::MissingPropertyType, 20),
+      // the type arguments of the map are unknown
+      (DiagnosticCode::MissingReturnType, 23),
+    ]
+  );
+  assert_eq!(
+    apply_text_edits(source, &result.output.fixes),
+    concat!(
+      "export function add(a: number, b: number): number {\n",
+      "  if (a > b) {\n",
+      "    return a as number;\n",
+      "  } else {\n",
+      "    return 1;\n",
+      "  }\n",
+      "}\n",
+      "export async function name(): Promise<Map<string, number>> {\n",
+      "  return new Map<string, number>();\n",
+      "}\n",
+      "export function mixed() {\n",
+      "  if (Math.random() > 0.5) {\n",
+      "    return 1;\n",
+      "  }\n",
+      "  return \"a\";\n",
+      "}\n",
+      "export class Service {\n",
+      "  cache?: Intl.Collator = new Intl.Collator();\n",
+      "  count = 0;\n",
+      "  other = createOther();\n",
+      "}\n",
+      "function createOther() {}\n",
+      "export function createMap() {\n",
+      "  return new Map();\n",
+      "}\n",
+    )
+  );
+  let dts = &result.output.entry_points[0].dts;
+  assert!(
+    dts.contains("add(a: number, b: number): number;"),
+    "{}",
+    dts
+  );
+  assert!(dts.contains("cache?: Intl.Collator;"), "{}", dts);
+}
+
+#[tokio::test]
+async fn test_test_import_map() {
+  let mut builder = TestBuilder::new();
+  builder
+    .with_loader(|loader| {
+      loader.add_file(
+        "file:///project/deno.json",
+        r#"{
+  "imports": {
+    "$std/": "https://deno.land/std@0.200.0/",
+    "utils": "./src/utils.ts"
+  },
+  "scopes": {
+    "./vendor/": {
+      "$std/": "./vendor/std/"
+    },
+    "https://deno.land/x/other/": {
+      "utils": "./src/other_utils.ts"
+    }
+  }
+}"#,
+      );
+      loader
+        .add_file("file:///project/mod.ts", "export { add } from 'utils';\n");
+      loader.add_file(
+        "file:///project/src/utils.ts",
+        "export function add(a: number, b: number): number { return a + b; }\n",
+      );
+    })
+    .entry_point("file:///project/mod.ts")
+    .import_map("file:///project/deno.json")
+    .test_import_map(TestImportMapOptions {
+      output_folder: "file:///project/dist".to_string(),
+    });
+
+  let result = builder.pack().await.unwrap();
+  assert_eq!(
+    result.output.test_import_map.unwrap(),
+    r#"{
+  "imports": {
+    "utils": "file:///project/src/utils.ts",
+    "file:///project/mod.ts": "file:///project/dist/mod.ts",
+    "$std/": "https://deno.land/std@0.200.0/"
+  },
+  "scopes": {
+    "https://deno.land/x/other/": {
+      "utils": "file:///project/src/other_utils.ts"
+    },
+    "file:///project/vendor/": {
+      "$std/": "file:///project/vendor/std/"
+    }
+  }
+}"#
+  );
+  assert!(result.diagnostics.is_empty());
+}
+
+#[tokio::test]
+async fn test_minimal_import_map() {
+  let mut builder = TestBuilder::new();
+  builder
+    .with_loader(|loader| {
+      loader.add_file(
+        "file:///project/deno.json",
+        r#"{
+  "imports": {
+    "$std/": "https://deno.land/std@0.200.0/",
+    "$std/testing/": "https://deno.land/std@0.200.0/testing/",
+    "math": "https://deno.land/x/math/mod.ts",
+    "utils": "./src/utils.ts",
+    "unused": "https://deno.land/x/unused/mod.ts"
+  },
+  "scopes": {
+    "https://deno.land/x/math/": {
+      "$std/": "https://deno.land/std@0.199.0/"
+    }
+  }
+}"#,
+      );
+      loader.add_file(
+        "file:///project/mod.ts",
+        concat!(
+          "import { bold } from '$std/fmt/colors.ts';\n",
+          "export { add } from 'math';\n",
+          "export { format } from 'utils';\n",
+          "export const text: string = bold('text');\n",
+        ),
+      );
+      loader.add_file(
+        "file:///project/src/utils.ts",
+        "export function format(text: string): string { return text; }\n",
+      );
+      loader.add_file(
+        "file:///project/mod.test.ts",
+        concat!(
+          "import { assert } from '$std/testing/asserts.ts';\n",
+          "import { text } from './mod.ts';\n",
+          "assert(text);\n",
+        ),
+      );
+      loader.add_file(
+        "https://deno.land/std@0.200.0/fmt/colors.ts",
+        "export function bold(text: string): string { return text; }\n",
+      );
+      loader.add_file(
+        "https://deno.land/std@0.200.0/testing/asserts.ts",
+        "export function assert(value: unknown): void {}\n",
+      );
+      loader.add_file(
+        "https://deno.land/x/math/mod.ts",
+        "export function add(a: number, b: number): number { return a + b; }\n",
+      );
+    })
+    .entry_point("file:///project/mod.ts")
+    .import_map("file:///project/deno.json")
+    .minimal_import_map(MinimalImportMapOptions {
+      test_files: vec!["file:///project/mod.test.ts".to_string()],
+    });
+
+  let result = builder.pack().await.unwrap();
+  assert_eq!(
+    result.output.import_map.unwrap(),
+    r#"{
+  "imports": {
+    "math": "https://deno.land/x/math/mod.ts",
+    "$std/": "https://deno.land/std@0.200.0/"
+  }
+}
+"#
+  );
+  assert_eq!(
+    result.output.unused_import_map_entries,
+    vec![
+      UnusedImportMapEntry {
+        specifier: ModuleSpecifier::parse("file:///project/deno.json").unwrap(),
+        scope: None,
+        key: "unused".to_string(),
+        used_by_tests: false,
+      },
+      UnusedImportMapEntry {
+        specifier: ModuleSpecifier::parse("file:///project/deno.json").unwrap(),
+        scope: None,
+        key: "$std/testing/".to_string(),
+        used_by_tests: true,
+      },
+      UnusedImportMapEntry {
+        specifier: ModuleSpecifier::parse("file:///project/deno.json").unwrap(),
+        scope: Some("https://deno.land/x/math/".to_string()),
+        key: "$std/".to_string(),
+        used_by_tests: false,
+      },
+    ]
+  );
+  assert!(result.diagnostics.is_empty());
+}
+
+#[tokio::test]
+async fn test_jsx_automatic_runtime() {
+  let mut builder = TestBuilder::new();
+  builder
+    .with_loader(|loader| {
+      loader.add_file(
+        "file:///project/deno.json",
+        r#"{
+  "compilerOptions": {
+    "jsx": "react-jsx",
+    "jsxImportSource": "preact"
+  },
+  "imports": {
+    "preact/": "https://esm.sh/preact/"
+  }
+}"#,
+      );
+      loader.add_file(
+        "file:///project/mod.tsx",
+        concat!(
+          "import { Title } from './title.tsx';\n",
+          "export function App(): JSX.Element {\n",
+          "  return <div><Title text=\"a\" /></div>;\n",
+          "}\n",
+        ),
+      );
+      loader.add_file(
+        "file:///project/title.tsx",
+        concat!(
+          "export function Title(props: { text: string }): JSX.Element {\n",
+          "  return <><h1>{props.text}</h1></>;\n",
+          "}\n",
+        ),
+      );
+      loader.add_file_with_headers(
+        "https://esm.sh/preact/jsx-runtime",
+        "export function jsx() {}\nexport function Fragment() {}\n",
+        &[("content-type", "application/javascript")],
+      );
+    })
+    .entry_point("file:///project/mod.tsx")
+    .import_map("file:///project/deno.json");
+
+  let result = builder.pack().await.unwrap();
+  assert_eq!(
+    result.output.entry_points[0].js,
+    r#"import * as pack2 from "https://esm.sh/preact/jsx-runtime";
+const pack0 = {
+  Title: undefined
+};
+(function _title_tsx() {
+  const _jsx = pack2.jsx, _Fragment = pack2.Fragment;
+  function Title(props) {
+    return /*#__PURE__*/ _jsx(_Fragment, {
+      children: /*#__PURE__*/ _jsx("h1", {
+        children: props.text
+      })
+    });
+  }
+  Object.defineProperty(pack0, "Title", {
+    get: ()=>Title
+  });
+})();
+const _jsx = pack2.jsx;
+export function App() {
+  return /*#__PURE__*/ _jsx("div", {
+    children: /*#__PURE__*/ _jsx(pack0.Title, {
+      text: "a"
+    })
+  });
+}
+"#
+  );
+  assert_eq!(
+    result.output.entry_points[0].dts,
+    "export function App(): JSX.Element;\n"
+  );
+  assert!(result.diagnostics.is_empty());
+
+  // the provided options are used instead of the deno.json
+  builder.jsx(JsxOptions {
+    jsx: JsxTransform::ReactJsxDev,
+    jsx_import_source: Some("npm:react".to_string()),
+    ..Default::default()
+  });
+  let result = builder.pack().await.unwrap();
+  assert!(result.output.entry_points[0]
+    .js
+    .starts_with("import * as pack2 from \"npm:react/jsx-dev-runtime\";\n"));
+}
+
+#[tokio::test]
+async fn test_jsx_import_source_pragma() {
+  let mut builder = TestBuilder::new();
+  builder
+    .with_loader(|loader| {
+      loader.add_file(
+        "file:///mod.tsx",
+        concat!(
+          "/** @jsxImportSource npm:preact */\n",
+          "import { Title } from './title.tsx';\n",
+          "export function App(): JSX.Element {\n",
+          "  return <div><Title /></div>;\n",
+          "}\n",
+        ),
+      );
+      loader.add_file(
+        "file:///title.tsx",
+        concat!(
+          "/** @jsx h */\n",
+          "export function Title(): JSX.Element {\n",
+          "  return <h1 />;\n",
+          "}\n",
+        ),
+      );
+    })
+    .entry_point("file:///mod.tsx")
+    .jsx(JsxOptions {
+      jsx: JsxTransform::ReactJsx,
+      jsx_import_source: Some("npm:react".to_string()),
+      ..Default::default()
+    });
+
+  let result = builder.pack().await.unwrap();
+  // each module imports the runtime of its import source
+  assert_eq!(
+    result.output.entry_points[0].js,
+    r#"import * as pack2 from "npm:preact/jsx-runtime";
+import * as pack3 from "npm:react/jsx-runtime";
+const pack0 = {
+  Title: undefined
+};
+(function title_tsx() {
+  const _jsx = pack3.jsx;
+  function Title() {
+    return /*#__PURE__*/ _jsx("h1", {});
+  }
+  Object.defineProperty(pack0, "Title", {
+    get: ()=>Title
+  });
+})();
+const _jsx = pack2.jsx;
+export function App() {
+  return /*#__PURE__*/ _jsx("div", {
+    children: /*#__PURE__*/ _jsx(pack0.Title, {})
+  });
+}
+"#
+  );
+  let diagnostics = result
+    .diagnostics
+    .iter()
+    .map(|d| (d.code, d.specifier.as_str(), d.message.as_str()))
+    .collect::<Vec<_>>();
+  assert_eq!(
+    diagnostics,
+    vec![(
+      DiagnosticCode::UnsupportedJsxPragma,
+      "file:///title.tsx",
+      "The @jsx pragma is not supported with the configured JSX transform.",
+    )]
+  );
+}
+
+#[tokio::test]
+async fn test_deno_json_import_map_field() {
+  let mut builder = TestBuilder::new();
+  builder
+    .with_loader(|loader| {
+      loader.add_file(
+        "file:///project/deno.jsonc",
+        r#"{
+  // points to an external import map
+  "importMap": "./maps/import_map.json",
+  "imports": {
+    "utils": "./src/utils.ts"
+  }
+}"#,
+      );
+      loader.add_file(
+        "file:///project/maps/import_map.json",
+        r#"{
+  "imports": {
+    "utils": "./ignored.ts",
+    "math": "../src/math.ts",
+    "missing": "../src/missing.ts"
+  },
+  "scopes": {
+    "../src/": {
+      "helpers": "../src/helpers.ts"
+    }
+  }
+}"#,
+      );
+      loader.add_file(
+        "file:///project/mod.ts",
+        "export { add } from 'math';\nexport { format } from 'utils';\n",
+      );
+      loader.add_file(
+        "file:///project/src/math.ts",
+        concat!(
+          "import { double } from 'helpers';\n",
+          "export function add(a: number, b: number): number {\n",
+          "  return double(a) + b;\n",
+          "}\n",
+        ),
+      );
+      loader.add_file(
+        "file:///project/src/helpers.ts",
+        "export function double(a: number): number { return a * 2; }\n",
+      );
+      loader.add_file(
+        "file:///project/src/utils.ts",
+        "export function format(text: string): string { return text; }\n",
+      );
+      loader
+        .add_file("file:///project/missing.ts", "export * from 'missing';\n");
+    })
+    .entry_point("file:///project/mod.ts")
+    .import_map("file:///project/deno.jsonc")
+    .minimal_import_map(Default::default());
+
+  let result = builder.pack().await.unwrap();
+  assert_eq!(
+    result.output.entry_points[0].js,
+    r#"const pack1 = {
+  add: undefined
+};
+const pack3 = {
+  double: undefined
+};
+const pack2 = {
+  format: undefined
+};
+(function _src_helpers_ts() {
+  function double(a) {
+    return a * 2;
+  }
+  Object.defineProperty(pack3, "double", {
+    get: ()=>double
+  });
+})();
+(function _src_math_ts() {
+  function add(a, b) {
+    return pack3.double(a) + b;
+  }
+  Object.defineProperty(pack1, "add", {
+    get: ()=>add
+  });
+})();
+(function _src_utils_ts() {
+  function format(text) {
+    return text;
+  }
+  Object.defineProperty(pack2, "format", {
+    get: ()=>format
+  });
+})();
+const _packReExport1 = pack1.add;
+export { _packReExport1 as add };
+const _packReExport2 = pack2.format;
+export { _packReExport2 as format };
+"#
+  );
+  // reports the file the entry came from
+  assert_eq!(
+    result.output.unused_import_map_entries,
+    vec![UnusedImportMapEntry {
+      specifier: ModuleSpecifier::parse("file:///project/maps/import_map.json")
+        .unwrap(),
+      scope: None,
+      key: "missing".to_string(),
+      used_by_tests: false,
+    }]
+  );
+
+  builder.entry_point("file:///project/missing.ts");
+  let err = builder.pack().await.err().unwrap();
+  assert_eq!(
+    format!("{:#}", err),
+    concat!(
+      "Import map entry \"missing\" from file:///project/maps/import_map.json ",
+      "mapped to file:///project/src/missing.ts: ",
+      "Module not found \"file:///project/src/missing.ts\"."
+    )
+  );
+}
+
+struct NullReporter;
+
+impl Reporter for NullReporter {
+  fn diagnostic(&self, diagnostic: Diagnostic) {
+    panic!("Unexpected diagnostic: {:?}", diagnostic);
+  }
+}
+
+fn find_line_and_col(text: &str, search: &str) -> (u32, u32) {
+  for (line_index, line) in text.lines().enumerate() {
+    if let Some(col) = line.find(search) {
+      return (line_index as u32, col as u32);
+    }
+  }
+  panic!("Could not find {} in {}", search, text);
+}
+
 struct Spec {
   files: Vec<File>,
   output_file: File,
@@ -141,12 +1445,12 @@ fn parse_spec(text: String) -> Spec {
   let mut files = Vec::new();
   let mut current_file = None;
   for line in text.split('\n') {
-    if line.starts_with("# ") {
+    if let Some(specifier) = line.strip_prefix("# ") {
       if let Some(file) = current_file.take() {
         files.push(file);
       }
       current_file = Some(File {
-        specifier: line[2..].trim().to_string(),
+        specifier: specifier.trim().to_string(),
         text: String::new(),
       });
     } else {